@@ -0,0 +1,180 @@
+use clap::Parser;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::service::{
+    ServiceOptions, Toolset, default_config_path, default_memory_dir, default_prompts_dir,
+    default_skills_dir,
+};
+
+/// Default MCP server for the Dive client
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Comma separated toolsets to mount (defaults to all)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Option<Vec<Toolset>>,
+
+    /// Path of the config file (defaults to ~/.dive/mcp/fs.json)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Directory to allow without asking the user, can be repeated
+    #[arg(long = "allow-dir", value_name = "DIR")]
+    pub allow_dirs: Vec<PathBuf>,
+
+    /// Refuse every operation that modifies files
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Directory where the permission policy approves reads, can be repeated
+    #[arg(long = "policy-read-dir", value_name = "DIR")]
+    pub policy_read_dirs: Vec<PathBuf>,
+
+    /// Directory where the permission policy approves file operations and
+    /// read-only commands, can be repeated
+    #[arg(long = "policy-scratch-dir", value_name = "DIR")]
+    pub policy_scratch_dirs: Vec<PathBuf>,
+
+    /// Never ask the user, decide permission requests with the policy only
+    #[arg(long)]
+    pub non_interactive: bool,
+
+    /// Seconds the user has to answer a permission request before it is denied
+    #[arg(long, value_name = "SECS")]
+    pub elicitation_timeout: Option<u64>,
+
+    /// Directory of prompt templates (defaults to ~/.dive/prompts)
+    #[arg(long, value_name = "DIR")]
+    pub prompts_dir: Option<PathBuf>,
+
+    /// Directory of installed skills (defaults to ~/.dive/skills)
+    #[arg(long, value_name = "DIR", env = "DIVE_SKILL_DIR")]
+    pub skills_dir: Option<PathBuf>,
+
+    /// Directory of the persistent memory store (defaults to ~/.dive/mcp/memory)
+    #[arg(long, value_name = "DIR")]
+    pub memory_dir: Option<PathBuf>,
+
+    /// Log filter, e.g. `info` or `dive_mcp=debug`
+    #[arg(long, default_value = "warn")]
+    pub log_level: String,
+
+    /// Write logs to this file instead of stderr
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Serve MCP Streamable HTTP on this address instead of stdio
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+
+    /// Bearer token HTTP clients must send
+    #[arg(long, env = "DIVE_MCP_HTTP_TOKEN", hide_env_values = true)]
+    pub http_token: Option<String>,
+
+    /// Generate a random bearer token when none is set and print it to stderr
+    #[arg(long)]
+    pub print_http_token: bool,
+
+    /// Browser origin allowed to call the HTTP transport besides localhost, can be repeated
+    #[arg(long = "http-allowed-origin", value_name = "ORIGIN")]
+    pub http_allowed_origins: Vec<String>,
+}
+
+/// Canonicalize the directories given to a command line flag
+fn canonical_dirs(dirs: &[PathBuf], flag: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut canonical = Vec::new();
+    for dir in dirs.iter() {
+        let dir = std::fs::canonicalize(dir)
+            .map_err(|e| format!("Invalid {} {}: {}", flag, dir.display(), e))?;
+        canonical.push(dir.to_string_lossy().to_string());
+    }
+    Ok(canonical)
+}
+
+impl Cli {
+    /// Set up the global tracing subscriber
+    ///
+    /// Logs never go to stdout, which carries the MCP stdio transport.
+    pub fn init_logging(&self) -> Result<(), Box<dyn Error>> {
+        let filter = tracing_subscriber::EnvFilter::try_new(&self.log_level)
+            .map_err(|e| format!("Invalid log level {}: {}", self.log_level, e))?;
+        let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+        match self.log_file.as_ref() {
+            Some(path) => {
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+                builder
+                    .with_ansi(false)
+                    .with_writer(std::sync::Arc::new(file))
+                    .try_init()
+                    .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+            }
+            None => builder
+                .with_writer(std::io::stderr)
+                .try_init()
+                .map_err(|e| format!("Failed to initialize logging: {}", e))?,
+        }
+
+        Ok(())
+    }
+
+    /// Bearer token for the HTTP transport
+    ///
+    /// A random token is only generated when `--print-http-token` asks for it,
+    /// so secrets never end up in logs by default.
+    pub fn http_token(&self) -> Result<String, Box<dyn Error>> {
+        match self.http_token.as_ref().filter(|t| !t.is_empty()) {
+            Some(token) => Ok(token.clone()),
+            None if self.print_http_token => {
+                let token = uuid::Uuid::new_v4().simple().to_string();
+                eprintln!("Generated HTTP bearer token: {}", token);
+                Ok(token)
+            }
+            None => Err(
+                "The HTTP transport needs a bearer token, set --http-token or \
+                 DIVE_MCP_HTTP_TOKEN, or pass --print-http-token to generate one"
+                    .into(),
+            ),
+        }
+    }
+
+    /// Resolve the options the service is started with
+    pub fn service_options(&self) -> Result<ServiceOptions, Box<dyn Error>> {
+        let config_path = match self.config.clone() {
+            Some(path) => path,
+            None => default_config_path()?,
+        };
+
+        let allow_dirs = canonical_dirs(&self.allow_dirs, "--allow-dir")?;
+        let policy_read_dirs = canonical_dirs(&self.policy_read_dirs, "--policy-read-dir")?;
+        let policy_scratch_dirs =
+            canonical_dirs(&self.policy_scratch_dirs, "--policy-scratch-dir")?;
+
+        let mut toolsets = Vec::new();
+        for toolset in self.tools.as_deref().unwrap_or(Toolset::ALL) {
+            if !toolsets.contains(toolset) {
+                toolsets.push(*toolset);
+            }
+        }
+
+        Ok(ServiceOptions {
+            config_path,
+            toolsets,
+            allow_dirs,
+            policy_read_dirs,
+            policy_scratch_dirs,
+            non_interactive: self.non_interactive,
+            elicitation_timeout: self.elicitation_timeout,
+            read_only: self.read_only,
+            prompts_dir: self.prompts_dir.clone().or_else(default_prompts_dir),
+            skills_dir: self.skills_dir.clone().or_else(default_skills_dir),
+            memory_dir: self.memory_dir.clone().or_else(default_memory_dir),
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use rmcp::transport::streamable_http_server::{
+    SessionManager, StreamableHttpServerConfig, StreamableHttpService,
+    session::local::LocalSessionManager,
+};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::service::DiveDefaultService;
+
+/// Path the MCP endpoint is served on
+const MCP_PATH: &str = "/mcp";
+/// Time open connections get to finish once shutdown has started
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+/// Hosts of browser origins that are always allowed
+const LOCAL_ORIGIN_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Compare two tokens without leaking the position of the first mismatch
+fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Reject requests without the expected `Authorization: Bearer` token
+async fn require_bearer_token(
+    State(token): State<Arc<String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|v| token_eq(v.trim().as_bytes(), token.as_bytes()));
+
+    if !authorized {
+        tracing::warn!("rejected unauthorized request to {}", request.uri());
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Unauthorized",
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Whether a browser `Origin` may call the server, the local host or an
+/// explicitly allowed origin
+fn origin_allowed(origin: &str, allowed_origins: &[String]) -> bool {
+    let origin = origin.trim_end_matches('/');
+    if allowed_origins
+        .iter()
+        .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+    {
+        return true;
+    }
+    reqwest::Url::parse(origin).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https")
+            && url
+                .host_str()
+                .is_some_and(|host| LOCAL_ORIGIN_HOSTS.contains(&host))
+    })
+}
+
+/// Reject browser requests from foreign origins, protecting the local server
+/// against DNS rebinding. Requests without `Origin` come from non-browser clients.
+async fn validate_origin(
+    State(allowed_origins): State<Arc<Vec<String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        let allowed = origin
+            .to_str()
+            .is_ok_and(|origin| origin_allowed(origin, &allowed_origins));
+        if !allowed {
+            tracing::warn!("rejected request from origin {:?}", origin);
+            return (StatusCode::FORBIDDEN, "Forbidden origin").into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Resolve when the process is asked to stop
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutting down MCP HTTP server");
+}
+
+/// Serve MCP over Streamable HTTP until a shutdown signal is received
+///
+/// Every MCP session gets its own service instance, so elicitation requests
+/// go back to the client session that issued the tool call.
+pub async fn serve(
+    addr: SocketAddr,
+    token: String,
+    allowed_origins: Vec<String>,
+    service: DiveDefaultService,
+) -> Result<(), Box<dyn Error>> {
+    let session_manager = Arc::new(LocalSessionManager::default());
+    let mcp_service = StreamableHttpService::new(
+        move || Ok(service.new_session()),
+        session_manager.clone(),
+        StreamableHttpServerConfig::default(),
+    );
+
+    let router = axum::Router::new()
+        .nest_service(MCP_PATH, mcp_service)
+        .layer(middleware::from_fn_with_state(
+            Arc::new(token),
+            require_bearer_token,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(allowed_origins),
+            validate_origin,
+        ));
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(
+        "serving MCP streamable HTTP on http://{}{}",
+        listener.local_addr()?,
+        MCP_PATH
+    );
+
+    // SSE streams stay open until their session closes, so shutting down
+    // closes every session and gives up waiting after a grace period
+    let shutdown = CancellationToken::new();
+    let server = axum::serve(listener, router).with_graceful_shutdown({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.cancel();
+            let ids: Vec<_> = session_manager
+                .sessions
+                .read()
+                .await
+                .keys()
+                .cloned()
+                .collect();
+            for id in ids {
+                let _ = session_manager.close_session(&id).await;
+            }
+        }
+    });
+
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } => {
+            tracing::warn!("connections still open after {:?}, stopping anyway", SHUTDOWN_GRACE);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_allowed() {
+        let allowed = vec!["https://app.example.com".to_string()];
+        assert!(origin_allowed("http://localhost:5173", &allowed));
+        assert!(origin_allowed("http://127.0.0.1", &allowed));
+        assert!(origin_allowed("http://[::1]:8080", &allowed));
+        assert!(origin_allowed("https://app.example.com/", &allowed));
+        assert!(!origin_allowed("http://localhost.evil.com", &allowed));
+        assert!(!origin_allowed("https://evil.example.com", &allowed));
+        assert!(!origin_allowed("null", &allowed));
+        assert!(!origin_allowed("file://localhost", &allowed));
+    }
+}
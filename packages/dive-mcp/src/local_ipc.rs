@@ -1,7 +1,7 @@
 use libdive_desktop::proto::ipc_service_client::IpcServiceClient;
 use libdive_desktop::proto::{
-    elicitation_result::Action, ElicitationRequest, McpStreamRequest, RequestedSchema,
-    SchemaProperty,
+    ElicitationRequest, McpStreamRequest, RequestedSchema, SchemaProperty,
+    elicitation_result::Action,
 };
 use rmcp::model::{ElicitationAction, ElicitationSchema, PrimitiveSchema, StringFormat};
 use std::collections::HashMap;
@@ -1,18 +1,28 @@
-use std::error::Error;
+use clap::Parser;
 use rmcp::ServiceExt;
+use std::error::Error;
 
+mod cli;
+mod http_server;
 #[cfg(feature = "local_ipc")]
 mod local_ipc;
 mod service;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = cli::Cli::parse();
+    cli.init_logging()?;
+    let options = cli.service_options()?;
+
+    let service = service::DiveDefaultService::new(options)?;
+
+    if let Some(addr) = cli.http {
+        return http_server::serve(addr, cli.http_token()?, cli.http_allowed_origins, service)
+            .await;
+    }
+
     let io = (tokio::io::stdin(), tokio::io::stdout());
-    service::DiveDefaultService::new()
-        .serve(io)
-        .await?
-        .waiting()
-        .await?;
+    service.serve(io).await?.waiting().await?;
 
     Ok(())
 }
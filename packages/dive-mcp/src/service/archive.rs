@@ -0,0 +1,986 @@
+use crate::service::{DiveDefaultService, run_blocking};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum number of entries listed by `list_archive`
+const LIST_LIMIT: usize = 1000;
+/// Maximum number of entries extracted from or written to an archive
+const MAX_ENTRIES: usize = 10_000;
+/// Maximum number of bytes extracted from an archive
+const MAX_EXTRACTED_SIZE: u64 = 1024 * 1024 * 1024;
+/// Maximum ratio between extracted bytes and archive size
+const MAX_COMPRESSION_RATIO: u64 = 100;
+/// Extracted size always allowed, whatever the ratio
+const RATIO_FLOOR: u64 = 10 * 1024 * 1024;
+
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    /// Guess the format from the file name
+    fn from_name(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// Detect the format of an existing archive, from its name then its first bytes
+    fn detect(path: &Path) -> Result<Self, String> {
+        if let Some(format) = Self::from_name(path) {
+            return Ok(format);
+        }
+
+        let mut header = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(262).read_to_end(&mut header))
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        if header.starts_with(b"PK\x03\x04") {
+            Ok(ArchiveFormat::Zip)
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Ok(ArchiveFormat::TarGz)
+        } else if header.get(257..262) == Some(b"ustar") {
+            Ok(ArchiveFormat::Tar)
+        } else {
+            Err(format!("Unrecognized archive format: {}", path.display()))
+        }
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ListArchiveParams {
+    /// The path to the archive (zip, tar or tar.gz)
+    path: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ExtractArchiveParams {
+    /// The path to the archive (zip, tar or tar.gz)
+    path: String,
+    /// The directory to extract into, created if missing
+    destination: String,
+    /// Replace files that already exist in the destination
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct CreateArchiveParams {
+    /// Files and directories to put in the archive, each stored under its own name
+    paths: Vec<String>,
+    /// The path of the archive to create
+    destination: String,
+    /// Archive format (zip, tar or tar.gz), guessed from the destination name if omitted
+    #[serde(default)]
+    format: Option<ArchiveFormat>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveEntry {
+    name: String,
+    #[serde(rename = "type")]
+    entry_type: &'static str,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compressed_size: Option<u64>,
+}
+
+/// What was extracted or archived
+#[derive(Debug, Default)]
+struct ArchiveReport {
+    files: usize,
+    directories: usize,
+    bytes: u64,
+    /// Symlinks and special files, never extracted or archived
+    skipped: Vec<String>,
+}
+
+/// Relative path of an archive entry inside the destination, `None` when it
+/// would escape it (absolute paths, `..` components)
+fn safe_entry_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+fn zip_error(e: zip::result::ZipError) -> String {
+    format!("Invalid zip archive: {}", e)
+}
+
+fn io_error(context: &str, path: &Path, e: io::Error) -> String {
+    format!("{} {}: {}", context, path.display(), e)
+}
+
+fn open_tar(path: &Path, gzip: bool) -> Result<tar::Archive<Box<dyn Read>>, String> {
+    let file = File::open(path).map_err(|e| io_error("Failed to open", path, e))?;
+    let reader: Box<dyn Read> = if gzip {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// List the entries of an archive, returning at most `LIST_LIMIT` of them
+/// along with the total entry count and size
+fn list_entries(
+    path: &Path,
+    format: ArchiveFormat,
+) -> Result<(Vec<ArchiveEntry>, usize, u64), String> {
+    let mut entries = Vec::new();
+    let mut count = 0;
+    let mut total_size = 0;
+    let mut push = |entry: ArchiveEntry| {
+        count += 1;
+        total_size += entry.size;
+        if entries.len() < LIST_LIMIT {
+            entries.push(entry);
+        }
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            let file = File::open(path).map_err(|e| io_error("Failed to open", path, e))?;
+            let mut zip = zip::ZipArchive::new(file).map_err(zip_error)?;
+            for i in 0..zip.len() {
+                let entry = zip.by_index_raw(i).map_err(zip_error)?;
+                let entry_type = if entry.is_dir() {
+                    "directory"
+                } else if entry.is_symlink() {
+                    "symlink"
+                } else {
+                    "file"
+                };
+                push(ArchiveEntry {
+                    name: entry.name().to_string(),
+                    entry_type,
+                    size: entry.size(),
+                    compressed_size: Some(entry.compressed_size()),
+                });
+            }
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut archive = open_tar(path, format == ArchiveFormat::TarGz)?;
+            let tar_entries = archive
+                .entries()
+                .map_err(|e| format!("Invalid tar archive: {}", e))?;
+            for entry in tar_entries {
+                let entry = entry.map_err(|e| format!("Invalid tar archive: {}", e))?;
+                let header = entry.header();
+                let entry_type = match header.entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => "file",
+                    tar::EntryType::Directory => "directory",
+                    tar::EntryType::Symlink | tar::EntryType::Link => "symlink",
+                    tar::EntryType::XGlobalHeader => continue,
+                    _ => "other",
+                };
+                push(ArchiveEntry {
+                    name: String::from_utf8_lossy(&entry.path_bytes()).to_string(),
+                    entry_type,
+                    size: header.size().unwrap_or(0),
+                    compressed_size: None,
+                });
+            }
+        }
+    }
+
+    Ok((entries, count, total_size))
+}
+
+/// Limits applied while extracting, against zip bombs
+struct ExtractBudget {
+    limit: u64,
+    remaining_bytes: u64,
+    remaining_entries: usize,
+}
+
+impl ExtractBudget {
+    /// The extracted size may not exceed `MAX_COMPRESSION_RATIO` times the
+    /// archive size, nor `MAX_EXTRACTED_SIZE`
+    fn new(archive_size: u64) -> Self {
+        let limit = archive_size
+            .saturating_mul(MAX_COMPRESSION_RATIO)
+            .clamp(RATIO_FLOOR, MAX_EXTRACTED_SIZE);
+        Self {
+            limit,
+            remaining_bytes: limit,
+            remaining_entries: MAX_ENTRIES,
+        }
+    }
+
+    fn take_entry(&mut self) -> Result<(), String> {
+        if self.remaining_entries == 0 {
+            return Err(format!(
+                "Archive has more than {} entries, refusing to extract",
+                MAX_ENTRIES
+            ));
+        }
+        self.remaining_entries -= 1;
+        Ok(())
+    }
+
+    /// Copy an entry, counting the bytes actually written rather than the
+    /// sizes claimed by the archive
+    fn copy(&mut self, reader: &mut dyn Read, writer: &mut dyn Write) -> Result<u64, String> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let bytes_read = reader
+                .read(&mut buffer)
+                .map_err(|e| format!("Failed to read archive entry: {}", e))?;
+            if bytes_read == 0 {
+                return Ok(written);
+            }
+            if bytes_read as u64 > self.remaining_bytes {
+                return Err(format!(
+                    "Archive expands to more than {} bytes, refusing to extract (possible zip bomb)",
+                    self.limit
+                ));
+            }
+            self.remaining_bytes -= bytes_read as u64;
+            written += bytes_read as u64;
+            writer
+                .write_all(&buffer[..bytes_read])
+                .map_err(|e| format!("Failed to write extracted file: {}", e))?;
+        }
+    }
+}
+
+fn write_entry(
+    target: &Path,
+    reader: &mut dyn Read,
+    mode: Option<u32>,
+    budget: &mut ExtractBudget,
+    report: &mut ArchiveReport,
+) -> Result<(), String> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| io_error("Failed to create", parent, e))?;
+    }
+    let mut file = File::create(target).map_err(|e| io_error("Failed to create", target, e))?;
+    report.bytes += budget.copy(reader, &mut file)?;
+    report.files += 1;
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(target, fs::Permissions::from_mode(mode & 0o755));
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+fn create_entry_dir(target: &Path, report: &mut ArchiveReport) -> Result<(), String> {
+    fs::create_dir_all(target).map_err(|e| io_error("Failed to create", target, e))?;
+    report.directories += 1;
+    Ok(())
+}
+
+fn unsafe_entry_error(name: &str) -> String {
+    format!(
+        "Refusing to extract {}: the entry path escapes the destination directory",
+        name
+    )
+}
+
+/// Extract every entry of the archive into `staging`
+fn extract_into(
+    path: &Path,
+    format: ArchiveFormat,
+    staging: &Path,
+) -> Result<ArchiveReport, String> {
+    let archive_size = fs::metadata(path)
+        .map_err(|e| io_error("Failed to open", path, e))?
+        .len();
+    let mut budget = ExtractBudget::new(archive_size);
+    let mut report = ArchiveReport::default();
+
+    match format {
+        ArchiveFormat::Zip => {
+            let file = File::open(path).map_err(|e| io_error("Failed to open", path, e))?;
+            let mut zip = zip::ZipArchive::new(file).map_err(zip_error)?;
+            for i in 0..zip.len() {
+                budget.take_entry()?;
+                let mut entry = zip.by_index(i).map_err(zip_error)?;
+                let name = entry.name().to_string();
+                let relative =
+                    safe_entry_path(Path::new(&name)).ok_or_else(|| unsafe_entry_error(&name))?;
+                if entry.is_symlink() {
+                    report.skipped.push(name);
+                } else if entry.is_dir() {
+                    create_entry_dir(&staging.join(relative), &mut report)?;
+                } else {
+                    if entry.size() > RATIO_FLOOR
+                        && entry.size() / entry.compressed_size().max(1) > MAX_COMPRESSION_RATIO
+                    {
+                        return Err(format!(
+                            "Refusing to extract {}: compression ratio above {} (possible zip bomb)",
+                            name, MAX_COMPRESSION_RATIO
+                        ));
+                    }
+                    let mode = entry.unix_mode();
+                    write_entry(
+                        &staging.join(relative),
+                        &mut entry,
+                        mode,
+                        &mut budget,
+                        &mut report,
+                    )?;
+                }
+            }
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut archive = open_tar(path, format == ArchiveFormat::TarGz)?;
+            let entries = archive
+                .entries()
+                .map_err(|e| format!("Invalid tar archive: {}", e))?;
+            for entry in entries {
+                let mut entry = entry.map_err(|e| format!("Invalid tar archive: {}", e))?;
+                let entry_type = entry.header().entry_type();
+                if entry_type == tar::EntryType::XGlobalHeader {
+                    continue;
+                }
+                budget.take_entry()?;
+                let entry_path = entry
+                    .path()
+                    .map_err(|e| format!("Invalid tar archive: {}", e))?
+                    .into_owned();
+                let name = entry_path.to_string_lossy().to_string();
+                let relative =
+                    safe_entry_path(&entry_path).ok_or_else(|| unsafe_entry_error(&name))?;
+                match entry_type {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => {
+                        let mode = entry.header().mode().ok();
+                        write_entry(
+                            &staging.join(relative),
+                            &mut entry,
+                            mode,
+                            &mut budget,
+                            &mut report,
+                        )?;
+                    }
+                    tar::EntryType::Directory => {
+                        create_entry_dir(&staging.join(relative), &mut report)?;
+                    }
+                    _ => report.skipped.push(name),
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Collect the relative paths of the directories and files under `dir`
+fn collect_tree(
+    root: &Path,
+    dir: &Path,
+    dirs: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        if path.is_dir() {
+            dirs.push(relative);
+            collect_tree(root, &path, dirs, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` stays inside `root` once existing symlinks are followed
+fn resolves_inside(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| ancestor.canonicalize().ok())
+        .is_some_and(|resolved| resolved.starts_with(root))
+}
+
+/// Move the extracted tree from `staging` into `root`, after checking that no
+/// file would be overwritten (unless allowed) or written through a symlink
+fn move_staged(staging: &Path, root: &Path, overwrite: bool) -> Result<(), String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    collect_tree(staging, staging, &mut dirs, &mut files)
+        .map_err(|e| io_error("Failed to read", staging, e))?;
+
+    let mut conflicts = Vec::new();
+    for relative in dirs.iter().chain(files.iter()) {
+        let target = root.join(relative);
+        if !resolves_inside(root, &target) {
+            return Err(format!(
+                "Refusing to extract {}: it would be written through a symlink outside the destination",
+                relative.display()
+            ));
+        }
+    }
+    for relative in &dirs {
+        let target = root.join(relative);
+        if target.symlink_metadata().is_ok() && !target.is_dir() {
+            conflicts.push(relative.display().to_string());
+        }
+    }
+    for relative in &files {
+        let target = root.join(relative);
+        if target.is_dir() || (!overwrite && target.symlink_metadata().is_ok()) {
+            conflicts.push(relative.display().to_string());
+        }
+    }
+    if !conflicts.is_empty() {
+        let shown = conflicts.iter().take(10).cloned().collect::<Vec<_>>();
+        return Err(format!(
+            "Extraction would overwrite {} existing path(s): {}{}. Set overwrite to replace files",
+            conflicts.len(),
+            shown.join(", "),
+            if conflicts.len() > shown.len() {
+                ", ..."
+            } else {
+                ""
+            }
+        ));
+    }
+
+    for relative in &dirs {
+        let target = root.join(relative);
+        fs::create_dir_all(&target).map_err(|e| io_error("Failed to create", &target, e))?;
+    }
+    for relative in &files {
+        let target = root.join(relative);
+        if overwrite && target.symlink_metadata().is_ok() {
+            fs::remove_file(&target).map_err(|e| io_error("Failed to replace", &target, e))?;
+        }
+        fs::rename(staging.join(relative), &target)
+            .map_err(|e| io_error("Failed to write", &target, e))?;
+    }
+    Ok(())
+}
+
+/// Extract an archive through a staging directory inside the destination, so
+/// that nothing is written outside of it and a rejected archive leaves no file
+fn extract(
+    path: &Path,
+    format: ArchiveFormat,
+    destination: &Path,
+    overwrite: bool,
+) -> Result<ArchiveReport, String> {
+    fs::create_dir_all(destination).map_err(|e| io_error("Failed to create", destination, e))?;
+    let root = destination
+        .canonicalize()
+        .map_err(|e| io_error("Failed to open", destination, e))?;
+    let staging = root.join(format!(".dive-extract-{}", uuid::Uuid::new_v4()));
+    fs::create_dir(&staging).map_err(|e| io_error("Failed to create", &staging, e))?;
+
+    let result = extract_into(path, format, &staging)
+        .and_then(|report| move_staged(&staging, &root, overwrite).map(|_| report));
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// A file or directory to write into a new archive
+struct SourceEntry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    size: u64,
+    mode: Option<u32>,
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Walk a source path, skipping symlinks and the archive being written
+fn collect_sources(
+    path: &Path,
+    name: String,
+    exclude: &[PathBuf],
+    entries: &mut Vec<SourceEntry>,
+    report: &mut ArchiveReport,
+) -> Result<(), String> {
+    if exclude.iter().any(|excluded| excluded == path) {
+        return Ok(());
+    }
+    if entries.len() >= MAX_ENTRIES {
+        return Err(format!(
+            "More than {} files to archive, refusing to create the archive",
+            MAX_ENTRIES
+        ));
+    }
+
+    let metadata = fs::symlink_metadata(path).map_err(|e| io_error("Failed to read", path, e))?;
+    if metadata.is_dir() {
+        entries.push(SourceEntry {
+            path: path.to_path_buf(),
+            name: name.clone(),
+            is_dir: true,
+            size: 0,
+            mode: file_mode(&metadata),
+        });
+        let mut children = fs::read_dir(path)
+            .and_then(|dir| {
+                dir.map(|entry| entry.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| io_error("Failed to read", path, e))?;
+        children.sort();
+        for child in children {
+            let child_name = child.file_name().unwrap_or_default().to_string_lossy();
+            collect_sources(
+                &child,
+                format!("{}/{}", name, child_name),
+                exclude,
+                entries,
+                report,
+            )?;
+        }
+    } else if metadata.is_file() {
+        entries.push(SourceEntry {
+            path: path.to_path_buf(),
+            name,
+            is_dir: false,
+            size: metadata.len(),
+            mode: file_mode(&metadata),
+        });
+    } else {
+        report.skipped.push(path.display().to_string());
+    }
+    Ok(())
+}
+
+fn write_zip(writer: impl Write + io::Seek, entries: &[SourceEntry]) -> Result<(), String> {
+    use zip::write::SimpleFileOptions;
+
+    let mut zip = zip::ZipWriter::new(writer);
+    for entry in entries {
+        let mut options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(entry.size >= u32::MAX as u64);
+        if let Some(mode) = entry.mode {
+            options = options.unix_permissions(mode);
+        }
+        if entry.is_dir {
+            zip.add_directory(format!("{}/", entry.name), options)
+                .map_err(zip_error)?;
+        } else {
+            zip.start_file(entry.name.as_str(), options)
+                .map_err(zip_error)?;
+            let mut file =
+                File::open(&entry.path).map_err(|e| io_error("Failed to read", &entry.path, e))?;
+            io::copy(&mut file, &mut zip)
+                .map_err(|e| io_error("Failed to read", &entry.path, e))?;
+        }
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, entries: &[SourceEntry]) -> Result<W, String> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for entry in entries {
+        let result = if entry.is_dir {
+            builder.append_dir(&entry.name, &entry.path)
+        } else {
+            builder.append_path_with_name(&entry.path, &entry.name)
+        };
+        result.map_err(|e| io_error("Failed to archive", &entry.path, e))?;
+    }
+    builder
+        .into_inner()
+        .map_err(|e| format!("Failed to write archive: {}", e))
+}
+
+/// Write an archive of `sources` to a partial file, renamed to `destination`
+/// once complete
+fn create(
+    sources: &[PathBuf],
+    destination: &Path,
+    format: ArchiveFormat,
+) -> Result<ArchiveReport, String> {
+    let partial = PathBuf::from(format!("{}.part", destination.display()));
+    let exclude = [destination.to_path_buf(), partial.clone()];
+
+    let mut report = ArchiveReport::default();
+    let mut entries = Vec::new();
+    let mut names = Vec::new();
+    for source in sources {
+        let name = source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Cannot archive {}: it has no file name", source.display()))?;
+        if names.contains(&name) {
+            return Err(format!(
+                "Two paths would be stored under the same name: {}",
+                name
+            ));
+        }
+        names.push(name.clone());
+        collect_sources(source, name, &exclude, &mut entries, &mut report)?;
+    }
+
+    let file = File::create(&partial).map_err(|e| io_error("Failed to create", &partial, e))?;
+    let written = match format {
+        ArchiveFormat::Zip => write_zip(BufWriter::new(file), &entries),
+        ArchiveFormat::Tar => write_tar(BufWriter::new(file), &entries).and_then(|mut writer| {
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to write archive: {}", e))
+        }),
+        ArchiveFormat::TarGz => {
+            let encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+            write_tar(encoder, &entries).and_then(|encoder| {
+                encoder
+                    .finish()
+                    .and_then(|mut writer| writer.flush())
+                    .map_err(|e| format!("Failed to write archive: {}", e))
+            })
+        }
+    };
+    if let Err(e) = written.and_then(|_| {
+        fs::rename(&partial, destination).map_err(|e| io_error("Failed to write", destination, e))
+    }) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    for entry in &entries {
+        if entry.is_dir {
+            report.directories += 1;
+        } else {
+            report.files += 1;
+            report.bytes += entry.size;
+        }
+    }
+    Ok(report)
+}
+
+fn internal_error(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, None)
+}
+
+fn json_result(result: serde_json::Value) -> CallToolResult {
+    CallToolResult::success(vec![Content::text(
+        serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+    )])
+}
+
+#[tool_router(router = tool_router_archive, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "List the entries of a zip, tar or tar.gz archive with their type and size",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn list_archive(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ListArchiveParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let path = PathBuf::from(&params.path);
+        let (entries, count, total_size, format) = run_blocking(move || {
+            let format = ArchiveFormat::detect(&path).map_err(internal_error)?;
+            list_entries(&path, format)
+                .map(|(entries, count, total_size)| (entries, count, total_size, format))
+                .map_err(internal_error)
+        })
+        .await?;
+
+        Ok(json_result(serde_json::json!({
+            "path": params.path,
+            "format": format.as_str(),
+            "totalEntries": count,
+            "totalSize": total_size,
+            "truncated": count > entries.len(),
+            "entries": entries,
+        })))
+    }
+
+    #[tool(
+        description = "Extract a zip, tar or tar.gz archive into a directory. Entries escaping the destination, symlinks and archives expanding to more than 1 GiB or 100 times their size are refused. Existing files are kept unless overwrite is set",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn extract_archive(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ExtractArchiveParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+        self.check_path_permission_with_elicitation(&params.destination, "write", &peer)
+            .await?;
+
+        let path = PathBuf::from(&params.path);
+        let destination = PathBuf::from(&params.destination);
+        let overwrite = params.overwrite;
+        let (report, format) = run_blocking(move || {
+            let format = ArchiveFormat::detect(&path).map_err(internal_error)?;
+            extract(&path, format, &destination, overwrite)
+                .map(|report| (report, format))
+                .map_err(internal_error)
+        })
+        .await?;
+
+        Ok(json_result(serde_json::json!({
+            "path": params.path,
+            "destination": params.destination,
+            "format": format.as_str(),
+            "files": report.files,
+            "directories": report.directories,
+            "bytes": report.bytes,
+            "skipped": report.skipped,
+        })))
+    }
+
+    #[tool(
+        description = "Create a zip, tar or tar.gz archive from files and directories. Directories are added recursively, symlinks are skipped",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn create_archive(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<CreateArchiveParams>,
+    ) -> Result<CallToolResult, McpError> {
+        if params.paths.is_empty() {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                "No paths to archive".to_string(),
+                None,
+            ));
+        }
+        let format = params
+            .format
+            .or_else(|| ArchiveFormat::from_name(Path::new(&params.destination)))
+            .ok_or_else(|| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INVALID_PARAMS,
+                    "Cannot guess the archive format from the destination name, set format to zip, tar or tar.gz".to_string(),
+                    None,
+                )
+            })?;
+
+        for path in &params.paths {
+            self.check_path_permission_with_elicitation(path, "read", &peer)
+                .await?;
+        }
+        self.check_path_permission_with_elicitation(&params.destination, "write", &peer)
+            .await?;
+
+        let sources = params
+            .paths
+            .iter()
+            .map(|path| PathBuf::from(Self::normalize_path(path)))
+            .collect::<Vec<_>>();
+        let destination = PathBuf::from(Self::normalize_path(&params.destination));
+        let report =
+            run_blocking(move || create(&sources, &destination, format).map_err(internal_error))
+                .await?;
+        let archive_size = fs::metadata(&params.destination)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        Ok(json_result(serde_json::json!({
+            "path": params.destination,
+            "format": format.as_str(),
+            "files": report.files,
+            "directories": report.directories,
+            "bytes": report.bytes,
+            "archiveSize": archive_size,
+            "skipped": report.skipped,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_paths() {
+        assert_eq!(
+            safe_entry_path(Path::new("logs/./app.log")),
+            Some(PathBuf::from("logs/app.log"))
+        );
+        assert_eq!(safe_entry_path(Path::new("../etc/passwd")), None);
+        assert_eq!(safe_entry_path(Path::new("logs/../../x")), None);
+        assert_eq!(safe_entry_path(Path::new("/etc/passwd")), None);
+        assert_eq!(safe_entry_path(Path::new("./")), None);
+
+        assert_eq!(
+            ArchiveFormat::from_name(Path::new("/tmp/Out.TGZ")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_name(Path::new("a.zip")),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(ArchiveFormat::from_name(Path::new("a.gz")), None);
+
+        let mut budget = ExtractBudget::new(1024);
+        assert_eq!(budget.limit, RATIO_FLOOR);
+        let mut sink = Vec::new();
+        let data = vec![0u8; RATIO_FLOOR as usize + 1];
+        assert!(budget.copy(&mut data.as_slice(), &mut sink).is_err());
+    }
+    /// Tar archive with raw entry names, the tar builder refuses unsafe ones
+    fn tar_archive(path: &Path, entries: &[(&str, tar::EntryType, &str)]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, entry_type, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            if *entry_type == tar::EntryType::Symlink {
+                header.set_link_name_literal(data).unwrap();
+                header.set_size(0);
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            } else {
+                header.set_size(data.len() as u64);
+                header.set_cksum();
+                builder.append(&header, data.as_bytes()).unwrap();
+            }
+        }
+        builder.finish().unwrap();
+    }
+
+    fn tree(dir: &Path) -> Vec<String> {
+        let (mut dirs, mut files) = (Vec::new(), Vec::new());
+        collect_tree(dir, dir, &mut dirs, &mut files).unwrap();
+        let mut names: Vec<String> = dirs
+            .iter()
+            .chain(&files)
+            .map(|path| path.display().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_extract_unsafe_entries() {
+        let root =
+            std::env::temp_dir().join(format!("dive-archive-{}", uuid::Uuid::new_v4().simple()));
+        let destination = root.join("out");
+        fs::create_dir_all(&destination).unwrap();
+        let archive = root.join("a.tar");
+        let regular = tar::EntryType::Regular;
+
+        // Entries escaping the destination fail the whole archive, even after safe ones
+        for name in ["../evil.txt", "sub/../../evil.txt", "/tmp/dive-evil.txt"] {
+            tar_archive(&archive, &[("ok.txt", regular, "ok"), (name, regular, "x")]);
+            let error = extract(&archive, ArchiveFormat::Tar, &destination, false).unwrap_err();
+            assert!(error.contains("escapes the destination"), "{}", error);
+            assert!(tree(&destination).is_empty(), "{}", name);
+        }
+        assert_eq!(tree(&root), ["a.tar", "out"]);
+
+        let zip_path = root.join("a.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("ok.txt", options).unwrap();
+        zip.write_all(b"ok").unwrap();
+        zip.start_file("../evil.txt", options).unwrap();
+        zip.write_all(b"x").unwrap();
+        zip.finish().unwrap();
+        assert!(extract(&zip_path, ArchiveFormat::Zip, &destination, false).is_err());
+        assert!(tree(&destination).is_empty());
+        assert!(!root.join("evil.txt").exists());
+
+        // Symlink entries are skipped, files under their name land in a real directory
+        tar_archive(
+            &archive,
+            &[
+                ("link", tar::EntryType::Symlink, "/etc"),
+                ("link/passwd", regular, "x"),
+            ],
+        );
+        let report = extract(&archive, ArchiveFormat::Tar, &destination, false).unwrap();
+        assert_eq!(report.skipped, ["link"]);
+        assert!(!destination.join("link").is_symlink());
+        assert_eq!(tree(&destination), ["link", "link/passwd"]);
+
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.add_symlink("zip-link", "/etc/passwd", options).unwrap();
+        zip.finish().unwrap();
+        let report = extract(&zip_path, ArchiveFormat::Zip, &destination, false).unwrap();
+        assert_eq!(report.skipped, ["zip-link"]);
+        assert!(destination.join("zip-link").symlink_metadata().is_err());
+
+        // An existing symlink in the destination is never written through
+        #[cfg(unix)]
+        {
+            let outside = root.join("outside");
+            fs::create_dir(&outside).unwrap();
+            std::os::unix::fs::symlink(&outside, destination.join("escape")).unwrap();
+            tar_archive(&archive, &[("escape/x.txt", regular, "x")]);
+            let error = extract(&archive, ArchiveFormat::Tar, &destination, true).unwrap_err();
+            assert!(error.contains("through a symlink"), "{}", error);
+            assert!(tree(&outside).is_empty());
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
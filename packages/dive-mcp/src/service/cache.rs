@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// Default upper bound of the on-disk cache size (100 MiB)
+pub const DEFAULT_CACHE_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Response headers that are replaced by a `304 Not Modified` response
+const REVALIDATION_HEADERS: &[&str] = &[
+    "cache-control",
+    "date",
+    "etag",
+    "expires",
+    "last-modified",
+    "age",
+    "vary",
+];
+
+/// Request headers carrying credentials, their responses are only stored when public
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "cookie"];
+
+/// A cached `GET` response stored as one JSON file
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedResponse {
+    pub url: String,
+    pub status: u16,
+    pub status_text: String,
+    /// Response headers with lowercase names
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    /// Request header values selected by the response `Vary` header
+    pub vary: HashMap<String, String>,
+    /// Unix time the request was sent
+    pub request_time: u64,
+    /// Unix time the response was received
+    pub response_time: u64,
+}
+
+/// Directives of a `Cache-Control` header that affect a private cache
+#[derive(Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    public: bool,
+    private: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(value: Option<&String>) -> Self {
+        let mut cc = Self::default();
+        for directive in value.map(|v| v.split(',')).into_iter().flatten() {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "public" => cc.public = true,
+                "private" => cc.private = true,
+                "max-age" => cc.max_age = arg.and_then(|v| v.parse().ok()),
+                _ => {}
+            }
+        }
+        cc
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse_http_date(value: Option<&String>) -> Option<u64> {
+    let time = httpdate::parse_http_date(value?).ok()?;
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+impl CachedResponse {
+    /// Whether the response may be written to the cache at all
+    ///
+    /// The cache is kept on disk and shared by every later request, so
+    /// `private` responses are never stored, and responses to requests sending
+    /// credentials only when marked `public`.
+    pub fn is_storable(&self, request_headers: &HashMap<String, String>) -> bool {
+        let cc = CacheControl::parse(self.headers.get("cache-control"));
+        let has_credentials = CREDENTIAL_HEADERS
+            .iter()
+            .any(|name| request_header(request_headers, name).is_some());
+        self.status == 200
+            && !cc.no_store
+            && !cc.private
+            && (cc.public || !has_credentials)
+            && self.headers.get("vary").map(|v| v.trim()) != Some("*")
+    }
+
+    /// Freshness lifetime in seconds (RFC 7234 section 4.2.1)
+    fn freshness_lifetime(&self) -> u64 {
+        let cc = CacheControl::parse(self.headers.get("cache-control"));
+        if let Some(max_age) = cc.max_age {
+            return max_age;
+        }
+
+        let date = parse_http_date(self.headers.get("date")).unwrap_or(self.response_time);
+        if let Some(expires) = self.headers.get("expires") {
+            // An invalid Expires value means already expired
+            return parse_http_date(Some(expires))
+                .map(|expires| expires.saturating_sub(date))
+                .unwrap_or(0);
+        }
+
+        // Heuristic freshness: 10% of the time since the last modification
+        parse_http_date(self.headers.get("last-modified"))
+            .map(|modified| date.saturating_sub(modified) / 10)
+            .unwrap_or(0)
+    }
+
+    /// Current age in seconds (RFC 7234 section 4.2.3)
+    pub fn current_age(&self, now: u64) -> u64 {
+        let date = parse_http_date(self.headers.get("date")).unwrap_or(self.response_time);
+        let apparent_age = self.response_time.saturating_sub(date);
+        let age_value = self
+            .headers
+            .get("age")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        let corrected_initial_age = apparent_age.max(age_value + response_delay);
+        let resident_time = now.saturating_sub(self.response_time);
+        corrected_initial_age + resident_time
+    }
+
+    /// Whether the response can be served without contacting the origin
+    pub fn is_fresh(&self, now: u64) -> bool {
+        !CacheControl::parse(self.headers.get("cache-control")).no_cache
+            && self.freshness_lifetime() > self.current_age(now)
+    }
+
+    /// Conditional request headers for revalidating this response
+    pub fn validators(&self) -> Vec<(&'static str, String)> {
+        let mut validators = Vec::new();
+        if let Some(etag) = self.headers.get("etag") {
+            validators.push(("if-none-match", etag.clone()));
+        }
+        if let Some(modified) = self.headers.get("last-modified") {
+            validators.push(("if-modified-since", modified.clone()));
+        }
+        validators
+    }
+
+    /// Whether the request headers select this stored response
+    fn matches_vary(&self, request_headers: &HashMap<String, String>) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_header(request_headers, name).unwrap_or("") == value)
+    }
+
+    /// Record the request header values named by the response `Vary` header
+    pub fn capture_vary(&mut self, request_headers: &HashMap<String, String>) {
+        self.vary = self
+            .headers
+            .get("vary")
+            .map(|v| v.split(','))
+            .into_iter()
+            .flatten()
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = request_header(request_headers, &name)
+                    .unwrap_or("")
+                    .to_string();
+                (name, value)
+            })
+            .collect();
+    }
+
+    /// Update the stored response with the headers of a `304 Not Modified`
+    pub fn refresh(&mut self, headers: &HashMap<String, String>, request_time: u64, now: u64) {
+        for name in REVALIDATION_HEADERS {
+            if let Some(value) = headers.get(*name) {
+                self.headers.insert(name.to_string(), value.clone());
+            }
+        }
+        self.request_time = request_time;
+        self.response_time = now;
+    }
+}
+
+fn request_header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Size-bounded on-disk cache for `fetch` GET responses
+///
+/// Each entry is a JSON file named after the SHA-256 of its URL. The file
+/// modification time tracks the last access and drives LRU eviction.
+#[derive(Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    max_size: u64,
+    evict_lock: Arc<Mutex<()>>,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self {
+            dir,
+            max_size,
+            evict_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let digest = Sha256::digest(url.as_bytes());
+        self.dir.join(format!("{:x}.json", digest))
+    }
+
+    /// Look up the stored response for `url` that matches the request headers
+    pub async fn get(
+        &self,
+        url: &str,
+        request_headers: &HashMap<String, String>,
+    ) -> Option<CachedResponse> {
+        let path = self.entry_path(url);
+        let content = fs::read(&path).await.ok()?;
+        let entry: CachedResponse = serde_json::from_slice(&content).ok()?;
+        if entry.url != url || !entry.matches_vary(request_headers) {
+            return None;
+        }
+
+        // Mark as recently used
+        let _ = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|f| f.set_modified(SystemTime::now()))
+        })
+        .await;
+
+        Some(entry)
+    }
+
+    /// Store a response, evicting least recently used entries above the size bound
+    pub async fn put(&self, entry: &CachedResponse) -> Result<(), Box<dyn std::error::Error>> {
+        let content = serde_json::to_vec(entry)?;
+        if content.len() as u64 > self.max_size {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.entry_path(&entry.url);
+        // Unique per write, concurrent puts of one URL must not share a file
+        let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4().simple()));
+        fs::write(&tmp_path, &content).await?;
+        fs::rename(&tmp_path, &path).await?;
+
+        self.evict().await
+    }
+
+    /// Remove the stored response for `url`
+    pub async fn remove(&self, url: &str) {
+        let _ = fs::remove_file(self.entry_path(url)).await;
+    }
+
+    async fn evict(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = self.evict_lock.lock().await;
+
+        let mut entries = Vec::new();
+        let mut total = 0u64;
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(item) = dir.next_entry().await? {
+            let path = item.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Ok(metadata) = item.metadata().await {
+                let accessed = metadata.modified().unwrap_or(UNIX_EPOCH);
+                total += metadata.len();
+                entries.push((accessed, metadata.len(), path));
+            }
+        }
+
+        if total <= self.max_size {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(accessed, _, _)| *accessed);
+        for (_, size, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if fs::remove_file(&path).await.is_ok() {
+                total = total.saturating_sub(size);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(headers: &[(&str, &str)], response_time: u64) -> CachedResponse {
+        CachedResponse {
+            url: "https://example.com/".to_string(),
+            status: 200,
+            status_text: "OK".to_string(),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: String::new(),
+            vary: HashMap::new(),
+            request_time: response_time,
+            response_time,
+        }
+    }
+
+    #[test]
+    fn test_max_age_freshness() {
+        let e = entry(&[("cache-control", "public, max-age=60")], 1_000);
+        assert!(e.is_fresh(1_030));
+        assert!(!e.is_fresh(1_061));
+    }
+
+    #[test]
+    fn test_no_cache_requires_revalidation() {
+        let e = entry(&[("cache-control", "no-cache, max-age=600")], 1_000);
+        assert!(!e.is_fresh(1_001));
+        assert!(e.is_storable(&HashMap::new()));
+    }
+
+    #[test]
+    fn test_no_store_and_vary_star_not_storable() {
+        let request = HashMap::new();
+        assert!(!entry(&[("cache-control", "no-store")], 0).is_storable(&request));
+        assert!(!entry(&[("vary", "*")], 0).is_storable(&request));
+        assert!(!entry(&[("cache-control", "private, max-age=60")], 0).is_storable(&request));
+    }
+
+    #[test]
+    fn test_credentials_need_public() {
+        let mut request = HashMap::new();
+        request.insert("Authorization".to_string(), "Bearer secret".to_string());
+        assert!(!entry(&[("cache-control", "max-age=60")], 0).is_storable(&request));
+        assert!(entry(&[("cache-control", "public, max-age=60")], 0).is_storable(&request));
+
+        let mut request = HashMap::new();
+        request.insert("cookie".to_string(), "session=1".to_string());
+        assert!(!entry(&[], 0).is_storable(&request));
+    }
+
+    #[test]
+    fn test_heuristic_freshness_from_last_modified() {
+        // Date is 1000s after Last-Modified, so the response is fresh for 100s
+        let e = entry(
+            &[
+                ("date", "Thu, 01 Jan 1970 00:16:40 GMT"),
+                ("last-modified", "Thu, 01 Jan 1970 00:00:00 GMT"),
+            ],
+            1_000,
+        );
+        assert!(e.is_fresh(1_099));
+        assert!(!e.is_fresh(1_101));
+    }
+
+    #[test]
+    fn test_vary_matching() {
+        let mut e = entry(&[("vary", "Accept-Language")], 0);
+        let mut request = HashMap::new();
+        request.insert("Accept-Language".to_string(), "en".to_string());
+        e.capture_vary(&request);
+        assert!(e.matches_vary(&request));
+
+        request.insert("Accept-Language".to_string(), "fr".to_string());
+        assert!(!e.matches_vary(&request));
+    }
+}
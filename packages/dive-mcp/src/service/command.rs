@@ -0,0 +1,582 @@
+use crate::service::DiveDefaultService;
+use crate::service::permission::PermissionChoice;
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::Deserialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+/// Default command timeout in seconds
+pub(super) const DEFAULT_TIMEOUT: u64 = 60;
+/// Maximum command timeout in seconds
+pub(super) const MAX_TIMEOUT: u64 = 600;
+/// Maximum bytes of stdout and stderr each returned to the model
+pub(super) const OUTPUT_LIMIT: usize = 64 * 1024;
+/// Time to wait for output pipes to close after the command was killed
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Characters that make a command line compound, so prefix rules do not apply to it
+const SHELL_OPERATORS: &[char] = &[';', '&', '|', '`', '$', '>', '<', '(', ')', '\n', '\r'];
+
+/// Suffix of a rule that allows any arguments after its prefix
+const PREFIX_WILDCARD: &str = " *";
+
+/// Programs whose first argument names a subcommand, so a rule for
+/// `cargo test` can allow other arguments without allowing `cargo publish`
+const SUBCOMMAND_PROGRAMS: &[&str] = &[
+    "cargo", "git", "npm", "pnpm", "yarn", "bun", "deno", "go", "make", "docker", "kubectl", "pip",
+    "uv", "poetry", "dotnet", "mvn", "gradle",
+];
+
+/// Command settings, read from the `command` section of the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    /// Command rules the user allowed permanently, either an exact command
+    /// line or a prefix ending in ` *` that allows any further arguments
+    pub allow_prefix: Vec<String>,
+    /// Tools the user allowed permanently as a whole, kept apart from the
+    /// command rules so they never match a `run_command` command line
+    pub allow_tools: Vec<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct RunCommandParams {
+    /// The command line to run with the system shell
+    command: String,
+    /// The working directory, must be within the allowed directories
+    cwd: String,
+    /// Timeout in seconds (default 60, max 600)
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+/// Collapse runs of whitespace so rules match regardless of spacing
+fn normalize_command(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub(super) fn is_compound(command: &str) -> bool {
+    command.contains(SHELL_OPERATORS)
+}
+
+/// The rule remembered when the user always allows a command
+///
+/// Subcommands of known tools are remembered as a prefix (`cargo test *`),
+/// every other command line is only remembered verbatim, so allowing
+/// `rm -rf build` does not allow other `rm` commands.
+fn command_rule(command: &str) -> String {
+    if is_compound(command) {
+        return command.to_string();
+    }
+
+    let mut words = command.split(' ');
+    match (words.next(), words.next()) {
+        (Some(program), Some(sub))
+            if SUBCOMMAND_PROGRAMS.contains(&program) && !sub.starts_with('-') =>
+        {
+            format!("{} {}{}", program, sub, PREFIX_WILDCARD)
+        }
+        _ => command.to_string(),
+    }
+}
+
+fn matches_rule(command: &str, rule: &str) -> bool {
+    match rule.strip_suffix(PREFIX_WILDCARD) {
+        Some(prefix) => {
+            !is_compound(command)
+                && command
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        }
+        None => command == rule,
+    }
+}
+
+/// Read a stream to the end, keeping at most `limit` bytes
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, limit: usize) -> (String, usize) {
+    let mut kept = Vec::new();
+    let mut total = 0usize;
+    let mut buffer = vec![0u8; 8192];
+    while let Ok(bytes_read) = reader.read(&mut buffer).await {
+        if bytes_read == 0 {
+            break;
+        }
+        let room = limit.saturating_sub(kept.len());
+        kept.extend_from_slice(&buffer[..bytes_read.min(room)]);
+        total += bytes_read;
+    }
+    (String::from_utf8_lossy(&kept).to_string(), total)
+}
+
+/// Kill the command together with the processes it started
+///
+/// On unix the command must have been spawned in its own process group.
+async fn kill_command(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // The command runs in its own process group, see `shell_command`
+        unsafe {
+            libc::kill(-(pid as i32), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+}
+
+/// Kills the process group of a command when dropped
+///
+/// The tool future is dropped when the client cancels the request, which
+/// would otherwise leave the processes started by the command running.
+struct ProcessGroupGuard(#[cfg_attr(not(unix), allow(dead_code))] Option<u32>);
+
+impl ProcessGroupGuard {
+    fn new(child: &tokio::process::Child) -> Self {
+        Self(child.id())
+    }
+
+    /// The command has been waited for, leave its group alone
+    fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+fn shell_command(command: &str) -> tokio::process::Command {
+    #[cfg(unix)]
+    {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+    #[cfg(windows)]
+    {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    }
+}
+
+pub(super) enum Outcome {
+    Exited(std::process::ExitStatus),
+    TimedOut,
+}
+
+/// How a process ended and what it printed
+pub(super) struct ProcessOutput {
+    pub outcome: Outcome,
+    pub stdout: String,
+    /// Bytes written to stdout, including the ones not kept
+    pub stdout_total: usize,
+    pub stderr: String,
+    pub stderr_total: usize,
+}
+
+/// Run a process in its own process group, feeding it `stdin`, until it
+/// exits or times out
+///
+/// The whole process group is killed on timeout, and when the future is
+/// dropped because the client cancelled the request. `what` names the
+/// process in error messages.
+pub(super) async fn run_process(
+    mut cmd: tokio::process::Command,
+    what: &str,
+    stdin: Option<String>,
+    timeout: Duration,
+) -> Result<ProcessOutput, McpError> {
+    cmd.stdin(if stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    })
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = cmd.spawn().map_err(|e| {
+        McpError::new(
+            rmcp::model::ErrorCode::INTERNAL_ERROR,
+            format!("Failed to start {}: {}", what, e),
+            None,
+        )
+    })?;
+    let mut group = ProcessGroupGuard::new(&child);
+
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+        tokio::spawn(async move {
+            let _ = pipe.write_all(input.as_bytes()).await;
+        });
+    }
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| tokio::spawn(read_capped(out, OUTPUT_LIMIT)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| tokio::spawn(read_capped(err, OUTPUT_LIMIT)));
+
+    let outcome = tokio::select! {
+        status = child.wait() => match status {
+            Ok(status) => Outcome::Exited(status),
+            Err(e) => {
+                kill_command(&mut child).await;
+                return Err(McpError::new(
+                    rmcp::model::ErrorCode::INTERNAL_ERROR,
+                    format!("Failed to wait for {}: {}", what, e),
+                    None,
+                ));
+            }
+        },
+        _ = tokio::time::sleep(timeout) => Outcome::TimedOut,
+    };
+
+    if matches!(outcome, Outcome::TimedOut) {
+        kill_command(&mut child).await;
+    }
+    group.disarm();
+
+    let mut output = Vec::new();
+    for task in [stdout, stderr] {
+        let captured = match task {
+            Some(task) => tokio::time::timeout(DRAIN_TIMEOUT, task)
+                .await
+                .ok()
+                .and_then(|r| r.ok())
+                .unwrap_or_default(),
+            None => Default::default(),
+        };
+        output.push(captured);
+    }
+    let (stderr, stderr_total) = output.pop().unwrap_or_default();
+    let (stdout, stdout_total) = output.pop().unwrap_or_default();
+
+    Ok(ProcessOutput {
+        outcome,
+        stdout,
+        stdout_total,
+        stderr,
+        stderr_total,
+    })
+}
+
+#[tool_router(router = tool_router_command, vis = "pub")]
+impl DiveDefaultService {
+    /// The grant allowing a command without asking the user, if any
+    async fn command_grant(&self, command: &str) -> Option<&'static str> {
+        let normalized = normalize_command(command);
+        if self
+            .allowed_commands
+            .read()
+            .await
+            .iter()
+            .any(|rule| matches_rule(&normalized, rule))
+        {
+            return Some("allow_prefix");
+        }
+        self.session_grants
+            .commands
+            .read()
+            .await
+            .iter()
+            .any(|rule| matches_rule(&normalized, rule))
+            .then_some("session")
+    }
+
+    /// Add a rule to the command allow list and persist it
+    pub(super) async fn remember_command_rule(&self, rule: String) {
+        let mut allowed_commands = self.allowed_commands.write().await;
+        if !allowed_commands.contains(&rule) {
+            allowed_commands.push(rule);
+        }
+        drop(allowed_commands);
+
+        // Save to config
+        let _ = self.save_allowed_commands().await;
+    }
+
+    /// Add a rule to the command allow list of this MCP connection
+    pub(super) async fn remember_session_command_rule(&self, rule: String) {
+        let mut session_commands = self.session_grants.commands.write().await;
+        if !session_commands.contains(&rule) {
+            session_commands.push(rule);
+        }
+    }
+
+    /// The grant allowing a tool as a whole, permanently or for this session
+    async fn tool_grant(&self, key: &str) -> Option<&'static str> {
+        if self
+            .allowed_tools
+            .read()
+            .await
+            .iter()
+            .any(|tool| tool == key)
+        {
+            return Some("allow_tools");
+        }
+        self.session_grants
+            .tools
+            .read()
+            .await
+            .iter()
+            .any(|tool| tool == key)
+            .then_some("session")
+    }
+
+    /// Ask for permission to use a tool, remembering the grant under `key`
+    ///
+    /// A tool that was already allowed is answered with `Once`.
+    pub(super) async fn request_tool_permission(
+        &self,
+        key: &str,
+        message: String,
+        always_label: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<PermissionChoice, McpError> {
+        if let Some(grant) = self.tool_grant(key).await {
+            self.log_auto_approval(serde_json::json!({ "tool": key }), grant)
+                .await;
+            return Ok(PermissionChoice::Once);
+        }
+
+        let choice = self.request_permission(message, always_label, peer).await?;
+        match choice {
+            PermissionChoice::Always => {
+                let mut allowed_tools = self.allowed_tools.write().await;
+                if !allowed_tools.iter().any(|tool| tool == key) {
+                    allowed_tools.push(key.to_string());
+                }
+                drop(allowed_tools);
+                let _ = self.save_allowed_commands().await;
+            }
+            PermissionChoice::Session => {
+                let mut session_tools = self.session_grants.tools.write().await;
+                if !session_tools.iter().any(|tool| tool == key) {
+                    session_tools.push(key.to_string());
+                }
+            }
+            _ => {}
+        }
+        Ok(choice)
+    }
+
+    /// Check command permission with elicitation support
+    pub(super) async fn check_command_permission(
+        &self,
+        command: &str,
+        cwd: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(), McpError> {
+        // Check if already allowed
+        if let Some(grant) = self.command_grant(command).await {
+            self.log_auto_approval(serde_json::json!({ "command": command, "cwd": cwd }), grant)
+                .await;
+            return Ok(());
+        }
+
+        let normalized = normalize_command(command);
+
+        let rule = command_rule(&normalized);
+        let message = format!(
+            "Permission required to run command:\n{}\n\nin {}\n\nAllow execution?",
+            command, cwd
+        );
+        let always_label = match rule.strip_suffix(PREFIX_WILDCARD) {
+            Some(prefix) => format!("Always (allow commands starting with `{}`)", prefix),
+            None => "Always (remember this exact command)".to_string(),
+        };
+
+        match self
+            .request_permission(message, &always_label, peer)
+            .await?
+        {
+            PermissionChoice::Always => {
+                self.remember_command_rule(rule).await;
+                Ok(())
+            }
+            PermissionChoice::Session => {
+                self.remember_session_command_rule(rule).await;
+                Ok(())
+            }
+            PermissionChoice::Once => Ok(()),
+            PermissionChoice::Denied => Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                format!("Command denied by user: {}", command),
+                None,
+            )),
+            PermissionChoice::Unsupported => {
+                if self.policy_allows_command(command, cwd).await {
+                    return Ok(());
+                }
+                let message = if self.has_policy() {
+                    format!(
+                        "Command denied by permission policy: {} in {}",
+                        command, cwd
+                    )
+                } else {
+                    format!(
+                        "Command denied: {} is not an allowed command. Client does not support elicitation for permission request.",
+                        command
+                    )
+                };
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    message,
+                    None,
+                ))
+            }
+        }
+    }
+
+    #[tool(
+        description = "Run a shell command in a working directory and return its exit status, stdout and stderr. The user must approve the command",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
+    )]
+    async fn run_command(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<RunCommandParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_read_only("execute")?;
+
+        let command = params.command.trim();
+        if command.is_empty() {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                "Command must not be empty".to_string(),
+                None,
+            ));
+        }
+
+        // Check permission with elicitation
+        self.check_path_permission_with_elicitation(&params.cwd, "execute", &peer)
+            .await?;
+        let cwd = Self::normalize_path(&params.cwd);
+        if !std::path::Path::new(&cwd).is_dir() {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("Working directory does not exist: {}", cwd),
+                None,
+            ));
+        }
+        self.check_command_permission(command, &cwd, &peer).await?;
+
+        let timeout = params
+            .timeout_secs
+            .unwrap_or(DEFAULT_TIMEOUT)
+            .clamp(1, MAX_TIMEOUT);
+
+        let output = run_process(
+            shell_command(command),
+            "command",
+            None,
+            Duration::from_secs(timeout),
+        )
+        .await?;
+
+        let (exit_code, timed_out) = match output.outcome {
+            Outcome::Exited(status) => (status.code(), false),
+            Outcome::TimedOut => (None, true),
+        };
+        let ProcessOutput {
+            stdout,
+            stdout_total,
+            stderr,
+            stderr_total,
+            ..
+        } = output;
+
+        let result = serde_json::json!({
+            "exitCode": exit_code,
+            "timedOut": timed_out,
+            "stdout": stdout,
+            "stderr": stderr,
+            "stdoutTruncated": stdout_total > OUTPUT_LIMIT,
+            "stderrTruncated": stderr_total > OUTPUT_LIMIT,
+        });
+        let content = vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )];
+
+        if timed_out {
+            Ok(CallToolResult::error(content))
+        } else {
+            Ok(CallToolResult::success(content))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_rule() {
+        assert_eq!(command_rule("cargo test --release"), "cargo test *");
+        assert_eq!(command_rule("git status"), "git status *");
+        assert_eq!(command_rule("git --no-pager log"), "git --no-pager log");
+        assert_eq!(command_rule("ls -la"), "ls -la");
+        assert_eq!(command_rule("rm -rf build"), "rm -rf build");
+        assert_eq!(command_rule("python script.py"), "python script.py");
+        assert_eq!(command_rule("make && rm -rf out"), "make && rm -rf out");
+    }
+
+    #[test]
+    fn test_matches_rule() {
+        assert!(matches_rule("cargo test", "cargo test *"));
+        assert!(matches_rule("cargo test --release", "cargo test *"));
+        assert!(!matches_rule("cargo testing", "cargo test *"));
+        assert!(!matches_rule("cargo test; rm -rf /", "cargo test *"));
+        assert!(matches_rule("rm -rf build", "rm -rf build"));
+        assert!(!matches_rule("rm -rf build /", "rm -rf build"));
+        assert!(!matches_rule("rm -rf src", "rm -rf build"));
+        assert!(matches_rule("make && rm -rf out", "make && rm -rf out"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_process() {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg("cat; echo err >&2; exit 3");
+        let output = run_process(
+            cmd,
+            "sh",
+            Some("hello".to_string()),
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(output.outcome, Outcome::Exited(status) if status.code() == Some(3)));
+        assert_eq!(output.stdout, "hello");
+        assert_eq!(output.stderr, "err\n");
+
+        let mut cmd = tokio::process::Command::new("sleep");
+        cmd.arg("10");
+        let output = run_process(cmd, "sleep", None, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(matches!(output.outcome, Outcome::TimedOut));
+    }
+}
@@ -0,0 +1,608 @@
+use crate::service::sqlite::{
+    SqliteQueryOutput, collect_rows, markdown_table, set_timeout, sql_error,
+};
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, limits::Limit};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Largest CSV, TSV or NDJSON file loaded, in bytes
+const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+/// Largest JSON document loaded, in bytes, it is parsed whole taking several
+/// times its size in memory
+const MAX_JSON_SIZE: u64 = 32 * 1024 * 1024;
+/// Maximum number of columns of the loaded table
+const MAX_COLUMNS: usize = 1000;
+/// Name of the table holding the file
+const TABLE_NAME: &str = "data";
+
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum DataFormat {
+    Csv,
+    Tsv,
+    Json,
+    #[serde(alias = "jsonl")]
+    Ndjson,
+}
+
+impl DataFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(DataFormat::Csv),
+            "tsv" | "tab" => Some(DataFormat::Tsv),
+            "json" => Some(DataFormat::Json),
+            "ndjson" | "jsonl" => Some(DataFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct QueryDataFileParams {
+    /// The path to the CSV, TSV, JSON or NDJSON file
+    path: String,
+    /// SQLite SELECT statement over the table `data` holding the file (default: SELECT * FROM data). Column names with spaces need double quotes
+    sql: Option<String>,
+    /// File format: csv, tsv, json or ndjson (defaults to the file extension)
+    format: Option<DataFormat>,
+    /// Field delimiter of CSV files, a single character (default ',' for csv, tab for tsv)
+    delimiter: Option<String>,
+    /// Whether the first CSV row holds the column names (default true)
+    has_header: Option<bool>,
+    /// Values bound to the `?` placeholders of the statement
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+    /// Maximum number of rows to return (default 100, max 1000)
+    limit: Option<usize>,
+    /// Timeout of the query in seconds (default 10, max 120)
+    timeout: Option<u64>,
+}
+
+/// Structured result of `query_data_file`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct DataQueryOutput {
+    /// Columns of the `data` table with their inferred types
+    schema: Vec<DataColumn>,
+    /// Number of records loaded from the file
+    total_rows: usize,
+    #[serde(flatten)]
+    result: SqliteQueryOutput,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct DataColumn {
+    /// Column name
+    name: String,
+    /// Inferred type: integer, real, boolean or text
+    #[serde(rename = "type")]
+    column_type: ColumnType,
+    /// Number of empty values
+    nulls: usize,
+}
+
+#[derive(Serialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Text,
+}
+
+impl ColumnType {
+    /// Type of one value, strings read from CSV are parsed
+    fn of(value: &Value, parse_strings: bool) -> Option<Self> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(ColumnType::Boolean),
+            Value::Number(n) if n.is_i64() => Some(ColumnType::Integer),
+            Value::Number(_) => Some(ColumnType::Real),
+            Value::String(text) if parse_strings => Some(Self::of_text(text)),
+            _ => Some(ColumnType::Text),
+        }
+    }
+
+    fn of_text(text: &str) -> Self {
+        let digits = text.trim_start_matches(['-', '+']);
+        // Leading zeros mark identifiers like zip codes, not numbers
+        if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+            ColumnType::Text
+        } else if text.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if text.parse::<f64>().is_ok_and(f64::is_finite) {
+            ColumnType::Real
+        } else if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+            ColumnType::Boolean
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    /// Type holding values of both types
+    fn merge(current: Option<Self>, next: Self) -> Self {
+        match current {
+            None => next,
+            Some(current) if current == next => current,
+            Some(ColumnType::Integer) if next == ColumnType::Real => ColumnType::Real,
+            Some(ColumnType::Real) if next == ColumnType::Integer => ColumnType::Real,
+            Some(_) => ColumnType::Text,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Real => "real",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Text => "text",
+        }
+    }
+
+    fn sql_type(&self) -> &'static str {
+        match self {
+            ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// Convert a value to the SQLite value stored in a column of this type
+    fn convert(&self, value: &Value) -> SqlValue {
+        match (self, value) {
+            (_, Value::Null) => SqlValue::Null,
+            (ColumnType::Integer, Value::Number(n)) => {
+                n.as_i64().map_or(SqlValue::Null, SqlValue::Integer)
+            }
+            (ColumnType::Integer, Value::String(s)) => {
+                s.parse().map_or(SqlValue::Null, SqlValue::Integer)
+            }
+            (ColumnType::Real, Value::Number(n)) => {
+                n.as_f64().map_or(SqlValue::Null, SqlValue::Real)
+            }
+            (ColumnType::Real, Value::String(s)) => {
+                s.parse().map_or(SqlValue::Null, SqlValue::Real)
+            }
+            (ColumnType::Boolean, Value::Bool(b)) => SqlValue::Integer(*b as i64),
+            (ColumnType::Boolean, Value::String(s)) => {
+                SqlValue::Integer(s.eq_ignore_ascii_case("true") as i64)
+            }
+            (_, Value::String(s)) => SqlValue::Text(s.clone()),
+            (_, other) => SqlValue::Text(other.to_string()),
+        }
+    }
+}
+
+/// Records read from a file, every row has one value per column
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    /// Strings come from CSV and may hold numbers
+    parse_strings: bool,
+}
+
+/// Make column names unique and non-empty, SQLite compares them ignoring case
+fn column_names(names: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(names.len());
+    for (index, name) in names.into_iter().enumerate() {
+        let base = match name.trim() {
+            "" => format!("column_{}", index + 1),
+            name => name.to_string(),
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while unique.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        unique.push(name);
+    }
+    unique
+}
+
+fn read_csv(text: &[u8], delimiter: u8, has_header: bool) -> Result<Table, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.strip_prefix(b"\xef\xbb\xbf").unwrap_or(text));
+    let mut records = reader.byte_records();
+    let field = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+
+    let mut names = Vec::new();
+    if has_header && let Some(header) = records.next() {
+        let header = header.map_err(|e| format!("Invalid CSV header: {}", e))?;
+        names = header.iter().map(field).collect();
+    }
+
+    let mut rows = Vec::new();
+    for record in records {
+        let record = record.map_err(|e| format!("Invalid CSV: {}", e))?;
+        let row: Vec<Value> = record
+            .iter()
+            .map(|bytes| match bytes {
+                b"" => Value::Null,
+                bytes => Value::String(field(bytes)),
+            })
+            .collect();
+        while names.len() < row.len() {
+            names.push(String::new());
+        }
+        rows.push(row);
+    }
+    for row in rows.iter_mut() {
+        row.resize(names.len(), Value::Null);
+    }
+
+    Ok(Table {
+        columns: column_names(names),
+        rows,
+        parse_strings: true,
+    })
+}
+
+/// Build a table from JSON records, objects give one column per key
+///
+/// Keys are renamed like CSV headers, so keys differing only in case or
+/// empty keys still give valid columns.
+fn json_table(records: Vec<Value>) -> Table {
+    let mut names: Vec<String> = Vec::new();
+    for record in &records {
+        match record {
+            Value::Object(map) => {
+                for key in map.keys() {
+                    if !names.contains(key) {
+                        names.push(key.clone());
+                    }
+                }
+            }
+            _ if !names.iter().any(|name| name == "value") => names.push("value".to_string()),
+            _ => {}
+        }
+    }
+
+    let rows = records
+        .into_iter()
+        .map(|record| match record {
+            Value::Object(mut map) => names
+                .iter()
+                .map(|name| map.remove(name).unwrap_or(Value::Null))
+                .collect(),
+            other => names
+                .iter()
+                .map(|name| match name.as_str() {
+                    "value" => other.clone(),
+                    _ => Value::Null,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Table {
+        columns: column_names(names),
+        rows,
+        parse_strings: false,
+    }
+}
+
+fn read_json(text: &[u8]) -> Result<Table, String> {
+    let value: Value = serde_json::from_slice(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let records = match value {
+        Value::Array(records) => records,
+        // A wrapper object like {"items": [...]} holds the records
+        Value::Object(map) if map.len() == 1 && map.values().all(Value::is_array) => {
+            match map.into_iter().next() {
+                Some((_, Value::Array(records))) => records,
+                _ => Vec::new(),
+            }
+        }
+        other => vec![other],
+    };
+    Ok(json_table(records))
+}
+
+/// Read JSON records, one per line, without holding the whole file
+fn read_ndjson(reader: impl BufRead) -> Result<Table, String> {
+    let mut records = Vec::new();
+    for (index, line) in reader.split(b'\n').enumerate() {
+        let line = line.map_err(|e| format!("Failed to read line {}: {}", index + 1, e))?;
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid JSON on line {}: {}", index + 1, e))?;
+        records.push(record);
+    }
+    Ok(json_table(records))
+}
+
+/// Infer the type of every column
+fn infer_schema(table: &Table) -> Vec<DataColumn> {
+    table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let mut column_type = None;
+            let mut nulls = 0;
+            for row in &table.rows {
+                match ColumnType::of(&row[index], table.parse_strings) {
+                    Some(value_type) => {
+                        column_type = Some(ColumnType::merge(column_type, value_type))
+                    }
+                    None => nulls += 1,
+                }
+            }
+            DataColumn {
+                name: name.clone(),
+                column_type: column_type.unwrap_or(ColumnType::Text),
+                nulls,
+            }
+        })
+        .collect()
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Load the table into an in-memory database, read-only once filled
+fn load_database(table: &Table, schema: &[DataColumn]) -> Result<Connection, McpError> {
+    let mut conn = Connection::open_in_memory().map_err(sql_error)?;
+    // Statements must not reach other database files
+    conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)
+        .map_err(sql_error)?;
+
+    let columns = schema
+        .iter()
+        .map(|column| {
+            format!(
+                "{} {}",
+                quote_identifier(&column.name),
+                column.column_type.sql_type()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(&format!("CREATE TABLE {} ({})", TABLE_NAME, columns), [])
+        .map_err(sql_error)?;
+
+    let tx = conn.transaction().map_err(sql_error)?;
+    {
+        let placeholders = vec!["?"; schema.len()].join(", ");
+        let mut insert = tx
+            .prepare(&format!(
+                "INSERT INTO {} VALUES ({})",
+                TABLE_NAME, placeholders
+            ))
+            .map_err(sql_error)?;
+        for row in &table.rows {
+            let values = schema
+                .iter()
+                .zip(row)
+                .map(|(column, value)| column.column_type.convert(value));
+            insert
+                .execute(rusqlite::params_from_iter(values))
+                .map_err(sql_error)?;
+        }
+    }
+    tx.commit().map_err(sql_error)?;
+
+    conn.pragma_update(None, "query_only", true)
+        .map_err(sql_error)?;
+    Ok(conn)
+}
+
+fn run_data_query(params: QueryDataFileParams) -> Result<DataQueryOutput, McpError> {
+    let invalid =
+        |message: String| McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None);
+
+    let path = Path::new(&params.path);
+    let format = params
+        .format
+        .or_else(|| DataFormat::from_path(path))
+        .ok_or_else(|| invalid("Cannot tell the file format, give format".to_string()))?;
+    let size = std::fs::metadata(path)
+        .map_err(|e| invalid(format!("Failed to read {}: {}", params.path, e)))?
+        .len();
+    if format == DataFormat::Json && size > MAX_JSON_SIZE {
+        return Err(invalid(format!(
+            "{} is larger than {} bytes, the limit for JSON documents. NDJSON files up to {} bytes are supported",
+            params.path, MAX_JSON_SIZE, MAX_FILE_SIZE
+        )));
+    }
+    if size > MAX_FILE_SIZE {
+        return Err(invalid(format!(
+            "{} is larger than {} bytes",
+            params.path, MAX_FILE_SIZE
+        )));
+    }
+    let read_error = |e: std::io::Error| invalid(format!("Failed to read {}: {}", params.path, e));
+
+    let table = match format {
+        DataFormat::Csv | DataFormat::Tsv => {
+            let delimiter = match params.delimiter.as_deref() {
+                Some(delimiter) if delimiter.len() == 1 => delimiter.as_bytes()[0],
+                Some("\\t") => b'\t',
+                Some(delimiter) => {
+                    return Err(invalid(format!(
+                        "Delimiter must be a single character, got {:?}",
+                        delimiter
+                    )));
+                }
+                None if format == DataFormat::Tsv => b'\t',
+                None => b',',
+            };
+            let text = std::fs::read(path).map_err(read_error)?;
+            read_csv(&text, delimiter, params.has_header.unwrap_or(true))
+        }
+        DataFormat::Json => read_json(&std::fs::read(path).map_err(read_error)?),
+        DataFormat::Ndjson => read_ndjson(BufReader::new(File::open(path).map_err(read_error)?)),
+    }
+    .map_err(invalid)?;
+    if table.columns.is_empty() {
+        return Err(invalid(format!("{} holds no columns", params.path)));
+    }
+    if table.columns.len() > MAX_COLUMNS {
+        return Err(invalid(format!(
+            "{} has {} columns, at most {} are supported",
+            params.path,
+            table.columns.len(),
+            MAX_COLUMNS
+        )));
+    }
+
+    let schema = infer_schema(&table);
+    let conn = load_database(&table, &schema)?;
+    set_timeout(&conn, params.timeout);
+
+    let sql = params
+        .sql
+        .unwrap_or_else(|| format!("SELECT * FROM {}", TABLE_NAME));
+    let mut stmt = conn.prepare(&sql).map_err(sql_error)?;
+    if !stmt.readonly() {
+        return Err(invalid("Only read-only statements are allowed".to_string()));
+    }
+    let result = collect_rows(&mut stmt, &params.params, params.limit)?;
+
+    Ok(DataQueryOutput {
+        schema,
+        total_rows: table.rows.len(),
+        result,
+    })
+}
+
+#[tool_router(router = tool_router_data, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Query a CSV, TSV, JSON or NDJSON file with SQL. The file is loaded into a SQLite table named `data` with inferred column types (integer, real, boolean, text), then a read-only statement runs on it, e.g. SELECT city, COUNT(*), AVG(price) FROM data WHERE price > 10 GROUP BY city. Returns the column types and up to `limit` rows as structured content and as a Markdown table",
+        output_schema = cached_schema_for_type::<DataQueryOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn query_data_file(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<QueryDataFileParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let output = run_blocking(move || run_data_query(params)).await?;
+        let columns = output
+            .schema
+            .iter()
+            .map(|column| format!("{} {}", column.name, column.column_type.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let text = format!(
+            "Table {} ({} rows): {}\n\n{}",
+            TABLE_NAME,
+            output.total_rows,
+            columns,
+            markdown_table(&output.result)
+        );
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(text)];
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_types() {
+        let csv =
+            b"\xef\xbb\xbfcity,zip,price,,City\nParis,07500,12.5,true,x\nLyon,69001,8,false,\n";
+        let table = read_csv(csv, b',', true).unwrap();
+        assert_eq!(
+            table.columns,
+            ["city", "zip", "price", "column_4", "City_2"]
+        );
+        let types: Vec<ColumnType> = infer_schema(&table).iter().map(|c| c.column_type).collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Text,
+                ColumnType::Text,
+                ColumnType::Real,
+                ColumnType::Boolean,
+                ColumnType::Text
+            ]
+        );
+
+        let table =
+            read_ndjson(&b"{\"a\": 1, \"b\": [1]}\n\n{\"a\": 2.5, \"c\": null}\n"[..]).unwrap();
+        assert_eq!(table.columns, ["a", "b", "c"]);
+        let schema = infer_schema(&table);
+        assert_eq!(schema[0].column_type, ColumnType::Real);
+        assert_eq!(schema[2].nulls, 2);
+        assert_eq!(
+            ColumnType::Text.convert(&serde_json::json!([1])),
+            SqlValue::Text("[1]".to_string())
+        );
+
+        let conn = load_database(&table, &schema).unwrap();
+        let sum: f64 = conn
+            .query_row("SELECT SUM(a) FROM data", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sum, 3.5);
+        assert!(conn.execute("DELETE FROM data", []).is_err());
+
+        // JSON keys are renamed like CSV headers
+        let table = read_ndjson(&b"{\"id\": 1}\n{\"ID\": 2}\n{\"\": 3}\n"[..]).unwrap();
+        assert_eq!(table.columns, ["id", "ID_2", "column_3"]);
+        assert_eq!(
+            table.rows[1],
+            [Value::Null, serde_json::json!(2), Value::Null]
+        );
+        let conn = load_database(&table, &infer_schema(&table)).unwrap();
+        let sum: i64 = conn
+            .query_row("SELECT SUM(ID_2) + SUM(column_3) FROM data", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(sum, 5);
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let dir = std::env::temp_dir().join(format!("dive-data-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let query = |name: &str, size: u64| {
+            let path = dir.join(name);
+            File::create(&path).unwrap().set_len(size).unwrap();
+            let params = serde_json::json!({ "path": path.to_string_lossy() });
+            run_data_query(serde_json::from_value(params).unwrap())
+                .err()
+                .map(|e| e.message.to_string())
+                .unwrap_or_default()
+        };
+
+        assert!(query("big.json", MAX_JSON_SIZE + 1).contains("limit for JSON documents"));
+        assert!(query("big.ndjson", MAX_FILE_SIZE + 1).contains("is larger than"));
+        // A sparse NDJSON file over the JSON limit is read, and fails on its content
+        assert!(query("zeros.ndjson", MAX_JSON_SIZE + 1).contains("Invalid JSON on line 1"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,348 @@
+use crate::service::DiveDefaultService;
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content, Meta, ProgressNotificationParam, ProgressToken},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Minimum interval between two progress notifications
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct DownloadUrlParams {
+    /// The URL to download
+    url: String,
+    /// The file path to save the download to
+    path: String,
+    /// Resume a previously interrupted download of the same file
+    #[serde(default = "default_resume")]
+    resume: bool,
+    /// Expected SHA-256 checksum (hex) of the downloaded file
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+fn default_resume() -> bool {
+    true
+}
+
+/// Path of the partially downloaded file
+fn partial_path(path: &str) -> String {
+    format!("{}.part", path)
+}
+
+/// Path of the file recording what the partial file was downloaded from
+fn partial_meta_path(path: &str) -> String {
+    format!("{}.part.json", path)
+}
+
+/// Origin of a partial download, checked with `If-Range` before resuming
+#[derive(Serialize, Deserialize)]
+struct PartialMeta {
+    url: String,
+    /// Strong ETag or Last-Modified of the response the partial file holds
+    validator: String,
+}
+
+/// Validator for `If-Range`, weak ETags cannot be used there
+fn range_validator(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    header("etag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header("last-modified"))
+        .map(String::from)
+}
+
+/// Parse `Content-Range: bytes <start>-<end>/<total>` or `bytes */<total>`
+/// into the first byte and the complete length, when known
+fn content_range(headers: &reqwest::header::HeaderMap) -> Option<(Option<u64>, Option<u64>)> {
+    let value = headers.get("content-range")?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = match range.trim() {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.trim().parse().ok()?),
+    };
+    Some((start, total.trim().parse().ok()))
+}
+
+/// Feed an existing partial file into the hasher, returning its size
+async fn hash_existing(path: &str, hasher: &mut Sha256) -> Result<u64, std::io::Error> {
+    let mut file = fs::File::open(path).await?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let bytes_read = file.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+        size += bytes_read as u64;
+    }
+    Ok(size)
+}
+
+async fn notify_progress(
+    peer: &Peer<RoleServer>,
+    token: Option<&ProgressToken>,
+    downloaded: u64,
+    total: Option<u64>,
+) {
+    if let Some(token) = token {
+        let message = match total {
+            Some(total) => format!("Downloaded {} of {} bytes", downloaded, total),
+            None => format!("Downloaded {} bytes", downloaded),
+        };
+        let _ = peer
+            .notify_progress(ProgressNotificationParam {
+                progress_token: token.clone(),
+                progress: downloaded as f64,
+                total: total.map(|t| t as f64),
+                message: Some(message),
+            })
+            .await;
+    }
+}
+
+fn internal_error(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, None)
+}
+
+#[tool_router(router = tool_router_download, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Download a URL directly to a file, reporting progress. Interrupted downloads are resumed and an optional SHA-256 checksum is verified",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = true
+        )
+    )]
+    async fn download_url(
+        &self,
+        peer: Peer<RoleServer>,
+        meta: Meta,
+        Parameters(params): Parameters<DownloadUrlParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Check permission with elicitation
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        let progress_token = meta.get_progress_token();
+        let part_path = partial_path(&params.path);
+        let meta_path = partial_meta_path(&params.path);
+
+        // Pick up where a previous download of the same URL stopped, only when
+        // the server can tell whether the file changed since
+        let mut hasher = Sha256::new();
+        let mut offset = 0u64;
+        let mut validator = None;
+        if params.resume && fs::try_exists(&part_path).await.unwrap_or(false) {
+            validator = fs::read(&meta_path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice::<PartialMeta>(&content).ok())
+                .filter(|meta| meta.url == params.url)
+                .map(|meta| meta.validator);
+            if validator.is_some() {
+                offset = hash_existing(&part_path, &mut hasher)
+                    .await
+                    .map_err(|e| internal_error(format!("Failed to read partial file: {}", e)))?;
+            }
+        }
+
+        let client = self.http_client.for_url(&params.url);
+        let send = |offset: u64, validator: Option<&str>| {
+            let mut request_builder = client.get(&params.url);
+            if let Some(validator) = validator.filter(|_| offset > 0) {
+                request_builder = request_builder
+                    .header("range", format!("bytes={}-", offset))
+                    .header("if-range", validator);
+            }
+            async move {
+                request_builder
+                    .send()
+                    .await
+                    .map_err(|e| internal_error(format!("Failed to send request: {}", e)))
+            }
+        };
+
+        let mut response = send(offset, validator.as_deref()).await?;
+        let mut status = response.status();
+        let mut total = None;
+        let mut already_complete = false;
+        let mut resumed = false;
+        let mut restart = false;
+        if offset > 0 {
+            match (status, content_range(response.headers())) {
+                (reqwest::StatusCode::PARTIAL_CONTENT, Some((Some(start), length)))
+                    if start == offset =>
+                {
+                    resumed = true;
+                    total = length;
+                }
+                // Range past the end, complete only if the file is as long as the partial one
+                (reqwest::StatusCode::RANGE_NOT_SATISFIABLE, Some((None, Some(length))))
+                    if length == offset =>
+                {
+                    already_complete = true;
+                    total = Some(length);
+                }
+                // The server ignored the range or the file changed, it sends the whole file
+                (status, _) if status == reqwest::StatusCode::OK => {}
+                _ => restart = true,
+            }
+            if !already_complete && !resumed {
+                offset = 0;
+                hasher = Sha256::new();
+            }
+        }
+
+        // A range response that does not continue the partial file
+        if restart {
+            response = send(0, None).await?;
+            status = response.status();
+        }
+        if !status.is_success() && !already_complete {
+            return Err(internal_error(format!(
+                "Download failed with status {}",
+                status
+            )));
+        }
+
+        if offset == 0 {
+            total = response.content_length();
+            // Record the origin so an interrupted download can be resumed safely
+            match range_validator(response.headers()) {
+                Some(validator) => {
+                    let meta = PartialMeta {
+                        url: params.url.clone(),
+                        validator,
+                    };
+                    let _ =
+                        fs::write(&meta_path, serde_json::to_vec(&meta).unwrap_or_default()).await;
+                }
+                None => {
+                    let _ = fs::remove_file(&meta_path).await;
+                }
+            }
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&part_path)
+            .await
+            .map_err(|e| internal_error(format!("Failed to open file: {}", e)))?;
+
+        let mut downloaded = offset;
+        let mut last_progress = Instant::now();
+        notify_progress(&peer, progress_token.as_ref(), downloaded, total).await;
+
+        if !already_complete {
+            loop {
+                let chunk = match response.chunk().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = file.flush().await;
+                        return Err(internal_error(format!(
+                            "Download interrupted after {} bytes, call again to resume: {}",
+                            downloaded, e
+                        )));
+                    }
+                };
+
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|e| internal_error(format!("Failed to write file: {}", e)))?;
+                hasher.update(&chunk);
+                downloaded += chunk.len() as u64;
+
+                if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                    notify_progress(&peer, progress_token.as_ref(), downloaded, total).await;
+                    last_progress = Instant::now();
+                }
+            }
+        }
+
+        file.flush()
+            .await
+            .map_err(|e| internal_error(format!("Failed to write file: {}", e)))?;
+        drop(file);
+        notify_progress(&peer, progress_token.as_ref(), downloaded, total).await;
+
+        let checksum = format!("{:x}", hasher.finalize());
+        if let Some(expected) = params.sha256.as_deref()
+            && !expected.trim().eq_ignore_ascii_case(&checksum)
+        {
+            let _ = fs::remove_file(&part_path).await;
+            let _ = fs::remove_file(&meta_path).await;
+            return Err(internal_error(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected.trim(),
+                checksum
+            )));
+        }
+
+        fs::rename(&part_path, &params.path)
+            .await
+            .map_err(|e| internal_error(format!("Failed to move downloaded file: {}", e)))?;
+        let _ = fs::remove_file(&meta_path).await;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Successfully downloaded {} bytes to {}\nsha256: {}",
+            downloaded, params.path, checksum
+        ))]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    reqwest::header::HeaderName::from_static(name),
+                    value.parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resume_headers() {
+        let h = headers(&[("content-range", "bytes 100-199/1000")]);
+        assert_eq!(content_range(&h), Some((Some(100), Some(1000))));
+        let h = headers(&[("content-range", "bytes */1000")]);
+        assert_eq!(content_range(&h), Some((None, Some(1000))));
+        let h = headers(&[("content-range", "bytes 0-9/*")]);
+        assert_eq!(content_range(&h), Some((Some(0), None)));
+        assert_eq!(content_range(&headers(&[])), None);
+
+        let h = headers(&[
+            ("etag", "W/\"1\""),
+            ("last-modified", "Thu, 01 Jan 1970 00:00:00 GMT"),
+        ]);
+        assert_eq!(
+            range_validator(&h).as_deref(),
+            Some("Thu, 01 Jan 1970 00:00:00 GMT")
+        );
+        let h = headers(&[("etag", "\"abc\"")]);
+        assert_eq!(range_validator(&h).as_deref(), Some("\"abc\""));
+        assert_eq!(range_validator(&headers(&[])), None);
+    }
+}
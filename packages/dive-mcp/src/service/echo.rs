@@ -16,7 +16,15 @@ struct EchoParam {
 
 #[tool_router(router = tool_router_echo, vis = "pub")]
 impl DiveDefaultService {
-    #[tool(description = "Repeat what you say")]
+    #[tool(
+        description = "Repeat what you say",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn echo(
         &self,
         Parameters(object): Parameters<EchoParam>,
@@ -1,11 +1,13 @@
-use crate::service::DiveDefaultService;
+use crate::service::cache::{self, CachedResponse};
+use crate::service::{DiveDefaultService, structured_result};
 use rmcp::{
     ErrorData as McpError,
-    handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content},
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::CallToolResult,
     tool, tool_router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
 use std::collections::HashMap;
 
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -41,28 +43,172 @@ pub struct FetchParams {
     /// Body data for POST/PUT requests (can be JSON object or form data)
     #[serde(default)]
     body: Option<serde_json::Value>,
+    /// Cache mode for GET requests (default, no-store or force-cache)
+    #[serde(default)]
+    cache: CacheMode,
+    /// JSONPath expression (e.g. `$.items[*].name`) applied to a JSON response body,
+    /// only the selected nodes are returned
+    #[serde(default)]
+    json_path: Option<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheMode {
+    /// Serve fresh cached responses and revalidate stale ones
+    #[default]
+    Default,
+    /// Bypass the cache completely
+    NoStore,
+    /// Serve any cached response regardless of freshness
+    ForceCache,
 }
 
 fn default_method() -> HttpMethod {
     HttpMethod::Get
 }
 
+/// Label of the cache outcome reported in the response envelope
+const CACHE_HIT: &str = "hit";
+const CACHE_REVALIDATED: &str = "revalidated";
+const CACHE_MISS: &str = "miss";
+
+/// Characters of a non-JSON body quoted when `json_path` cannot be applied
+const BODY_SNIPPET_LIMIT: usize = 500;
+
+/// Structured result of `fetch`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchOutput {
+    /// HTTP status code
+    status: u16,
+    /// Reason phrase of the status code
+    status_text: String,
+    /// Response headers, names in lowercase
+    headers: HashMap<String, String>,
+    /// Response body as text, or the array of nodes selected by `json_path`
+    body: serde_json::Value,
+    /// Number of nodes selected by `json_path`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<usize>,
+    /// Cache outcome of a GET request (hit, revalidated or miss)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<String>,
+    /// Age in seconds of a response served from the cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    age: Option<u64>,
+}
+
+/// Build the JSON envelope returned to the model
+fn envelope(
+    response: &CachedResponse,
+    json_path: Option<&JsonPath>,
+    cache_label: Option<&str>,
+    age: Option<u64>,
+) -> Result<CallToolResult, McpError> {
+    let mut output = FetchOutput {
+        status: response.status,
+        status_text: response.status_text.clone(),
+        headers: response.headers.clone(),
+        body: serde_json::json!(response.body),
+        matches: None,
+        cache: cache_label.map(String::from),
+        age,
+    };
+    if let Some(json_path) = json_path {
+        let body: serde_json::Value = serde_json::from_str(&response.body).map_err(|e| {
+            let mut snippet: String = response.body.chars().take(BODY_SNIPPET_LIMIT).collect();
+            if response.body.chars().count() > BODY_SNIPPET_LIMIT {
+                snippet.push('…');
+            }
+            McpError::new(
+                rmcp::model::ErrorCode::INTERNAL_ERROR,
+                format!(
+                    "Cannot apply json_path: response body is not valid JSON ({}), status: {} {}, content-type: {}\n\n{}",
+                    e,
+                    response.status,
+                    response.status_text,
+                    response
+                        .headers
+                        .get("content-type")
+                        .map(String::as_str)
+                        .unwrap_or("unknown"),
+                    snippet
+                ),
+                Some(serde_json::json!({
+                    "status": response.status,
+                    "statusText": response.status_text,
+                    "body": snippet,
+                })),
+            )
+        })?;
+        let nodes = json_path.query(&body).all();
+        output.matches = Some(nodes.len());
+        output.body = serde_json::json!(nodes);
+    }
+
+    structured_result(&output)
+}
+
 #[tool_router(router = tool_router_fetch, vis = "pub")]
 impl DiveDefaultService {
-    #[tool(description = "Make HTTP requests with support for different methods and content types")]
+    #[tool(
+        description = "Make HTTP requests with support for different methods and content types. GET responses are cached on disk following HTTP caching rules. Use json_path to return only selected parts of a JSON response",
+        output_schema = cached_schema_for_type::<FetchOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
+    )]
     pub async fn fetch(
         &self,
         Parameters(params): Parameters<FetchParams>,
     ) -> Result<CallToolResult, McpError> {
+        let json_path = params
+            .json_path
+            .as_deref()
+            .map(JsonPath::parse)
+            .transpose()
+            .map_err(|e| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INVALID_PARAMS,
+                    format!("Invalid json_path: {}", e),
+                    None,
+                )
+            })?;
+        let request_headers = params.headers.clone().unwrap_or_default();
+        let http_cache = match params.method {
+            HttpMethod::Get if params.cache != CacheMode::NoStore => self.http_cache.as_ref(),
+            _ => None,
+        };
+
+        // Look up the disk cache
+        let cached = match http_cache {
+            Some(http_cache) => http_cache.get(&params.url, &request_headers).await,
+            None => None,
+        };
+        if let Some(entry) = cached.as_ref() {
+            let now = cache::now_secs();
+            if params.cache == CacheMode::ForceCache || entry.is_fresh(now) {
+                return envelope(
+                    entry,
+                    json_path.as_ref(),
+                    Some(CACHE_HIT),
+                    Some(entry.current_age(now)),
+                );
+            }
+        }
+
         // Build the request based on method
+        let client = self.http_client.for_url(&params.url);
         let mut request_builder = match params.method {
-            HttpMethod::Get => self.http_client.get(&params.url),
-            HttpMethod::Post => self.http_client.post(&params.url),
-            HttpMethod::Put => self.http_client.put(&params.url),
-            HttpMethod::Delete => self.http_client.delete(&params.url),
-            HttpMethod::Options => self
-                .http_client
-                .request(reqwest::Method::OPTIONS, &params.url),
+            HttpMethod::Get => client.get(&params.url),
+            HttpMethod::Post => client.post(&params.url),
+            HttpMethod::Put => client.put(&params.url),
+            HttpMethod::Delete => client.delete(&params.url),
+            HttpMethod::Options => client.request(reqwest::Method::OPTIONS, &params.url),
         };
 
         // Add headers if provided
@@ -72,6 +218,13 @@ impl DiveDefaultService {
             }
         }
 
+        // Revalidate a stale cached response
+        if let Some(entry) = cached.as_ref() {
+            for (key, value) in entry.validators() {
+                request_builder = request_builder.header(key, value);
+            }
+        }
+
         // Add body if provided
         if let Some(body) = params.body {
             match params.content_type {
@@ -103,42 +256,103 @@ impl DiveDefaultService {
         }
 
         // Send the request
-        match request_builder.send().await {
-            Ok(response) => {
-                let status = response.status();
-                let headers: HashMap<String, String> = response
-                    .headers()
-                    .iter()
-                    .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-                    .collect();
-
-                match response.text().await {
-                    Ok(body) => {
-                        let result = serde_json::json!({
-                            "status": status.as_u16(),
-                            "statusText": status.canonical_reason().unwrap_or(""),
-                            "headers": headers,
-                            "body": body,
-                        });
-
-                        Ok(CallToolResult::success(vec![Content::text(
-                            serde_json::to_string_pretty(&result)
-                                .unwrap_or_else(|_| result.to_string()),
-                        )]))
-                    }
-                    Err(e) => Err(McpError::new(
-                        rmcp::model::ErrorCode::INTERNAL_ERROR,
-                        format!("Failed to read response body: {}", e),
-                        None,
-                    )),
-                }
-            }
-            Err(e) => Err(McpError::new(
+        let request_time = cache::now_secs();
+        let response = request_builder.send().await.map_err(|e| {
+            McpError::new(
                 rmcp::model::ErrorCode::INTERNAL_ERROR,
                 format!("Failed to send request: {}", e),
                 None,
-            )),
+            )
+        })?;
+
+        let status = response.status();
+        let headers: HashMap<String, String> = response
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+            .collect();
+
+        // The cached response is still valid
+        if let (Some(http_cache), Some(mut entry)) = (http_cache, cached)
+            && status == reqwest::StatusCode::NOT_MODIFIED
+        {
+            entry.refresh(&headers, request_time, cache::now_secs());
+            if entry.is_storable(&request_headers) {
+                let _ = http_cache.put(&entry).await;
+            } else {
+                http_cache.remove(&entry.url).await;
+            }
+            return envelope(&entry, json_path.as_ref(), Some(CACHE_REVALIDATED), None);
         }
+
+        let body = response.text().await.map_err(|e| {
+            McpError::new(
+                rmcp::model::ErrorCode::INTERNAL_ERROR,
+                format!("Failed to read response body: {}", e),
+                None,
+            )
+        })?;
+
+        let mut result = CachedResponse {
+            url: params.url,
+            status: status.as_u16(),
+            status_text: status.canonical_reason().unwrap_or("").to_string(),
+            headers,
+            body,
+            vary: HashMap::new(),
+            request_time,
+            response_time: cache::now_secs(),
+        };
+
+        let Some(http_cache) = http_cache else {
+            return envelope(&result, json_path.as_ref(), None, None);
+        };
+
+        if result.is_storable(&request_headers) {
+            result.capture_vary(&request_headers);
+            let _ = http_cache.put(&result).await;
+        } else {
+            http_cache.remove(&result.url).await;
+        }
+
+        envelope(&result, json_path.as_ref(), Some(CACHE_MISS), None)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, body: &str) -> CachedResponse {
+        CachedResponse {
+            url: "https://example.com/".to_string(),
+            status,
+            status_text: "Not Found".to_string(),
+            headers: HashMap::new(),
+            body: body.to_string(),
+            vary: HashMap::new(),
+            request_time: 0,
+            response_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_json_path_envelope() {
+        let path = JsonPath::parse("$.items[*].name").unwrap();
+        let result = envelope(
+            &response(200, r#"{"items": [{"name": "a"}, {"name": "b"}]}"#),
+            Some(&path),
+            None,
+            None,
+        )
+        .unwrap();
+        let output = result.structured_content.unwrap();
+        assert_eq!(output["body"], serde_json::json!(["a", "b"]));
+        assert_eq!(output["matches"], 2);
+
+        let error =
+            envelope(&response(404, "<html>gone</html>"), Some(&path), None, None).unwrap_err();
+        assert!(error.message.contains("status: 404 Not Found"));
+        assert_eq!(error.data.unwrap()["body"], "<html>gone</html>");
+    }
+}
@@ -1,31 +1,26 @@
-#[cfg(not(feature = "local_ipc"))]
-use std::time::Duration;
-
-use crate::service::DiveDefaultService;
-#[cfg(feature = "local_ipc")]
-use rmcp::model::ElicitationAction;
-#[cfg(not(feature = "local_ipc"))]
-use rmcp::model::{CreateElicitationRequestParam, ElicitationAction};
+use crate::service::permission::{PathScopes, PermissionChoice, is_granted};
+use crate::service::{DiveDefaultService, structured_result};
 use rmcp::{
     ErrorData as McpError, Peer,
-    handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content, ElicitationSchema, EnumSchema, PrimitiveSchema},
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
     service::RoleServer,
     tool, tool_router,
 };
 
 use base64::{Engine as _, engine::general_purpose};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tokio::io::AsyncReadExt;
 
-#[cfg(not(feature = "local_ipc"))]
-const ELICITATION_TIMEOUT: u64 = 600;
-
 #[derive(Deserialize, schemars::JsonSchema)]
 struct ReadFileParams {
     /// The path to the file to read
     path: String,
+    /// First line of a text file to return, 1-based (default 1)
+    start_line: Option<usize>,
+    /// Last line of a text file to return, inclusive (default: end of file)
+    end_line: Option<usize>,
 }
 
 #[derive(Deserialize, schemars::JsonSchema)]
@@ -42,6 +37,24 @@ struct ListDirectoryParams {
     path: String,
 }
 
+/// Structured result of `list_directory`
+#[derive(Serialize, schemars::JsonSchema)]
+struct ListDirectoryOutput {
+    /// The directory that was listed
+    path: String,
+    /// Files and directories in it
+    entries: Vec<DirectoryEntry>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct DirectoryEntry {
+    /// File name
+    name: String,
+    /// Entry type (file or directory)
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
 #[derive(Deserialize, schemars::JsonSchema)]
 struct CreateDirectoryParams {
     /// The path to the directory to create
@@ -68,7 +81,7 @@ struct DenyDirParams {
 }
 
 /// Check if a file is binary by reading the first 8KB and looking for null bytes
-async fn is_binary_file(path: &str) -> Result<bool, std::io::Error> {
+pub(super) async fn is_binary_file(path: &str) -> Result<bool, std::io::Error> {
     let mut file = fs::File::open(path).await?;
     let mut buffer = vec![0u8; 8192];
     let bytes_read = file.read(&mut buffer).await?;
@@ -77,38 +90,34 @@ async fn is_binary_file(path: &str) -> Result<bool, std::io::Error> {
     Ok(buffer[..bytes_read].contains(&0))
 }
 
-/// Permission choice enum values
-const PERMISSION_ALWAYS: &str = "always";
-const PERMISSION_YES: &str = "yes";
-const PERMISSION_NO: &str = "no";
-
-/// Create the permission elicitation schema
-fn create_permission_schema() -> ElicitationSchema {
-    let mut properties = std::collections::BTreeMap::new();
-    properties.insert(
-        "choice".to_string(),
-        PrimitiveSchema::Enum(
-            EnumSchema::new(vec![
-                PERMISSION_ALWAYS.to_string(),
-                PERMISSION_YES.to_string(),
-                PERMISSION_NO.to_string(),
-            ])
-            .enum_names(vec![
-                "Always (remember this choice)".to_string(),
-                "Yes (allow this time)".to_string(),
-                "No (deny access)".to_string(),
-            ])
-            .description("Select your permission choice"),
-        ),
-    );
-
-    ElicitationSchema::new(properties).with_required(vec!["choice".to_string()])
+/// Lines `start..=end` of a text, 1-based, keeping their line endings
+fn line_range(content: &str, start: Option<usize>, end: Option<usize>) -> Result<String, String> {
+    let start = start.unwrap_or(1).max(1);
+    let total = content.lines().count();
+    if start > total.max(1) {
+        return Err(format!(
+            "start_line {} is past the end of the file ({} lines)",
+            start, total
+        ));
+    }
+    let end = end.unwrap_or(total.max(start));
+    if end < start {
+        return Err(format!("end_line {} is before start_line {}", end, start));
+    }
+    Ok(content
+        .split_inclusive('\n')
+        .skip(start - 1)
+        .take(end - start + 1)
+        .collect())
 }
 
+/// Operations still permitted when the server runs with `--read-only`
+const READ_ONLY_OPERATIONS: &[&str] = &["read", "list"];
+
 #[tool_router(router = tool_router_fs, vis = "pub")]
 impl DiveDefaultService {
     /// Normalize path to absolute path
-    fn normalize_path(path: &str) -> String {
+    pub(super) fn normalize_path(path: &str) -> String {
         match std::fs::canonicalize(path) {
             Ok(p) => p.to_string_lossy().to_string(),
             Err(_) => {
@@ -128,33 +137,37 @@ impl DiveDefaultService {
         }
     }
 
-    /// Get the parent directory of a path
-    fn get_parent_dir(path: &str) -> Option<String> {
-        let path_buf = std::path::PathBuf::from(path);
-        path_buf
-            .parent()
-            .map(|p| p.to_string_lossy().to_string())
-            .filter(|s| !s.is_empty())
-    }
-
     /// Check if a path is within allowed directories (without elicitation)
+    ///
+    /// Entries match whole path components, so an allowed file does not
+    /// allow siblings sharing its name as a prefix.
     fn is_path_allowed(&self, abs_path: &str, allowed_dirs: &[String]) -> bool {
-        for allowed_dir in allowed_dirs.iter() {
-            if abs_path.starts_with(allowed_dir) {
-                return true;
-            }
+        is_granted(abs_path, allowed_dirs) || is_granted(abs_path, &self.extra_allowed_dirs)
+    }
+
+    /// Reject modifying operations when running in read-only mode
+    pub(super) fn check_read_only(&self, operation: &str) -> Result<(), McpError> {
+        if self.read_only && !READ_ONLY_OPERATIONS.contains(&operation) {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                format!(
+                    "Access denied: {} operation is not allowed in read-only mode",
+                    operation
+                ),
+                None,
+            ));
         }
-        false
+        Ok(())
     }
 
-    /// Check path permission with elicitation support (using MCP peer elicitation)
-    #[cfg(not(feature = "local_ipc"))]
-    async fn check_path_permission_with_elicitation(
+    /// Check path permission with elicitation support
+    pub(super) async fn check_path_permission_with_elicitation(
         &self,
         path: &str,
         operation: &str,
         peer: &Peer<RoleServer>,
     ) -> Result<(), McpError> {
+        self.check_read_only(operation)?;
         let abs_path = Self::normalize_path(path);
 
         // Check if already allowed
@@ -163,18 +176,10 @@ impl DiveDefaultService {
             if self.is_path_allowed(&abs_path, &allowed_dirs) {
                 return Ok(());
             }
-        }
-
-        // Check if client supports elicitation
-        if !peer.supports_elicitation() {
-            return Err(McpError::new(
-                rmcp::model::ErrorCode::INVALID_REQUEST,
-                format!(
-                    "Access denied: {} is not within allowed directories. Client does not support elicitation for permission request.",
-                    abs_path
-                ),
-                None,
-            ));
+            let session_paths = self.session_grants.paths.read().await;
+            if self.is_path_allowed(&abs_path, &session_paths) {
+                return Ok(());
+            }
         }
 
         // Request permission via elicitation
@@ -183,152 +188,172 @@ impl DiveDefaultService {
             operation, abs_path
         );
 
-        let result = peer
-            .create_elicitation_with_timeout(
-                CreateElicitationRequestParam {
-                    message,
-                    requested_schema: create_permission_schema(),
-                },
-                Some(Duration::from_secs(ELICITATION_TIMEOUT)),
-            )
-            .await
-            .map_err(|e| {
-                McpError::new(
-                    rmcp::model::ErrorCode::INTERNAL_ERROR,
-                    format!("Failed to request permission: {}", e),
-                    None,
-                )
-            })?;
-
-        match result.action {
-            ElicitationAction::Accept => {
-                if let Some(content) = result.content {
-                    let choice = content
-                        .get("choice")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(PERMISSION_NO);
-
-                    match choice {
-                        PERMISSION_ALWAYS => {
-                            // Add parent directory to allowed list
-                            let dir_to_allow =
-                                Self::get_parent_dir(&abs_path).unwrap_or_else(|| abs_path.clone());
-
-                            let mut allowed_dirs = self.allowed_dirs.write().await;
-                            if !allowed_dirs.contains(&dir_to_allow) {
-                                allowed_dirs.push(dir_to_allow);
-                            }
-                            drop(allowed_dirs);
-
-                            // Save to config
-                            let _ = self.save_allowed_dirs().await;
-                            Ok(())
-                        }
-                        PERMISSION_YES => Ok(()),
-                        _ => Err(McpError::new(
-                            rmcp::model::ErrorCode::INVALID_REQUEST,
-                            format!("Access denied by user: {}", abs_path),
-                            None,
-                        )),
-                    }
-                } else {
-                    Err(McpError::new(
-                        rmcp::model::ErrorCode::INVALID_REQUEST,
-                        "No permission choice provided".to_string(),
-                        None,
-                    ))
+        let scopes = PathScopes::new(&abs_path);
+        let (choice, path_to_allow) = self.request_path_permission(message, &scopes, peer).await?;
+        match choice {
+            PermissionChoice::Always => {
+                let mut allowed_dirs = self.allowed_dirs.write().await;
+                if !allowed_dirs.contains(&path_to_allow) {
+                    allowed_dirs.push(path_to_allow);
+                }
+                drop(allowed_dirs);
+
+                // Save to config
+                let _ = self.save_allowed_dirs().await;
+                Ok(())
+            }
+            PermissionChoice::Session => {
+                let mut session_paths = self.session_grants.paths.write().await;
+                if !session_paths.contains(&path_to_allow) {
+                    session_paths.push(path_to_allow);
                 }
+                Ok(())
             }
-            ElicitationAction::Decline | ElicitationAction::Cancel => Err(McpError::new(
+            PermissionChoice::Once => Ok(()),
+            PermissionChoice::Denied => Err(McpError::new(
                 rmcp::model::ErrorCode::INVALID_REQUEST,
-                format!("Access denied: {}", abs_path),
+                format!("Access denied by user: {}", abs_path),
                 None,
             )),
+            PermissionChoice::Unsupported => {
+                if self.policy_allows_path(&abs_path, operation).await {
+                    return Ok(());
+                }
+                let message = if self.has_policy() {
+                    format!(
+                        "Access denied by permission policy: {} operation on {}",
+                        operation, abs_path
+                    )
+                } else {
+                    format!(
+                        "Access denied: {} is not within allowed directories. Client does not support elicitation for permission request.",
+                        abs_path
+                    )
+                };
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    message,
+                    None,
+                ))
+            }
         }
     }
 
-    /// Check path permission with elicitation support (using local IPC / libdive)
-    #[cfg(feature = "local_ipc")]
-    async fn check_path_permission_with_elicitation(
+    /// Check permission for several path operations, asking the user once
+    /// for all the paths not allowed yet
+    ///
+    /// Takes absolute paths with their operation. Grants cover the listed
+    /// paths themselves, not their folders.
+    pub(super) async fn check_paths_permission_with_elicitation(
         &self,
-        path: &str,
-        operation: &str,
-        _peer: &Peer<RoleServer>,
+        requests: &[(String, &str)],
+        peer: &Peer<RoleServer>,
     ) -> Result<(), McpError> {
-        let abs_path = Self::normalize_path(path);
+        for (_, operation) in requests {
+            self.check_read_only(operation)?;
+        }
 
-        // Check if already allowed
-        {
+        let pending: Vec<&(String, &str)> = {
             let allowed_dirs = self.allowed_dirs.read().await;
-            if self.is_path_allowed(&abs_path, &allowed_dirs) {
-                return Ok(());
-            }
+            let session_paths = self.session_grants.paths.read().await;
+            requests
+                .iter()
+                .filter(|(path, _)| {
+                    !self.is_path_allowed(path, &allowed_dirs)
+                        && !self.is_path_allowed(path, &session_paths)
+                })
+                .collect()
+        };
+        if pending.is_empty() {
+            return Ok(());
         }
 
-        // Request permission via local IPC (libdive)
+        let listing = pending
+            .iter()
+            .map(|(path, operation)| format!("  {}: {}", operation, path))
+            .collect::<Vec<_>>()
+            .join("\n");
         let message = format!(
-            "Permission required for {} operation on:\n{}\n\nAllow access?",
-            operation, abs_path
+            "Permission required for {} paths:\n{}\n\nAllow access?",
+            pending.len(),
+            listing
         );
 
-        let result = crate::local_ipc::request_elicitation(message, create_permission_schema())
-            .await
-            .map_err(|e| {
-                McpError::new(
-                    rmcp::model::ErrorCode::INTERNAL_ERROR,
-                    format!("Failed to request permission via local IPC: {}", e),
-                    None,
-                )
-            })?;
-
-        match result.action {
-            ElicitationAction::Accept => {
-                if let Some(content) = result.content {
-                    let choice = content
-                        .get("choice")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(PERMISSION_NO);
-
-                    match choice {
-                        PERMISSION_ALWAYS => {
-                            // Add parent directory to allowed list
-                            let dir_to_allow =
-                                Self::get_parent_dir(&abs_path).unwrap_or_else(|| abs_path.clone());
-
-                            let mut allowed_dirs = self.allowed_dirs.write().await;
-                            if !allowed_dirs.contains(&dir_to_allow) {
-                                allowed_dirs.push(dir_to_allow);
-                            }
-                            drop(allowed_dirs);
-
-                            // Save to config
-                            let _ = self.save_allowed_dirs().await;
-                            Ok(())
-                        }
-                        PERMISSION_YES => Ok(()),
-                        _ => Err(McpError::new(
-                            rmcp::model::ErrorCode::INVALID_REQUEST,
-                            format!("Access denied by user: {}", abs_path),
-                            None,
-                        )),
+        match self
+            .request_permission(message, "Always (remember these paths)", peer)
+            .await?
+        {
+            PermissionChoice::Always => {
+                let mut allowed_dirs = self.allowed_dirs.write().await;
+                for (path, _) in pending {
+                    if !allowed_dirs.contains(path) {
+                        allowed_dirs.push(path.clone());
                     }
-                } else {
-                    Err(McpError::new(
-                        rmcp::model::ErrorCode::INVALID_REQUEST,
-                        "No permission choice provided".to_string(),
-                        None,
-                    ))
                 }
+                drop(allowed_dirs);
+
+                // Save to config
+                let _ = self.save_allowed_dirs().await;
+                Ok(())
             }
-            ElicitationAction::Decline | ElicitationAction::Cancel => Err(McpError::new(
+            PermissionChoice::Session => {
+                let mut session_paths = self.session_grants.paths.write().await;
+                for (path, _) in pending {
+                    if !session_paths.contains(path) {
+                        session_paths.push(path.clone());
+                    }
+                }
+                Ok(())
+            }
+            PermissionChoice::Once => Ok(()),
+            PermissionChoice::Denied => Err(McpError::new(
                 rmcp::model::ErrorCode::INVALID_REQUEST,
-                format!("Access denied: {}", abs_path),
+                format!(
+                    "Access denied by user: {}",
+                    pending
+                        .iter()
+                        .map(|(path, _)| path.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
                 None,
             )),
+            PermissionChoice::Unsupported => {
+                let mut denied = Vec::new();
+                for (path, operation) in pending {
+                    if !self.policy_allows_path(path, operation).await {
+                        denied.push(path.as_str());
+                    }
+                }
+                if denied.is_empty() {
+                    return Ok(());
+                }
+                let message = if self.has_policy() {
+                    format!("Access denied by permission policy: {}", denied.join(", "))
+                } else {
+                    format!(
+                        "Access denied: {} not within allowed directories. Client does not support elicitation for permission request.",
+                        denied.join(", ")
+                    )
+                };
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    message,
+                    None,
+                ))
+            }
         }
     }
 
-    #[tool(description = "Read file content from the specified path")]
+    #[tool(
+        description = "Read file content from the specified path. For text files, `start_line` and `end_line` select a range of lines, like the line ranges listed by `code_outline`",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn read_file(
         &self,
         peer: Peer<RoleServer>,
@@ -369,7 +394,17 @@ impl DiveDefaultService {
         } else {
             // Read text file normally
             match fs::read_to_string(&params.path).await {
-                Ok(content) => Ok(CallToolResult::success(vec![Content::text(content)])),
+                Ok(content) if params.start_line.is_none() && params.end_line.is_none() => {
+                    Ok(CallToolResult::success(vec![Content::text(content)]))
+                }
+                Ok(content) => {
+                    let lines = line_range(&content, params.start_line, params.end_line).map_err(
+                        |message| {
+                            McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+                        },
+                    )?;
+                    Ok(CallToolResult::success(vec![Content::text(lines)]))
+                }
                 Err(e) => Err(McpError::new(
                     rmcp::model::ErrorCode::INTERNAL_ERROR,
                     format!("Failed to read file: {}", e),
@@ -379,7 +414,15 @@ impl DiveDefaultService {
         }
     }
 
-    #[tool(description = "Write content to a file at the specified path")]
+    #[tool(
+        description = "Write content to a file at the specified path",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn write_file(
         &self,
         peer: Peer<RoleServer>,
@@ -402,7 +445,16 @@ impl DiveDefaultService {
         }
     }
 
-    #[tool(description = "List all files and directories in the specified path")]
+    #[tool(
+        description = "List all files and directories in the specified path",
+        output_schema = cached_schema_for_type::<ListDirectoryOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn list_directory(
         &self,
         peer: Peer<RoleServer>,
@@ -422,12 +474,25 @@ impl DiveDefaultService {
                         } else {
                             "file"
                         };
-                        items.push(format!("{} ({})", file_name, file_type));
+                        items.push(DirectoryEntry {
+                            name: file_name,
+                            entry_type: file_type.to_string(),
+                        });
                     }
                 }
-                Ok(CallToolResult::success(vec![Content::text(
-                    items.join("\n"),
-                )]))
+
+                // Keep the plain "name (type)" listing as text content
+                let listing = items
+                    .iter()
+                    .map(|item| format!("{} ({})", item.name, item.entry_type))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let mut result = structured_result(&ListDirectoryOutput {
+                    path: params.path,
+                    entries: items,
+                })?;
+                result.content = vec![Content::text(listing)];
+                Ok(result)
             }
             Err(e) => Err(McpError::new(
                 rmcp::model::ErrorCode::INTERNAL_ERROR,
@@ -437,7 +502,15 @@ impl DiveDefaultService {
         }
     }
 
-    #[tool(description = "Create a new directory at the specified path")]
+    #[tool(
+        description = "Create a new directory at the specified path",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn create_directory(
         &self,
         peer: Peer<RoleServer>,
@@ -460,7 +533,15 @@ impl DiveDefaultService {
         }
     }
 
-    #[tool(description = "Delete a file at the specified path")]
+    #[tool(
+        description = "Delete a file at the specified path",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn delete_file(
         &self,
         peer: Peer<RoleServer>,
@@ -520,7 +601,15 @@ impl DiveDefaultService {
     //     ))]))
     // }
 
-    #[tool(description = "Remove a directory from the allowed directories list")]
+    #[tool(
+        description = "Remove a directory from the allowed directories list",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
     async fn deny_dir(
         &self,
         Parameters(params): Parameters<DenyDirParams>,
@@ -549,9 +638,14 @@ impl DiveDefaultService {
             ));
         }
 
+        let note = if self.read_only {
+            " for this process (read-only mode, the config file is unchanged)"
+        } else {
+            ""
+        };
         Ok(CallToolResult::success(vec![Content::text(format!(
-            "Successfully removed {} from allowed directories",
-            path
+            "Successfully removed {} from allowed directories{}",
+            path, note
         ))]))
     }
 
@@ -570,3 +664,28 @@ impl DiveDefaultService {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_range() {
+        let content = "one\r\ntwo\nthree";
+        assert_eq!(line_range(content, None, None).unwrap(), content);
+        assert_eq!(line_range(content, Some(2), None).unwrap(), "two\nthree");
+        assert_eq!(line_range(content, Some(1), Some(1)).unwrap(), "one\r\n");
+        assert_eq!(
+            line_range(content, Some(0), Some(2)).unwrap(),
+            "one\r\ntwo\n"
+        );
+        assert_eq!(line_range(content, Some(3), Some(10)).unwrap(), "three");
+        assert!(line_range(content, Some(4), None).is_err());
+        assert!(line_range(content, Some(3), Some(2)).is_err());
+
+        // A final newline does not start another line
+        assert_eq!(line_range("a\nb\n", Some(2), None).unwrap(), "b\n");
+        assert!(line_range("a\nb\n", Some(3), None).is_err());
+        assert_eq!(line_range("", Some(1), None).unwrap(), "");
+    }
+}
@@ -0,0 +1,994 @@
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use git2::{
+    BlameOptions, Delta, Diff, DiffFindOptions, DiffFormat, DiffOptions, Oid, Repository, Sort,
+    Status, StatusOptions,
+};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Maximum size of a diff or file returned to the model
+const OUTPUT_LIMIT: usize = 128 * 1024;
+/// Maximum number of status entries returned
+const STATUS_LIMIT: usize = 1000;
+/// Commits returned by `git_log` when no limit is given
+const DEFAULT_LOG_LIMIT: usize = 20;
+/// Maximum number of commits returned by `git_log`
+const MAX_LOG_LIMIT: usize = 200;
+/// Maximum number of commits inspected when filtering the log by path
+const MAX_LOG_SCAN: usize = 10_000;
+/// Maximum number of lines blamed at once
+const MAX_BLAME_LINES: usize = 500;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct GitStatusParams {
+    /// A path inside the repository
+    path: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct GitDiffParams {
+    /// A path inside the repository
+    path: String,
+    /// Base revision (commit, branch, tag). Without it the working tree is
+    /// compared to the index, or the index to HEAD when `staged` is set
+    #[serde(default)]
+    from: Option<String>,
+    /// Target revision, compared to `from`. Without it `from` is compared to the working tree
+    #[serde(default)]
+    to: Option<String>,
+    /// Show the staged changes (index against HEAD)
+    #[serde(default)]
+    staged: bool,
+    /// Only show changes to these paths (relative to the repository root)
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Lines of context around each change (default 3)
+    #[serde(default)]
+    context_lines: Option<u32>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct GitLogParams {
+    /// A path inside the repository
+    path: String,
+    /// Revision to start from (default HEAD)
+    #[serde(default)]
+    rev: Option<String>,
+    /// Only list commits changing this file or directory
+    #[serde(default)]
+    file: Option<String>,
+    /// Maximum number of commits to return (default 20, max 200)
+    #[serde(default)]
+    limit: Option<usize>,
+    /// Number of matching commits to skip
+    #[serde(default)]
+    skip: usize,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct GitShowFileParams {
+    /// A path inside the repository
+    path: String,
+    /// The file to show, relative to the repository root or absolute
+    file: String,
+    /// Revision to read the file at (default HEAD)
+    #[serde(default)]
+    rev: Option<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct GitBlameParams {
+    /// A path inside the repository
+    path: String,
+    /// The file to blame, relative to the repository root or absolute
+    file: String,
+    /// Revision to blame at (default HEAD)
+    #[serde(default)]
+    rev: Option<String>,
+    /// First line to blame, starting at 1
+    #[serde(default)]
+    start_line: Option<usize>,
+    /// Last line to blame (at most 500 lines are blamed at once)
+    #[serde(default)]
+    end_line: Option<usize>,
+}
+
+/// Structured result of `git_status`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct GitStatusOutput {
+    /// Root of the working tree
+    root: String,
+    /// Current branch, none when HEAD is detached
+    branch: Option<String>,
+    /// Commit HEAD points to, none in an empty repository
+    head: Option<String>,
+    /// Upstream of the current branch
+    upstream: Option<GitUpstream>,
+    /// Operation in progress, like merge or rebase, or clean
+    state: String,
+    /// Whether there is nothing to commit
+    clean: bool,
+    /// More entries were changed than returned
+    truncated: bool,
+    /// Changed, staged and untracked files
+    entries: Vec<GitStatusEntry>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct GitUpstream {
+    /// Name of the upstream branch
+    name: String,
+    /// Commits on the branch and not on its upstream
+    ahead: usize,
+    /// Commits on the upstream and not on the branch
+    behind: usize,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct GitStatusEntry {
+    /// Path relative to the repository root
+    path: String,
+    /// Staged change: added, modified, deleted, renamed or typechange
+    index: Option<String>,
+    /// Unstaged change: untracked, modified, deleted, renamed, typechange or conflicted
+    worktree: Option<String>,
+}
+
+/// Structured result of `git_diff`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct GitDiffOutput {
+    files_changed: usize,
+    insertions: usize,
+    deletions: usize,
+    /// The patch was cut to the output limit
+    truncated: bool,
+    files: Vec<GitDiffFile>,
+    /// Unified patch
+    patch: String,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct GitDiffFile {
+    /// Path after the change
+    path: Option<String>,
+    /// Path before a rename
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    /// added, deleted, modified, renamed, copied, typechange or conflicted
+    status: String,
+}
+
+/// Structured result of `git_log`
+#[derive(Serialize, schemars::JsonSchema)]
+struct GitLogOutput {
+    /// Revision the log starts from
+    rev: String,
+    /// File or directory the commits were filtered by
+    file: Option<String>,
+    /// More commits match, use skip to page
+    more: bool,
+    /// Commits, newest first
+    commits: Vec<GitCommit>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct GitCommit {
+    id: String,
+    author: String,
+    email: String,
+    /// Author date
+    date: String,
+    /// First line of the message
+    summary: String,
+    /// Rest of the message
+    body: Option<String>,
+    parents: Vec<String>,
+}
+
+/// Structured result of `git_show_file`
+#[derive(Serialize, schemars::JsonSchema)]
+struct GitShowFileOutput {
+    /// Path relative to the repository root
+    file: String,
+    /// Revision the file was read at
+    rev: String,
+    /// Size of the file in bytes
+    size: usize,
+    /// Binary files are not returned
+    binary: bool,
+    /// The content was cut to the output limit
+    truncated: bool,
+    /// Text of the file
+    content: Option<String>,
+}
+
+/// Structured result of `git_blame`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct GitBlameOutput {
+    /// Path relative to the repository root
+    file: String,
+    /// Revision blamed at
+    rev: String,
+    start_line: usize,
+    end_line: usize,
+    /// Number of lines of the file
+    total_lines: usize,
+    /// Consecutive lines last changed by the same commit
+    hunks: Vec<GitBlameHunk>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct GitBlameHunk {
+    start_line: usize,
+    end_line: usize,
+    commit: String,
+    author: String,
+    date: String,
+    /// Summary of the commit
+    summary: Option<String>,
+    lines: Vec<String>,
+}
+
+fn git_error(e: git2::Error) -> McpError {
+    McpError::new(
+        rmcp::model::ErrorCode::INVALID_PARAMS,
+        format!("Git error: {}", e.message()),
+        None,
+    )
+}
+
+fn invalid_params(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+}
+
+/// Cut `text` to at most `limit` bytes on a character boundary
+fn truncate(text: &mut String, limit: usize) -> bool {
+    if text.len() <= limit {
+        return false;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
+}
+
+/// Path of `file` relative to the repository root, rejecting paths outside it
+fn repo_relative(root: &Path, file: &str) -> Result<PathBuf, McpError> {
+    let path = Path::new(file);
+    let relative = if path.is_absolute() {
+        let absolute = PathBuf::from(DiveDefaultService::normalize_path(file));
+        absolute
+            .strip_prefix(root)
+            .map(Path::to_path_buf)
+            .map_err(|_| invalid_params(format!("{} is outside the repository", file)))?
+    } else {
+        path.to_path_buf()
+    };
+    if relative
+        .components()
+        .any(|component| !matches!(component, std::path::Component::Normal(_)))
+    {
+        return Err(invalid_params(format!(
+            "{} is not a path inside the repository",
+            file
+        )));
+    }
+    Ok(relative)
+}
+
+fn format_time(time: git2::Time) -> String {
+    let seconds = u64::try_from(time.seconds()).unwrap_or(0);
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn commit_output(commit: &git2::Commit) -> GitCommit {
+    let author = commit.author();
+    GitCommit {
+        id: commit.id().to_string(),
+        author: author.name().unwrap_or("").to_string(),
+        email: author.email().unwrap_or("").to_string(),
+        date: format_time(author.when()),
+        summary: commit.summary().unwrap_or("").to_string(),
+        body: commit
+            .body()
+            .map(str::trim)
+            .filter(|body| !body.is_empty())
+            .map(String::from),
+        parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+    }
+}
+
+fn resolve_commit<'r>(
+    repo: &'r Repository,
+    rev: Option<&str>,
+) -> Result<git2::Commit<'r>, McpError> {
+    repo.revparse_single(rev.unwrap_or("HEAD"))
+        .and_then(|object| object.peel_to_commit())
+        .map_err(git_error)
+}
+
+fn resolve_tree<'r>(repo: &'r Repository, rev: &str) -> Result<git2::Tree<'r>, McpError> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(git_error)
+}
+
+fn index_status(status: Status) -> Option<&'static str> {
+    if status.is_index_new() {
+        Some("added")
+    } else if status.is_index_modified() {
+        Some("modified")
+    } else if status.is_index_deleted() {
+        Some("deleted")
+    } else if status.is_index_renamed() {
+        Some("renamed")
+    } else if status.is_index_typechange() {
+        Some("typechange")
+    } else {
+        None
+    }
+}
+
+fn worktree_status(status: Status) -> Option<&'static str> {
+    if status.is_conflicted() {
+        Some("conflicted")
+    } else if status.is_wt_new() {
+        Some("untracked")
+    } else if status.is_wt_modified() {
+        Some("modified")
+    } else if status.is_wt_deleted() {
+        Some("deleted")
+    } else if status.is_wt_renamed() {
+        Some("renamed")
+    } else if status.is_wt_typechange() {
+        Some("typechange")
+    } else {
+        None
+    }
+}
+
+fn delta_status(delta: Delta) -> &'static str {
+    match delta {
+        Delta::Added | Delta::Untracked => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        Delta::Conflicted => "conflicted",
+        _ => "modified",
+    }
+}
+
+fn read_status(repo: &Repository) -> Result<GitStatusOutput, McpError> {
+    let head = repo.head().ok();
+    let branch = head
+        .as_ref()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(String::from));
+    let head_id = head.as_ref().and_then(|head| head.target());
+
+    // Commits ahead of and behind the upstream branch
+    let upstream = branch.as_ref().and_then(|name| {
+        let local = repo.find_branch(name, git2::BranchType::Local).ok()?;
+        let upstream = local.upstream().ok()?;
+        let upstream_name = upstream.name().ok().flatten()?.to_string();
+        let (ahead, behind) = repo
+            .graph_ahead_behind(head_id?, upstream.get().target()?)
+            .ok()?;
+        Some(GitUpstream {
+            name: upstream_name,
+            ahead,
+            behind,
+        })
+    });
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut options)).map_err(git_error)?;
+    let entries = statuses
+        .iter()
+        .take(STATUS_LIMIT)
+        .map(|entry| {
+            let status = entry.status();
+            GitStatusEntry {
+                path: entry.path().unwrap_or("").to_string(),
+                index: index_status(status).map(String::from),
+                worktree: worktree_status(status).map(String::from),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(GitStatusOutput {
+        root: repo.workdir().unwrap_or(repo.path()).display().to_string(),
+        branch,
+        head: head_id.map(|id| id.to_string()),
+        upstream,
+        state: format!("{:?}", repo.state()).to_lowercase(),
+        clean: statuses.is_empty(),
+        truncated: statuses.len() > entries.len(),
+        entries,
+    })
+}
+
+fn build_diff<'r>(repo: &'r Repository, params: &GitDiffParams) -> Result<Diff<'r>, McpError> {
+    let mut options = DiffOptions::new();
+    options.context_lines(params.context_lines.unwrap_or(3));
+    for path in &params.paths {
+        options.pathspec(path);
+    }
+
+    let mut diff = match (params.from.as_deref(), params.to.as_deref()) {
+        (Some(from), Some(to)) => {
+            let from = resolve_tree(repo, from)?;
+            let to = resolve_tree(repo, to)?;
+            repo.diff_tree_to_tree(Some(&from), Some(&to), Some(&mut options))
+        }
+        (Some(from), None) => {
+            let from = resolve_tree(repo, from)?;
+            repo.diff_tree_to_workdir_with_index(Some(&from), Some(&mut options))
+        }
+        (None, Some(_)) => {
+            return Err(invalid_params("`to` requires `from` to be set".to_string()));
+        }
+        (None, None) if params.staged => {
+            // An unborn HEAD has no tree, everything staged is new
+            let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+            repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))
+        }
+        (None, None) => repo.diff_index_to_workdir(None, Some(&mut options)),
+    }
+    .map_err(git_error)?;
+
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(git_error)?;
+    Ok(diff)
+}
+
+/// Render a diff as a unified patch of at most `OUTPUT_LIMIT` bytes
+fn diff_patch(diff: &Diff) -> (String, bool) {
+    let mut patch = String::new();
+    let mut truncated = false;
+    let _ = diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        if patch.len() > OUTPUT_LIMIT {
+            truncated = true;
+            return false;
+        }
+        true
+    });
+    if truncated {
+        truncate(&mut patch, OUTPUT_LIMIT);
+    }
+    (patch, truncated)
+}
+
+fn read_diff(repo: &Repository, params: &GitDiffParams) -> Result<GitDiffOutput, McpError> {
+    let diff = build_diff(repo, params)?;
+    let stats = diff.stats().map_err(git_error)?;
+    let files = diff
+        .deltas()
+        .map(|delta| {
+            let new_path = delta.new_file().path().map(|p| p.display().to_string());
+            let old_path = delta.old_file().path().map(|p| p.display().to_string());
+            GitDiffFile {
+                path: new_path.clone().or(old_path.clone()),
+                old_path: old_path.filter(|old| Some(old) != new_path.as_ref()),
+                status: delta_status(delta.status()).to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let (patch, truncated) = diff_patch(&diff);
+
+    Ok(GitDiffOutput {
+        files_changed: stats.files_changed(),
+        insertions: stats.insertions(),
+        deletions: stats.deletions(),
+        truncated,
+        files,
+        patch,
+    })
+}
+
+/// Text of a diff result: the stats, one line per file, then the patch
+fn diff_text(output: &GitDiffOutput) -> String {
+    let mut text = format!(
+        "{} files changed, {} insertions(+), {} deletions(-)\n",
+        output.files_changed, output.insertions, output.deletions
+    );
+    for file in &output.files {
+        let path = file.path.as_deref().unwrap_or_default();
+        match &file.old_path {
+            Some(old_path) => text.push_str(&format!("{} {} -> {}\n", file.status, old_path, path)),
+            None => text.push_str(&format!("{} {}\n", file.status, path)),
+        }
+    }
+    if !output.patch.is_empty() {
+        text.push('\n');
+        text.push_str(&output.patch);
+    }
+    if output.truncated {
+        text.push_str("\n[truncated]");
+    }
+    text
+}
+
+/// Whether a commit changes `file`, compared to its first parent
+fn touches(repo: &Repository, commit: &git2::Commit, file: &Path) -> Result<bool, git2::Error> {
+    let tree = commit.tree()?;
+    let parent = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let mut options = DiffOptions::new();
+    options.pathspec(file);
+    let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), Some(&mut options))?;
+    Ok(diff.deltas().len() > 0)
+}
+
+fn read_log(
+    repo: &Repository,
+    root: &Path,
+    params: &GitLogParams,
+) -> Result<GitLogOutput, McpError> {
+    let start = resolve_commit(repo, params.rev.as_deref())?;
+    let file = params
+        .file
+        .as_deref()
+        .map(|file| repo_relative(root, file))
+        .transpose()?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LOG_LIMIT)
+        .clamp(1, MAX_LOG_LIMIT);
+
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    walk.set_sorting(Sort::TIME).map_err(git_error)?;
+    walk.push(start.id()).map_err(git_error)?;
+
+    let mut commits = Vec::new();
+    let mut skipped = 0;
+    let mut more = false;
+    for (scanned, oid) in walk.enumerate() {
+        if file.is_some() && scanned >= MAX_LOG_SCAN {
+            more = true;
+            break;
+        }
+        let commit = repo
+            .find_commit(oid.map_err(git_error)?)
+            .map_err(git_error)?;
+        if let Some(file) = file.as_ref()
+            && !touches(repo, &commit, file).map_err(git_error)?
+        {
+            continue;
+        }
+        if skipped < params.skip {
+            skipped += 1;
+            continue;
+        }
+        if commits.len() == limit {
+            more = true;
+            break;
+        }
+        commits.push(commit_output(&commit));
+    }
+
+    Ok(GitLogOutput {
+        rev: params.rev.as_deref().unwrap_or("HEAD").to_string(),
+        file: file.map(|file| file.display().to_string()),
+        more,
+        commits,
+    })
+}
+
+fn read_file_at(
+    repo: &Repository,
+    root: &Path,
+    params: &GitShowFileParams,
+) -> Result<GitShowFileOutput, McpError> {
+    let file = repo_relative(root, &params.file)?;
+    let rev = params.rev.as_deref().unwrap_or("HEAD");
+    let tree = resolve_tree(repo, rev)?;
+    let blob = tree
+        .get_path(&file)
+        .and_then(|entry| entry.to_object(repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(git_error)?;
+
+    let mut output = GitShowFileOutput {
+        file: file.display().to_string(),
+        rev: rev.to_string(),
+        size: blob.size(),
+        binary: blob.is_binary(),
+        truncated: false,
+        content: None,
+    };
+    if !output.binary {
+        let mut content = String::from_utf8_lossy(blob.content()).to_string();
+        output.truncated = truncate(&mut content, OUTPUT_LIMIT);
+        output.content = Some(content);
+    }
+    Ok(output)
+}
+
+/// Text of a file result: the content, or a note for binary files
+fn file_text(output: &GitShowFileOutput) -> String {
+    match &output.content {
+        Some(content) if output.truncated => format!(
+            "{}\n\n[truncated, the file is {} bytes]",
+            content, output.size
+        ),
+        Some(content) => content.clone(),
+        None => format!(
+            "Binary file {} at {} ({} bytes)",
+            output.file, output.rev, output.size
+        ),
+    }
+}
+
+fn read_blame(
+    repo: &Repository,
+    root: &Path,
+    params: &GitBlameParams,
+) -> Result<GitBlameOutput, McpError> {
+    let file = repo_relative(root, &params.file)?;
+    let commit = resolve_commit(repo, params.rev.as_deref())?;
+    let blob = commit
+        .tree()
+        .and_then(|tree| tree.get_path(&file))
+        .and_then(|entry| entry.to_object(repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(git_error)?;
+    if blob.is_binary() {
+        return Err(invalid_params(format!(
+            "Cannot blame binary file {}",
+            file.display()
+        )));
+    }
+    let content = String::from_utf8_lossy(blob.content()).to_string();
+    let lines = content.lines().collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Err(invalid_params(format!("{} is empty", file.display())));
+    }
+
+    let start = params.start_line.unwrap_or(1).max(1);
+    let end = params
+        .end_line
+        .unwrap_or(usize::MAX)
+        .min(start + MAX_BLAME_LINES - 1)
+        .min(lines.len());
+    if start > end {
+        return Err(invalid_params(format!(
+            "Line {} is past the end of {} ({} lines)",
+            start,
+            file.display(),
+            lines.len()
+        )));
+    }
+
+    let mut options = BlameOptions::new();
+    options
+        .newest_commit(commit.id())
+        .min_line(start)
+        .max_line(end);
+    let blame = repo
+        .blame_file(&file, Some(&mut options))
+        .map_err(git_error)?;
+
+    // Group consecutive lines coming from the same commit
+    let mut hunks: Vec<GitBlameHunk> = Vec::new();
+    let mut current: Option<Oid> = None;
+    for line_number in start..=end {
+        let Some(hunk) = blame.get_line(line_number) else {
+            continue;
+        };
+        let id = hunk.final_commit_id();
+        let text = lines[line_number - 1];
+        if current == Some(id)
+            && let Some(last) = hunks.last_mut()
+        {
+            last.end_line = line_number;
+            last.lines.push(text.to_string());
+            continue;
+        }
+        current = Some(id);
+        let signature = hunk.final_signature();
+        let summary = repo
+            .find_commit(id)
+            .ok()
+            .and_then(|commit| commit.summary().map(String::from));
+        hunks.push(GitBlameHunk {
+            start_line: line_number,
+            end_line: line_number,
+            commit: id.to_string(),
+            author: signature.name().unwrap_or("").to_string(),
+            date: format_time(signature.when()),
+            summary,
+            lines: vec![text.to_string()],
+        });
+    }
+
+    Ok(GitBlameOutput {
+        file: file.display().to_string(),
+        rev: params.rev.as_deref().unwrap_or("HEAD").to_string(),
+        start_line: start,
+        end_line: end,
+        total_lines: lines.len(),
+        hunks,
+    })
+}
+
+#[tool_router(router = tool_router_git, vis = "pub")]
+impl DiveDefaultService {
+    /// Open the repository containing `path`, after checking read access to
+    /// its whole working tree
+    async fn open_repository(
+        &self,
+        path: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(Repository, PathBuf), McpError> {
+        let repo = Repository::discover(path).map_err(|e| {
+            invalid_params(format!("No git repository at {}: {}", path, e.message()))
+        })?;
+        let root = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+        let root = PathBuf::from(Self::normalize_path(&root.to_string_lossy()));
+        self.check_path_permission_with_elicitation(&root.to_string_lossy(), "read", peer)
+            .await?;
+        Ok((repo, root))
+    }
+
+    #[tool(
+        description = "Show the status of a git repository: current branch, upstream ahead/behind counts and changed, staged and untracked files",
+        output_schema = cached_schema_for_type::<GitStatusOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn git_status(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<GitStatusParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (repo, _) = self.open_repository(&params.path, &peer).await?;
+        let output = run_blocking(move || read_status(&repo)).await?;
+        structured_result(&output)
+    }
+
+    #[tool(
+        description = "Show a git diff as a unified patch with per-file stats: unstaged changes by default, staged changes with `staged`, a revision against the working tree with `from`, or two revisions with `from` and `to`",
+        output_schema = cached_schema_for_type::<GitDiffOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn git_diff(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<GitDiffParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (repo, _) = self.open_repository(&params.path, &peer).await?;
+        let output = run_blocking(move || read_diff(&repo, &params)).await?;
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(diff_text(&output))];
+        Ok(result)
+    }
+
+    #[tool(
+        description = "List the commits of a git repository, newest first, optionally only those changing a file or directory",
+        output_schema = cached_schema_for_type::<GitLogOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn git_log(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<GitLogParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (repo, root) = self.open_repository(&params.path, &peer).await?;
+        let output = run_blocking(move || read_log(&repo, &root, &params)).await?;
+        structured_result(&output)
+    }
+
+    #[tool(
+        description = "Show the content of a file at a git revision (commit, branch or tag)",
+        output_schema = cached_schema_for_type::<GitShowFileOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn git_show_file(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<GitShowFileParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (repo, root) = self.open_repository(&params.path, &peer).await?;
+        let output = run_blocking(move || read_file_at(&repo, &root, &params)).await?;
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(file_text(&output))];
+        Ok(result)
+    }
+
+    #[tool(
+        description = "Show which commit last changed each line of a file, for a range of at most 500 lines. Uncommitted changes are not included",
+        output_schema = cached_schema_for_type::<GitBlameOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn git_blame(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<GitBlameParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (repo, root) = self.open_repository(&params.path, &peer).await?;
+        let output = run_blocking(move || read_blame(&repo, &root, &params)).await?;
+        structured_result(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_relative() {
+        let root = Path::new("/repo");
+        assert_eq!(
+            repo_relative(root, "src/main.rs").unwrap(),
+            PathBuf::from("src/main.rs")
+        );
+        assert!(repo_relative(root, "../other/file").is_err());
+        assert!(repo_relative(root, "/elsewhere/file").is_err());
+
+        let mut text = "héllo".to_string();
+        assert!(truncate(&mut text, 2));
+        assert_eq!(text, "h");
+    }
+    #[test]
+    fn test_read_repository() {
+        let root = std::env::temp_dir().join(format!("dive-git-{}", uuid::Uuid::new_v4().simple()));
+        let repo = Repository::init(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Ada", "ada@example.com").unwrap();
+        let id = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Add a\n\nFirst file",
+                &tree,
+                &[],
+            )
+            .unwrap()
+            .to_string();
+
+        std::fs::write(root.join("a.txt"), "one\nTWO\n").unwrap();
+        std::fs::write(root.join("b.txt"), "new\n").unwrap();
+
+        let status = read_status(&repo).unwrap();
+        assert_eq!(status.head.as_deref(), Some(id.as_str()));
+        assert!(status.branch.is_some());
+        assert!(!status.clean);
+        let entries: Vec<_> = status
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.index.as_deref(), e.worktree.as_deref()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("a.txt", None, Some("modified")),
+                ("b.txt", None, Some("untracked"))
+            ]
+        );
+
+        let params = GitDiffParams {
+            path: String::new(),
+            from: None,
+            to: None,
+            staged: false,
+            paths: Vec::new(),
+            context_lines: None,
+        };
+        let diff = read_diff(&repo, &params).unwrap();
+        assert_eq!(
+            (diff.files_changed, diff.insertions, diff.deletions),
+            (1, 1, 1)
+        );
+        assert_eq!(diff.files[0].path.as_deref(), Some("a.txt"));
+        assert!(diff.patch.contains("-two\n+TWO\n"));
+        assert!(
+            diff_text(&diff)
+                .starts_with("1 files changed, 1 insertions(+), 1 deletions(-)\nmodified a.txt\n")
+        );
+
+        let log = read_log(
+            &repo,
+            &root,
+            &GitLogParams {
+                path: String::new(),
+                rev: None,
+                file: Some("a.txt".to_string()),
+                limit: None,
+                skip: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(log.commits.len(), 1);
+        assert_eq!(log.commits[0].summary, "Add a");
+        assert_eq!(log.commits[0].body.as_deref(), Some("First file"));
+        assert!(!log.more);
+
+        let file = read_file_at(
+            &repo,
+            &root,
+            &GitShowFileParams {
+                path: String::new(),
+                file: "a.txt".to_string(),
+                rev: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(file.content.as_deref(), Some("one\ntwo\n"));
+        assert_eq!(file_text(&file), "one\ntwo\n");
+
+        // Blame reads the committed file, not the working tree
+        let blame = read_blame(
+            &repo,
+            &root,
+            &GitBlameParams {
+                path: String::new(),
+                file: "a.txt".to_string(),
+                rev: None,
+                start_line: None,
+                end_line: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(blame.total_lines, 2);
+        assert_eq!(blame.hunks.len(), 1);
+        assert_eq!(blame.hunks[0].commit, id);
+        assert_eq!(blame.hunks[0].author, "Ada");
+        assert_eq!(blame.hunks[0].lines, ["one", "two"]);
+        assert_eq!((blame.hunks[0].start_line, blame.hunks[0].end_line), (1, 2));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,726 @@
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{
+    AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, RgbImage,
+};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::CallToolResult,
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Maximum width or height of images read or written
+const MAX_DIMENSION: u32 = 16384;
+/// Maximum memory used to decode one image
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+/// Default JPEG quality
+const DEFAULT_QUALITY: u8 = 85;
+/// Maximum length of an EXIF value in `image_info`
+const MAX_EXIF_VALUE: usize = 200;
+
+/// Formats images can be written in
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Webp,
+    Gif,
+    Bmp,
+}
+
+impl OutputFormat {
+    fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(OutputFormat::Png),
+            ImageFormat::Jpeg => Some(OutputFormat::Jpeg),
+            ImageFormat::WebP => Some(OutputFormat::Webp),
+            ImageFormat::Gif => Some(OutputFormat::Gif),
+            ImageFormat::Bmp => Some(OutputFormat::Bmp),
+            _ => None,
+        }
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Gif => ImageFormat::Gif,
+            OutputFormat::Bmp => ImageFormat::Bmp,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpeg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Bmp => "bmp",
+        }
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ImageInfoParams {
+    /// Path of the image file
+    path: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum ResizeMode {
+    /// Fit inside width x height, keeping the aspect ratio
+    #[default]
+    Fit,
+    /// Cover width x height, keeping the aspect ratio and cropping the overflow
+    Fill,
+    /// Stretch to exactly width x height
+    Exact,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ResizeImageParams {
+    /// Path of the image file
+    path: String,
+    /// Target width in pixels, computed from the aspect ratio when omitted
+    width: Option<u32>,
+    /// Target height in pixels, computed from the aspect ratio when omitted
+    height: Option<u32>,
+    /// How to use width and height when both are given: fit (default), fill or exact
+    mode: Option<ResizeMode>,
+    /// Where to write the result, the format follows its extension (defaults to overwriting the image)
+    output: Option<String>,
+    /// JPEG quality from 1 to 100 (default 85)
+    quality: Option<u8>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct CropImageParams {
+    /// Path of the image file
+    path: String,
+    /// Left edge of the area to keep, in pixels
+    x: u32,
+    /// Top edge of the area to keep, in pixels
+    y: u32,
+    /// Width of the area to keep
+    width: u32,
+    /// Height of the area to keep
+    height: u32,
+    /// Where to write the result, the format follows its extension (defaults to overwriting the image)
+    output: Option<String>,
+    /// JPEG quality from 1 to 100 (default 85)
+    quality: Option<u8>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct RotateImageParams {
+    /// Path of the image file
+    path: String,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270 (negative values rotate counter-clockwise)
+    degrees: i32,
+    /// Mirror the image after rotating it
+    flip: Option<Flip>,
+    /// Where to write the result, the format follows its extension (defaults to overwriting the image)
+    output: Option<String>,
+    /// JPEG quality from 1 to 100 (default 85)
+    quality: Option<u8>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ConvertImageParams {
+    /// Path of the image file
+    path: String,
+    /// Where to write the converted image
+    output: String,
+    /// Target format: png, jpeg or webp (also gif or bmp), defaults to the output extension
+    format: Option<OutputFormat>,
+    /// JPEG quality from 1 to 100 (default 85)
+    quality: Option<u8>,
+}
+
+/// Structured result of `image_info`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ImageInfoOutput {
+    /// Path of the image file
+    path: String,
+    /// Detected image format
+    format: String,
+    /// Width in pixels as stored
+    width: u32,
+    /// Height in pixels as stored
+    height: u32,
+    /// Pixel layout, like Rgba8
+    color_type: String,
+    /// File size in bytes
+    size: u64,
+    /// EXIF orientation, applied when the image is edited
+    orientation: String,
+    /// EXIF fields of the primary image, by tag name
+    exif: BTreeMap<String, String>,
+}
+
+/// Structured result of the image editing tools
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ImageOutput {
+    /// Path of the written image
+    path: String,
+    /// Format of the written image
+    format: String,
+    /// Width in pixels
+    width: u32,
+    /// Height in pixels
+    height: u32,
+    /// File size in bytes
+    size: u64,
+    /// The input had EXIF metadata, like the camera or GPS position, which is
+    /// not written to the result
+    metadata_dropped: bool,
+}
+
+fn decode_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    limits
+}
+
+fn image_reader(path: &Path) -> Result<ImageReader<BufReader<File>>, String> {
+    let mut reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    reader.limits(decode_limits());
+    Ok(reader)
+}
+
+/// Whether a GIF or WebP image has more than one frame
+fn is_animated(path: &Path, format: ImageFormat) -> Result<bool, image::ImageError> {
+    let reader = BufReader::new(File::open(path)?);
+    match format {
+        ImageFormat::Gif => {
+            let mut decoder = GifDecoder::new(reader)?;
+            decoder.set_limits(decode_limits())?;
+            Ok(decoder.into_frames().take(2).filter(Result::is_ok).count() > 1)
+        }
+        ImageFormat::WebP => Ok(WebPDecoder::new(reader)?.has_animation()),
+        _ => Ok(false),
+    }
+}
+
+/// Decode an image, turned upright according to its EXIF orientation
+///
+/// Animated images are refused, only their first frame would be kept.
+fn open_image(path: &Path) -> Result<(DynamicImage, ImageFormat), String> {
+    let reader = image_reader(path)?;
+    let format = reader.format().ok_or("Unrecognized image format")?;
+    let animated = is_animated(path, format)
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    if animated {
+        return Err(format!(
+            "{} is animated, editing it would keep only the first frame",
+            path.display()
+        ));
+    }
+    let decode = || {
+        let mut decoder = reader.into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        Ok::<_, image::ImageError>(image)
+    };
+    let image = decode().map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    Ok((image, format))
+}
+
+/// Read the EXIF fields of the primary image, if the container has any
+fn read_exif(path: &Path) -> BTreeMap<String, String> {
+    let Ok(file) = File::open(path) else {
+        return BTreeMap::new();
+    };
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(file)) else {
+        return BTreeMap::new();
+    };
+    exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY && field.tag != exif::Tag::MakerNote)
+        .map(|field| {
+            let mut value = field.display_value().with_unit(&exif).to_string();
+            if let exif::Value::Ascii(_) = field.value {
+                value = value.trim_matches('"').to_string();
+            }
+            if value.len() > MAX_EXIF_VALUE {
+                let mut end = MAX_EXIF_VALUE;
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                value.truncate(end);
+                value.push_str("...");
+            }
+            (field.tag.to_string(), value)
+        })
+        .collect()
+}
+
+fn image_info(path: &Path) -> Result<ImageInfoOutput, String> {
+    let size = fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len();
+    let reader = image_reader(path)?;
+    let format = reader.format().ok_or("Unrecognized image format")?;
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    let (width, height) = decoder.dimensions();
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+
+    Ok(ImageInfoOutput {
+        path: path.to_string_lossy().to_string(),
+        format: OutputFormat::from_image_format(format)
+            .map(|format| format.as_str().to_string())
+            .unwrap_or_else(|| format!("{:?}", format).to_lowercase()),
+        width,
+        height,
+        color_type: format!("{:?}", decoder.color_type()),
+        size,
+        orientation: format!("{:?}", orientation),
+        exif: read_exif(path),
+    })
+}
+
+/// Format to write `output` in: `format`, else the one of the output
+/// extension, else the one of the input when the output has no extension
+fn output_format(
+    format: Option<OutputFormat>,
+    output: &Path,
+    input_format: ImageFormat,
+) -> Result<OutputFormat, String> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    let supported = "give an output ending in .png, .jpg, .webp, .gif or .bmp";
+    match output.extension() {
+        Some(extension) => ImageFormat::from_extension(extension)
+            .and_then(OutputFormat::from_image_format)
+            .ok_or_else(|| {
+                format!(
+                    "Cannot write .{} images, {}",
+                    extension.to_string_lossy(),
+                    supported
+                )
+            }),
+        None => OutputFormat::from_image_format(input_format)
+            .ok_or_else(|| format!("Cannot write this format, {}", supported)),
+    }
+}
+
+/// Size of an image resized to `width` x `height`, a missing side follows
+/// the aspect ratio
+fn target_size(
+    (from_width, from_height): (u32, u32),
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<(u32, u32), String> {
+    let scaled = |side: u32, from: u32, to: u32| {
+        ((side as u64 * to as u64 + from as u64 / 2) / from as u64).max(1) as u32
+    };
+    let size = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, scaled(from_height, from_width, width)),
+        (None, Some(height)) => (scaled(from_width, from_height, height), height),
+        (None, None) => return Err("Give a width, a height or both".to_string()),
+    };
+    if size.0 == 0 || size.1 == 0 || size.0 > MAX_DIMENSION || size.1 > MAX_DIMENSION {
+        return Err(format!(
+            "Target size {}x{} must be between 1 and {} pixels per side",
+            size.0, size.1, MAX_DIMENSION
+        ));
+    }
+    Ok(size)
+}
+
+/// Drop transparency by blending the image over a white background
+fn flatten(image: &DynamicImage) -> RgbImage {
+    if !image.has_alpha() {
+        return image.to_rgb8();
+    }
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16) + 127) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+/// Encode an image next to `path` then move it in place, returns the file size
+fn save_image(
+    image: &DynamicImage,
+    path: &Path,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<u64, String> {
+    let part = PathBuf::from(format!("{}.part", path.display()));
+    let write = || -> Result<(), String> {
+        let mut writer = BufWriter::new(File::create(&part).map_err(|e| e.to_string())?);
+        let result = match format {
+            OutputFormat::Jpeg => flatten(image)
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut writer, quality)),
+            OutputFormat::Webp => {
+                // The pure Rust WebP encoder only writes lossless images
+                let encoder = WebPEncoder::new_lossless(&mut writer);
+                if image.has_alpha() {
+                    image.to_rgba8().write_with_encoder(encoder)
+                } else {
+                    image.to_rgb8().write_with_encoder(encoder)
+                }
+            }
+            OutputFormat::Png => match image {
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                    DynamicImage::ImageRgba16(image.to_rgba16())
+                        .write_to(&mut writer, ImageFormat::Png)
+                }
+                _ => image.write_to(&mut writer, ImageFormat::Png),
+            },
+            OutputFormat::Gif | OutputFormat::Bmp if image.has_alpha() => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+                    .write_to(&mut writer, format.image_format())
+            }
+            OutputFormat::Gif | OutputFormat::Bmp => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_to(&mut writer, format.image_format()),
+        };
+        result.map_err(|e| e.to_string())?;
+        writer.flush().map_err(|e| e.to_string())
+    };
+
+    if let Err(e) = write().and_then(|_| fs::rename(&part, path).map_err(|e| e.to_string())) {
+        let _ = fs::remove_file(&part);
+        return Err(format!("Failed to write {}: {}", path.display(), e));
+    }
+    fs::metadata(path)
+        .map(|meta| meta.len())
+        .map_err(|e| e.to_string())
+}
+
+fn invalid_params(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+}
+
+#[tool_router(router = tool_router_image, vis = "pub")]
+impl DiveDefaultService {
+    /// Decode an image, apply `edit` and write the result
+    ///
+    /// The output format is `format`, else the one of the output extension,
+    /// else the one of the input. EXIF metadata is not carried over, the
+    /// result says when the input had some. Animated images are refused.
+    async fn edit_image(
+        &self,
+        peer: &Peer<RoleServer>,
+        path: String,
+        output: Option<String>,
+        format: Option<OutputFormat>,
+        quality: Option<u8>,
+        edit: impl FnOnce(DynamicImage) -> Result<DynamicImage, String> + Send + 'static,
+    ) -> Result<CallToolResult, McpError> {
+        let input = Self::normalize_path(&path);
+        let output = output
+            .map(|output| Self::normalize_path(&output))
+            .unwrap_or_else(|| input.clone());
+        self.check_paths_permission_with_elicitation(
+            &[(input.clone(), "read"), (output.clone(), "write")],
+            peer,
+        )
+        .await?;
+
+        let quality = quality.unwrap_or(DEFAULT_QUALITY);
+        if !(1..=100).contains(&quality) {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                "quality must be between 1 and 100".to_string(),
+                None,
+            ));
+        }
+
+        let work = move || -> Result<ImageOutput, String> {
+            let (image, input_format) = open_image(Path::new(&input))?;
+            let format = output_format(format, Path::new(&output), input_format)?;
+            let metadata_dropped = !read_exif(Path::new(&input)).is_empty();
+            let image = edit(image)?;
+            let size = save_image(&image, Path::new(&output), format, quality)?;
+            Ok(ImageOutput {
+                path: output,
+                format: format.as_str().to_string(),
+                width: image.width(),
+                height: image.height(),
+                size,
+                metadata_dropped,
+            })
+        };
+        let result = run_blocking(move || work().map_err(invalid_params)).await?;
+        structured_result(&result)
+    }
+
+    #[tool(
+        description = "Read the format, dimensions, color type and EXIF metadata of an image (PNG, JPEG, WebP, GIF, BMP)",
+        output_schema = cached_schema_for_type::<ImageInfoOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn image_info(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ImageInfoParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let path = Self::normalize_path(&params.path);
+        let info =
+            run_blocking(move || image_info(Path::new(&path)).map_err(invalid_params)).await?;
+        structured_result(&info)
+    }
+
+    #[tool(
+        description = "Resize an image. With only a width or a height the aspect ratio is kept, with both the image fits inside them unless mode is fill or exact. Writes to output, or overwrites the image. EXIF metadata is not kept",
+        output_schema = cached_schema_for_type::<ImageOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn resize_image(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ResizeImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (width, height) = (params.width, params.height);
+        let mode = params.mode.unwrap_or_default();
+        self.edit_image(
+            &peer,
+            params.path,
+            params.output,
+            None,
+            params.quality,
+            move |image| {
+                let (width, height) = target_size((image.width(), image.height()), width, height)?;
+                Ok(match mode {
+                    ResizeMode::Fit => image.resize(width, height, FilterType::Lanczos3),
+                    ResizeMode::Fill => image.resize_to_fill(width, height, FilterType::Lanczos3),
+                    ResizeMode::Exact => image.resize_exact(width, height, FilterType::Lanczos3),
+                })
+            },
+        )
+        .await
+    }
+
+    #[tool(
+        description = "Crop an image to the rectangle at x, y of the given width and height. Writes to output, or overwrites the image. EXIF metadata is not kept",
+        output_schema = cached_schema_for_type::<ImageOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn crop_image(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<CropImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let (x, y, width, height) = (params.x, params.y, params.width, params.height);
+        self.edit_image(
+            &peer,
+            params.path,
+            params.output,
+            None,
+            params.quality,
+            move |image| {
+                let fits = width > 0
+                    && height > 0
+                    && x.checked_add(width)
+                        .is_some_and(|right| right <= image.width())
+                    && y.checked_add(height)
+                        .is_some_and(|bottom| bottom <= image.height());
+                if !fits {
+                    return Err(format!(
+                        "Crop area {}x{} at {},{} is outside the {}x{} image",
+                        width,
+                        height,
+                        x,
+                        y,
+                        image.width(),
+                        image.height()
+                    ));
+                }
+                Ok(image.crop_imm(x, y, width, height))
+            },
+        )
+        .await
+    }
+
+    #[tool(
+        description = "Rotate an image clockwise by 90, 180 or 270 degrees and optionally flip it. Writes to output, or overwrites the image. EXIF metadata is not kept",
+        output_schema = cached_schema_for_type::<ImageOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn rotate_image(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<RotateImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let degrees = params.degrees.rem_euclid(360);
+        if degrees % 90 != 0 {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!(
+                    "Rotation must be a multiple of 90 degrees, got {}",
+                    params.degrees
+                ),
+                None,
+            ));
+        }
+        let flip = params.flip;
+        self.edit_image(
+            &peer,
+            params.path,
+            params.output,
+            None,
+            params.quality,
+            move |image| {
+                let image = match degrees {
+                    90 => image.rotate90(),
+                    180 => image.rotate180(),
+                    270 => image.rotate270(),
+                    _ => image,
+                };
+                Ok(match flip {
+                    Some(Flip::Horizontal) => image.fliph(),
+                    Some(Flip::Vertical) => image.flipv(),
+                    None => image,
+                })
+            },
+        )
+        .await
+    }
+
+    #[tool(
+        description = "Convert an image to PNG, JPEG or WebP (also GIF or BMP). The format follows the output extension unless given. Transparency is flattened on white for JPEG, WebP output is lossless",
+        output_schema = cached_schema_for_type::<ImageOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn convert_image(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ConvertImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.edit_image(
+            &peer,
+            params.path,
+            Some(params.output),
+            params.format,
+            params.quality,
+            Ok,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_sizes() {
+        assert_eq!(target_size((1920, 1080), Some(320), None), Ok((320, 180)));
+        assert_eq!(target_size((1920, 1080), None, Some(90)), Ok((160, 90)));
+        assert_eq!(target_size((3, 1000), Some(1), None), Ok((1, 333)));
+        assert_eq!(target_size((1000, 1), Some(10), None), Ok((10, 1)));
+        assert!(target_size((100, 100), None, None).is_err());
+        assert!(target_size((100, 100), Some(0), Some(10)).is_err());
+        assert!(target_size((100, 100), Some(MAX_DIMENSION + 1), None).is_err());
+
+        let mut rgba = image::RgbaImage::new(2, 1);
+        rgba.put_pixel(0, 0, image::Rgba([0, 0, 0, 0]));
+        rgba.put_pixel(1, 0, image::Rgba([255, 0, 0, 255]));
+        let flat = flatten(&DynamicImage::ImageRgba8(rgba));
+        assert_eq!(flat.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(flat.get_pixel(1, 0).0, [255, 0, 0]);
+
+        assert_eq!(
+            serde_json::from_str::<OutputFormat>("\"jpg\"").unwrap(),
+            OutputFormat::Jpeg
+        );
+
+        let png = ImageFormat::Png;
+        let format = |format, output: &str| output_format(format, Path::new(output), png);
+        assert_eq!(format(None, "a/b.JPG"), Ok(OutputFormat::Jpeg));
+        assert_eq!(format(None, "a/b"), Ok(OutputFormat::Png));
+        assert_eq!(
+            format(Some(OutputFormat::Webp), "a/b.tiff"),
+            Ok(OutputFormat::Webp)
+        );
+        assert!(format(None, "a/b.tiff").unwrap_err().contains(".tiff"));
+        assert!(format(None, "a/b.txt").is_err());
+        assert!(output_format(None, Path::new("b"), ImageFormat::Tiff).is_err());
+    }
+
+    #[test]
+    fn test_open_animated() {
+        let dir =
+            std::env::temp_dir().join(format!("dive-image-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let write_gif = |name: &str, frames: u8| {
+            let path = dir.join(name);
+            let mut encoder = image::codecs::gif::GifEncoder::new(File::create(&path).unwrap());
+            for i in 0..frames {
+                let frame = image::RgbaImage::from_pixel(2, 2, image::Rgba([i * 100, 0, 0, 255]));
+                encoder.encode_frame(image::Frame::new(frame)).unwrap();
+            }
+            path
+        };
+        let still = write_gif("still.gif", 1);
+        let animated = write_gif("animated.gif", 2);
+
+        let (image, format) = open_image(&still).unwrap();
+        assert_eq!((image.width(), format), (2, ImageFormat::Gif));
+        assert!(open_image(&animated).unwrap_err().contains("animated"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::service::DiveDefaultService;
+use rmcp::{
+    Peer,
+    model::{LoggingLevel, LoggingMessageNotificationParam},
+    service::RoleServer,
+};
+use serde_json::Value;
+use std::sync::RwLock;
+
+/// Level used until the client sends `logging/setLevel`
+const DEFAULT_LEVEL: LoggingLevel = LoggingLevel::Warning;
+
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// MCP log notifications of one session
+pub struct McpLogger {
+    level: RwLock<LoggingLevel>,
+    peer: RwLock<Option<Peer<RoleServer>>>,
+}
+
+impl Default for McpLogger {
+    fn default() -> Self {
+        Self {
+            level: RwLock::new(DEFAULT_LEVEL),
+            peer: RwLock::new(None),
+        }
+    }
+}
+
+impl McpLogger {
+    /// Send notifications to this peer from now on
+    pub fn attach(&self, peer: Peer<RoleServer>) {
+        if let Ok(mut current) = self.peer.write() {
+            *current = Some(peer);
+        }
+    }
+
+    pub fn set_level(&self, level: LoggingLevel) {
+        if let Ok(mut current) = self.level.write() {
+            *current = level;
+        }
+    }
+
+    fn enabled(&self, level: LoggingLevel) -> bool {
+        self.level
+            .read()
+            .map(|current| severity(level) >= severity(*current))
+            .unwrap_or(false)
+    }
+
+    /// Log to the process log and, above the session level, to the client
+    pub async fn log(&self, level: LoggingLevel, logger: &str, data: Value) {
+        match level {
+            LoggingLevel::Debug => tracing::debug!(logger, "{}", data),
+            LoggingLevel::Info | LoggingLevel::Notice => tracing::info!(logger, "{}", data),
+            LoggingLevel::Warning => tracing::warn!(logger, "{}", data),
+            _ => tracing::error!(logger, "{}", data),
+        }
+
+        if !self.enabled(level) {
+            return;
+        }
+        let peer = self.peer.read().ok().and_then(|peer| peer.clone());
+        if let Some(peer) = peer {
+            let _ = peer
+                .notify_logging_message(LoggingMessageNotificationParam {
+                    level,
+                    logger: Some(logger.to_string()),
+                    data,
+                })
+                .await;
+        }
+    }
+}
+
+impl DiveDefaultService {
+    /// Send a log message to the client of this session
+    pub(super) async fn log(&self, level: LoggingLevel, logger: &str, data: Value) {
+        self.logger.log(level, logger, data).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_threshold() {
+        let logger = McpLogger::default();
+        assert!(!logger.enabled(LoggingLevel::Info));
+        assert!(logger.enabled(LoggingLevel::Error));
+
+        logger.set_level(LoggingLevel::Debug);
+        assert!(logger.enabled(LoggingLevel::Info));
+    }
+}
@@ -0,0 +1,640 @@
+use crate::service::cache::now_secs;
+use crate::service::{DiveDefaultService, run_blocking};
+use rmcp::{
+    ErrorData as McpError,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content},
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Namespace used when none is given
+const DEFAULT_NAMESPACE: &str = "default";
+/// Maximum length of a namespace name
+const MAX_NAMESPACE_LENGTH: usize = 64;
+/// Maximum length of a key
+const MAX_KEY_LENGTH: usize = 256;
+/// Maximum size of a value, in bytes
+const MAX_VALUE_SIZE: usize = 64 * 1024;
+/// Maximum number of tags of an entry
+const MAX_TAGS: usize = 16;
+/// Maximum length of a tag
+const MAX_TAG_LENGTH: usize = 64;
+/// Maximum number of entries of a namespace
+const MAX_ENTRIES: usize = 1000;
+/// Maximum size of a namespace file, in bytes
+const MAX_NAMESPACE_SIZE: usize = 1024 * 1024;
+/// File locked by every process updating the store, namespaces cannot start with '.'
+const LOCK_FILE: &str = ".lock";
+/// Characters of a value shown in listings and search results
+const PREVIEW_LENGTH: usize = 120;
+/// Search results returned when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MemoryEntry {
+    value: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+type Namespace = BTreeMap<String, MemoryEntry>;
+
+fn invalid_params(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+}
+
+fn internal_error(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, None)
+}
+
+/// Namespaces become file names, so only a safe subset of characters is accepted
+fn validate_namespace(namespace: &str) -> Result<(), String> {
+    if namespace.is_empty() || namespace.len() > MAX_NAMESPACE_LENGTH {
+        return Err(format!(
+            "Namespace must be 1 to {} characters long",
+            MAX_NAMESPACE_LENGTH
+        ));
+    }
+    if namespace.starts_with('.')
+        || !namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(format!(
+            "Invalid namespace {:?}: use letters, digits, '-', '_' and '.', not starting with '.'",
+            namespace
+        ));
+    }
+    Ok(())
+}
+
+fn validate_entry(key: &str, value: &str, tags: &[String]) -> Result<(), String> {
+    if key.is_empty() || key.chars().count() > MAX_KEY_LENGTH {
+        return Err(format!(
+            "Key must be 1 to {} characters long",
+            MAX_KEY_LENGTH
+        ));
+    }
+    if key.chars().any(char::is_control) {
+        return Err("Key must not contain control characters".to_string());
+    }
+    if value.len() > MAX_VALUE_SIZE {
+        return Err(format!(
+            "Value is {} bytes, the limit is {} bytes",
+            value.len(),
+            MAX_VALUE_SIZE
+        ));
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(format!("At most {} tags are allowed", MAX_TAGS));
+    }
+    if tags
+        .iter()
+        .any(|tag| tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH)
+    {
+        return Err(format!(
+            "Tags must be 1 to {} characters long",
+            MAX_TAG_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn preview(value: &str) -> String {
+    let mut preview: String = value
+        .chars()
+        .take(PREVIEW_LENGTH)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if value.chars().count() > PREVIEW_LENGTH {
+        preview.push('…');
+    }
+    preview
+}
+
+fn format_time(seconds: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Whether every whitespace separated term of the query appears in the entry
+fn matches_query(key: &str, entry: &MemoryEntry, terms: &[String]) -> bool {
+    let haystack = format!("{}\n{}\n{}", key, entry.value, entry.tags.join(" ")).to_lowercase();
+    terms.iter().all(|term| haystack.contains(term.as_str()))
+}
+
+/// Parse the content of a namespace file, a missing file is an empty namespace
+fn parse_namespace(
+    namespace: &str,
+    path: &Path,
+    content: std::io::Result<Vec<u8>>,
+) -> Result<Namespace, McpError> {
+    match content {
+        Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+            internal_error(format!(
+                "Memory namespace {} is corrupted ({}): {}",
+                namespace,
+                path.display(),
+                e
+            ))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Namespace::new()),
+        Err(e) => Err(internal_error(format!(
+            "Failed to read memory namespace {}: {}",
+            namespace, e
+        ))),
+    }
+}
+
+/// Write a namespace through a temporary file, removing it once empty
+fn write_namespace(namespace: &str, path: &Path, entries: &Namespace) -> Result<(), McpError> {
+    if entries.is_empty() {
+        let _ = fs::remove_file(path);
+        return Ok(());
+    }
+
+    let content = serde_json::to_vec_pretty(entries)
+        .map_err(|e| internal_error(format!("Failed to serialize memory: {}", e)))?;
+    if content.len() > MAX_NAMESPACE_SIZE {
+        return Err(invalid_params(format!(
+            "Memory namespace {} would grow to {} bytes, the limit is {} bytes. Delete entries first",
+            namespace,
+            content.len(),
+            MAX_NAMESPACE_SIZE
+        )));
+    }
+
+    let temp = path.with_extension(format!("json.{}.tmp", uuid::Uuid::new_v4().simple()));
+    let written = fs::write(&temp, &content).and_then(|_| fs::rename(&temp, path));
+    written.map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        internal_error(format!(
+            "Failed to write memory namespace {}: {}",
+            namespace, e
+        ))
+    })
+}
+
+/// Persistent notes of the agents, one JSON file per namespace
+///
+/// Several processes may share the directory, updates hold an advisory lock
+/// on a file of the store while they read, change and write a namespace.
+pub struct MemoryStore {
+    dir: Option<PathBuf>,
+    /// Serializes the updates of the sessions of this process
+    lock: Mutex<()>,
+}
+
+impl MemoryStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn dir(&self) -> Result<&PathBuf, McpError> {
+        self.dir
+            .as_ref()
+            .ok_or_else(|| internal_error("The memory store is disabled".to_string()))
+    }
+
+    fn namespace_path(&self, namespace: &str) -> Result<PathBuf, McpError> {
+        validate_namespace(namespace).map_err(invalid_params)?;
+        Ok(self.dir()?.join(format!("{}.json", namespace)))
+    }
+
+    async fn load(&self, namespace: &str) -> Result<Namespace, McpError> {
+        let path = self.namespace_path(namespace)?;
+        parse_namespace(namespace, &path, tokio::fs::read(&path).await)
+    }
+
+    /// Read a namespace, change it with `edit` and write it back, while no
+    /// other session or process updates the store
+    async fn update<T: Send + 'static>(
+        &self,
+        namespace: &str,
+        edit: impl FnOnce(&mut Namespace) -> Result<T, McpError> + Send + 'static,
+    ) -> Result<T, McpError> {
+        let path = self.namespace_path(namespace)?;
+        let dir = self.dir()?.clone();
+        let namespace = namespace.to_string();
+
+        let _guard = self.lock.lock().await;
+        run_blocking(move || {
+            let lock = fs::create_dir_all(&dir)
+                .and_then(|_| {
+                    fs::OpenOptions::new()
+                        .create(true)
+                        .truncate(false)
+                        .write(true)
+                        .open(dir.join(LOCK_FILE))
+                })
+                .and_then(|file| file.lock().map(|_| file))
+                .map_err(|e| internal_error(format!("Failed to lock the memory store: {}", e)))?;
+
+            let mut entries = parse_namespace(&namespace, &path, fs::read(&path))?;
+            let result = edit(&mut entries)?;
+            write_namespace(&namespace, &path, &entries)?;
+            drop(lock);
+            Ok(result)
+        })
+        .await
+    }
+
+    /// Names of the namespaces holding entries
+    async fn namespaces(&self) -> Result<Vec<String>, McpError> {
+        let mut namespaces = Vec::new();
+        let Ok(mut entries) = tokio::fs::read_dir(self.dir()?).await else {
+            return Ok(namespaces);
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(namespace) = file_name.strip_suffix(".json")
+                && validate_namespace(namespace).is_ok()
+            {
+                namespaces.push(namespace.to_string());
+            }
+        }
+        namespaces.sort();
+        Ok(namespaces)
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct MemorySetParams {
+    /// Namespace, e.g. a project or conversation name (default "default")
+    #[serde(default)]
+    namespace: Option<String>,
+    /// Key of the entry, replaced if it exists
+    key: String,
+    /// Content to remember (at most 64 KiB)
+    value: String,
+    /// Tags used by search
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct MemoryKeyParams {
+    /// Namespace of the entry (default "default")
+    #[serde(default)]
+    namespace: Option<String>,
+    /// Key of the entry
+    key: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct MemoryListParams {
+    /// Namespace to list the entries of. Without it the namespaces are listed
+    #[serde(default)]
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct MemorySearchParams {
+    /// Words that must all appear in the key, value or tags (case insensitive)
+    query: String,
+    /// Only search this namespace. Without it every namespace is searched
+    #[serde(default)]
+    namespace: Option<String>,
+    /// Maximum number of results (default 20)
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+fn namespace_or_default(namespace: Option<String>) -> String {
+    namespace.unwrap_or_else(|| DEFAULT_NAMESPACE.to_string())
+}
+
+fn json_result(result: serde_json::Value) -> CallToolResult {
+    CallToolResult::success(vec![Content::text(
+        serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+    )])
+}
+
+#[tool_router(router = tool_router_memory, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Remember a value under a key in a persistent namespace, kept across chats. Use it for user preferences and task state. Replaces any existing value of the key",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn memory_set(
+        &self,
+        Parameters(params): Parameters<MemorySetParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_read_only("write")?;
+        let namespace = namespace_or_default(params.namespace);
+        validate_entry(&params.key, &params.value, &params.tags).map_err(invalid_params)?;
+
+        let (key, name) = (params.key.clone(), namespace.clone());
+        let (created, count) = self
+            .memory
+            .update(&namespace, move |entries| {
+                let now = now_secs();
+                let created = match entries.get_mut(&key) {
+                    Some(entry) => {
+                        entry.value = params.value;
+                        entry.tags = params.tags;
+                        entry.updated_at = now;
+                        false
+                    }
+                    None => {
+                        if entries.len() >= MAX_ENTRIES {
+                            return Err(invalid_params(format!(
+                                "Memory namespace {} already holds {} entries. Delete entries first",
+                                name, MAX_ENTRIES
+                            )));
+                        }
+                        entries.insert(
+                            key,
+                            MemoryEntry {
+                                value: params.value,
+                                tags: params.tags,
+                                created_at: now,
+                                updated_at: now,
+                            },
+                        );
+                        true
+                    }
+                };
+                Ok((created, entries.len()))
+            })
+            .await?;
+
+        Ok(json_result(serde_json::json!({
+            "namespace": namespace,
+            "key": params.key,
+            "created": created,
+            "entries": count,
+        })))
+    }
+
+    #[tool(
+        description = "Recall the value stored under a key of a memory namespace",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn memory_get(
+        &self,
+        Parameters(params): Parameters<MemoryKeyParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let namespace = namespace_or_default(params.namespace);
+        let entries = self.memory.load(&namespace).await?;
+        let entry = entries.get(&params.key).ok_or_else(|| {
+            invalid_params(format!(
+                "Nothing remembered under {:?} in namespace {}",
+                params.key, namespace
+            ))
+        })?;
+
+        Ok(json_result(serde_json::json!({
+            "namespace": namespace,
+            "key": params.key,
+            "value": entry.value,
+            "tags": entry.tags,
+            "createdAt": format_time(entry.created_at),
+            "updatedAt": format_time(entry.updated_at),
+        })))
+    }
+
+    #[tool(
+        description = "List the memory namespaces, or the keys of one namespace with a preview of their values",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn memory_list(
+        &self,
+        Parameters(params): Parameters<MemoryListParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(namespace) = params.namespace else {
+            let mut namespaces = Vec::new();
+            for namespace in self.memory.namespaces().await? {
+                let entries = self.memory.load(&namespace).await?;
+                namespaces.push(serde_json::json!({
+                    "namespace": namespace,
+                    "entries": entries.len(),
+                }));
+            }
+            return Ok(json_result(serde_json::json!({ "namespaces": namespaces })));
+        };
+
+        let entries = self.memory.load(&namespace).await?;
+        let keys = entries
+            .iter()
+            .map(|(key, entry)| {
+                serde_json::json!({
+                    "key": key,
+                    "tags": entry.tags,
+                    "size": entry.value.len(),
+                    "updatedAt": format_time(entry.updated_at),
+                    "preview": preview(&entry.value),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json_result(serde_json::json!({
+            "namespace": namespace,
+            "entries": keys,
+        })))
+    }
+
+    #[tool(
+        description = "Search remembered entries whose key, value or tags contain all the words of the query",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn memory_search(
+        &self,
+        Parameters(params): Parameters<MemorySearchParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let terms = params
+            .query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Err(invalid_params("The query is empty".to_string()));
+        }
+        let namespaces = match params.namespace {
+            Some(namespace) => vec![namespace],
+            None => self.memory.namespaces().await?,
+        };
+        let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).max(1);
+
+        let mut results = Vec::new();
+        let mut total = 0;
+        for namespace in namespaces {
+            let entries = self.memory.load(&namespace).await?;
+            for (key, entry) in &entries {
+                if !matches_query(key, entry, &terms) {
+                    continue;
+                }
+                total += 1;
+                if results.len() < limit {
+                    results.push(serde_json::json!({
+                        "namespace": namespace,
+                        "key": key,
+                        "tags": entry.tags,
+                        "updatedAt": format_time(entry.updated_at),
+                        "preview": preview(&entry.value),
+                    }));
+                }
+            }
+        }
+
+        Ok(json_result(serde_json::json!({
+            "query": params.query,
+            "total": total,
+            "results": results,
+        })))
+    }
+
+    #[tool(
+        description = "Forget the entry stored under a key of a memory namespace",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn memory_delete(
+        &self,
+        Parameters(params): Parameters<MemoryKeyParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_read_only("delete")?;
+        let namespace = namespace_or_default(params.namespace);
+
+        let key = params.key.clone();
+        let deleted = self
+            .memory
+            .update(&namespace, move |entries| {
+                Ok(entries.remove(&key).is_some())
+            })
+            .await?;
+
+        Ok(json_result(serde_json::json!({
+            "namespace": namespace,
+            "key": params.key,
+            "deleted": deleted,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_validation() {
+        assert!(validate_namespace("project-x_1.2").is_ok());
+        assert!(validate_namespace("../etc").is_err());
+        assert!(validate_namespace(".hidden").is_err());
+        assert!(validate_namespace("a/b").is_err());
+        assert!(validate_namespace("").is_err());
+
+        assert!(validate_entry("theme", "dark", &[]).is_ok());
+        assert!(validate_entry("", "dark", &[]).is_err());
+        assert!(validate_entry("k", &"x".repeat(MAX_VALUE_SIZE + 1), &[]).is_err());
+        assert!(validate_entry("k", "v", &["x".repeat(MAX_TAG_LENGTH + 1)]).is_err());
+        assert!(validate_entry("k", "v", &[String::new()]).is_err());
+
+        let entry = MemoryEntry {
+            value: "Prefers dark THEME".to_string(),
+            tags: vec!["ui".to_string()],
+            created_at: 0,
+            updated_at: 0,
+        };
+        let terms = |query: &str| {
+            query
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        };
+        assert!(matches_query("prefs", &entry, &terms("dark theme")));
+        assert!(matches_query("prefs", &entry, &terms("UI prefs")));
+        assert!(!matches_query("prefs", &entry, &terms("light")));
+    }
+    #[tokio::test]
+    async fn test_memory_store() {
+        let dir =
+            std::env::temp_dir().join(format!("dive-memory-{}", uuid::Uuid::new_v4().simple()));
+        let store = MemoryStore::new(Some(dir.clone()));
+        let entry = |value: &str| MemoryEntry {
+            value: value.to_string(),
+            tags: vec!["ui".to_string()],
+            created_at: 1,
+            updated_at: 2,
+        };
+
+        assert!(store.load("notes").await.unwrap().is_empty());
+        let count = store
+            .update("notes", move |entries| {
+                entries.insert("theme".to_string(), entry("dark"));
+                entries.insert("font".to_string(), entry("mono"));
+                Ok(entries.len())
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let entries = store.load("notes").await.unwrap();
+        assert_eq!(entries["theme"].value, "dark");
+        assert_eq!(entries["font"].tags, ["ui"]);
+        assert_eq!(entries["font"].updated_at, 2);
+        assert_eq!(store.namespaces().await.unwrap(), ["notes"]);
+
+        // A failed edit leaves the namespace as it was
+        let failed = store
+            .update("notes", |entries| {
+                entries.clear();
+                Err::<(), _>(invalid_params("no".to_string()))
+            })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(store.load("notes").await.unwrap().len(), 2);
+
+        // Emptied namespaces are removed, no temporary file is left behind
+        store
+            .update("notes", |entries| {
+                entries.clear();
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert!(store.namespaces().await.unwrap().is_empty());
+        let files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, [LOCK_FILE]);
+
+        assert!(MemoryStore::new(None).load("notes").await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
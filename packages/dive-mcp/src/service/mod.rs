@@ -1,48 +1,336 @@
-use rmcp::{ServerHandler, handler::server::tool::ToolRouter, model::*, tool_handler, tool_router};
+use rmcp::{
+    RoleServer, ServerHandler,
+    handler::server::tool::{ToolCallContext, ToolRouter},
+    model::*,
+    service::{NotificationContext, RequestContext},
+    tool_router,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio_util::sync::{CancellationToken, DropGuard};
 
+mod archive;
+mod cache;
+mod command;
+mod data;
+mod download;
 mod echo;
 mod fetch;
 mod fs;
+mod git;
+mod http;
+mod image;
+mod logging;
+mod memory;
+mod notebook;
+mod outline;
+mod patch;
+mod permission;
+mod policy;
+mod prompts;
+mod python;
+mod replace;
+mod skills;
+mod sqlite;
+mod terminal;
+
+use cache::{DEFAULT_CACHE_MAX_SIZE, HttpCache};
+use command::CommandConfig;
+use http::{HttpClient, HttpConfig};
+use logging::McpLogger;
+use memory::MemoryStore;
+use permission::{PermissionConfig, SessionGrants, elicitation_timeout};
+use policy::PolicyConfig;
+use prompts::PromptLibrary;
+use python::PythonWorkspace;
+use skills::SkillLibrary;
+use terminal::TerminalManager;
+
+/// Build a result carrying `output` as structured content, with the same JSON
+/// as text for clients that do not read structured content
+fn structured_result<T: Serialize>(output: &T) -> Result<CallToolResult, ErrorData> {
+    let value = serde_json::to_value(output).map_err(|e| {
+        ErrorData::new(
+            ErrorCode::INTERNAL_ERROR,
+            format!("Failed to serialize result: {}", e),
+            None,
+        )
+    })?;
+    let text = serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string());
+    let mut result = CallToolResult::success(vec![Content::text(text)]);
+    result.structured_content = Some(value);
+    Ok(result)
+}
+
+/// Run blocking file, database or repository work off the async runtime
+async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, ErrorData> + Send + 'static,
+) -> Result<T, ErrorData> {
+    tokio::task::spawn_blocking(work).await.map_err(|e| {
+        ErrorData::new(
+            ErrorCode::INTERNAL_ERROR,
+            format!("Background task failed: {}", e),
+            None,
+        )
+    })?
+}
+
+/// Groups of tools that can be mounted with `--tools`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Toolset {
+    Echo,
+    Fetch,
+    Download,
+    Fs,
+    Command,
+    Terminal,
+    Python,
+    Skills,
+    Archive,
+    Sqlite,
+    Git,
+    Memory,
+    Image,
+    Notebook,
+    Data,
+}
+
+impl Toolset {
+    pub const ALL: &[Toolset] = &[
+        Toolset::Echo,
+        Toolset::Fetch,
+        Toolset::Download,
+        Toolset::Fs,
+        Toolset::Command,
+        Toolset::Terminal,
+        Toolset::Python,
+        Toolset::Skills,
+        Toolset::Archive,
+        Toolset::Sqlite,
+        Toolset::Git,
+        Toolset::Memory,
+        Toolset::Image,
+        Toolset::Notebook,
+        Toolset::Data,
+    ];
+}
+
+/// Options the service is started with
+pub struct ServiceOptions {
+    /// Config file holding the persisted allow list and http settings
+    pub config_path: PathBuf,
+    /// Toolsets to mount
+    pub toolsets: Vec<Toolset>,
+    /// Directories allowed for this process only, never persisted
+    pub allow_dirs: Vec<String>,
+    /// Refuse every operation that modifies files
+    pub read_only: bool,
+    /// Directory holding the prompt templates, prompts are disabled when `None`
+    pub prompts_dir: Option<PathBuf>,
+    /// Directory of installed skills, skills are disabled when `None`
+    pub skills_dir: Option<PathBuf>,
+    /// Directory of the memory store, memory tools fail when `None`
+    pub memory_dir: Option<PathBuf>,
+    /// Policy directories where reads are approved without asking
+    pub policy_read_dirs: Vec<String>,
+    /// Policy directories where everything is approved without asking
+    pub policy_scratch_dirs: Vec<String>,
+    /// Never ask the user, decide every request with the policy
+    pub non_interactive: bool,
+    /// Seconds the user has to answer a permission request
+    pub elicitation_timeout: Option<u64>,
+}
+
+/// Default prompt template directory (`~/.dive/prompts`)
+pub fn default_prompts_dir() -> Option<PathBuf> {
+    homedir::my_home()
+        .ok()
+        .flatten()
+        .map(|home| home.join(".dive/prompts"))
+}
+
+/// Default skill directory (`~/.dive/skills`)
+pub fn default_skills_dir() -> Option<PathBuf> {
+    homedir::my_home()
+        .ok()
+        .flatten()
+        .map(|home| home.join(".dive/skills"))
+}
+
+/// Default memory store directory (`~/.dive/mcp/memory`)
+pub fn default_memory_dir() -> Option<PathBuf> {
+    homedir::my_home()
+        .ok()
+        .flatten()
+        .map(|home| home.join(".dive/mcp/memory"))
+}
+
+/// Default config file location (`~/.dive/mcp/fs.json`)
+pub fn default_config_path() -> Result<PathBuf, String> {
+    homedir::my_home()
+        .ok()
+        .flatten()
+        .map(|home| home.join(".dive/mcp/fs.json"))
+        .ok_or_else(|| {
+            "Cannot determine the home directory, pass the config file with --config".to_string()
+        })
+}
 
 #[derive(Clone)]
 pub struct DiveDefaultService {
-    http_client: reqwest::Client,
+    http_client: HttpClient,
+    http_cache: Option<HttpCache>,
     tool_router: ToolRouter<Self>,
+    config_path: Arc<PathBuf>,
     allowed_dirs: Arc<RwLock<Vec<String>>>,
+    extra_allowed_dirs: Arc<Vec<String>>,
+    allowed_commands: Arc<RwLock<Vec<String>>>,
+    allowed_tools: Arc<RwLock<Vec<String>>>,
+    read_only: bool,
+    policy: Arc<PolicyConfig>,
+    /// Time the user has to answer a permission request
+    elicitation_timeout: Duration,
+    /// Permissions granted for this MCP session only
+    session_grants: Arc<SessionGrants>,
+    /// Terminal sessions, owned by a single MCP session
+    terminals: Arc<TerminalManager>,
+    /// Python virtualenv and scratch dir, owned by a single MCP session
+    python: Arc<PythonWorkspace>,
+    prompts: Arc<PromptLibrary>,
+    skills: Arc<SkillLibrary>,
+    memory: Arc<MemoryStore>,
+    /// Log notifications, owned by a single MCP session
+    logger: Arc<McpLogger>,
+    /// Cancelled when the MCP session ends, stops its background tasks
+    session: SessionToken,
+}
+
+/// Token cancelled once every clone of a session's service is dropped
+#[derive(Clone)]
+struct SessionToken {
+    token: CancellationToken,
+    _guard: Arc<DropGuard>,
+}
+
+impl Default for SessionToken {
+    fn default() -> Self {
+        let token = CancellationToken::new();
+        Self {
+            _guard: Arc::new(token.clone().drop_guard()),
+            token,
+        }
+    }
 }
 
 #[tool_router]
 impl DiveDefaultService {
-    pub fn new() -> Self {
-        let allowed_dirs = Self::load_allowed_dirs().unwrap_or_default();
-        Self {
-            http_client: reqwest::Client::new(),
-            tool_router: Self::tool_router_echo()
-                + Self::tool_router_fetch()
-                + Self::tool_router_fs(),
-            allowed_dirs: Arc::new(RwLock::new(allowed_dirs)),
+    /// Create the service, failing on a malformed config file section or
+    /// unusable HTTP settings rather than silently running without them
+    pub fn new(options: ServiceOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let allowed_dirs = Self::load_allowed_dirs(&options.config_path).unwrap_or_default();
+        let http_config =
+            Self::load_config_section::<HttpConfig>(&options.config_path, "http")?.merge_env();
+        let command_config =
+            Self::load_config_section::<CommandConfig>(&options.config_path, "command")?;
+        let policy = Self::load_config_section::<PolicyConfig>(&options.config_path, "policy")?
+            .merge(
+                &options.policy_read_dirs,
+                &options.policy_scratch_dirs,
+                options.non_interactive,
+            );
+        let permission_config =
+            Self::load_config_section::<PermissionConfig>(&options.config_path, "permission")?;
+        let http_client = HttpClient::new(&http_config)?;
+        let http_cache = homedir::my_home().ok().flatten().map(|home| {
+            HttpCache::new(
+                home.join(".dive/host_cache/fetch"),
+                http_config.cache_max_size.unwrap_or(DEFAULT_CACHE_MAX_SIZE),
+            )
+        });
+
+        let mut tool_router = ToolRouter::new();
+        for toolset in options.toolsets.iter() {
+            tool_router += match toolset {
+                Toolset::Echo => Self::tool_router_echo(),
+                Toolset::Fetch => Self::tool_router_fetch(),
+                Toolset::Download => Self::tool_router_download(),
+                Toolset::Fs => {
+                    Self::tool_router_fs()
+                        + Self::tool_router_patch()
+                        + Self::tool_router_replace()
+                        + Self::tool_router_outline()
+                }
+                Toolset::Command => Self::tool_router_command(),
+                Toolset::Terminal => Self::tool_router_terminal(),
+                Toolset::Python => Self::tool_router_python(),
+                Toolset::Skills => Self::tool_router_skills(),
+                Toolset::Archive => Self::tool_router_archive(),
+                Toolset::Sqlite => Self::tool_router_sqlite(),
+                Toolset::Git => Self::tool_router_git(),
+                Toolset::Memory => Self::tool_router_memory(),
+                Toolset::Image => Self::tool_router_image(),
+                Toolset::Notebook => Self::tool_router_notebook(),
+                Toolset::Data => Self::tool_router_data(),
+            };
         }
+
+        Ok(Self {
+            http_client,
+            http_cache,
+            tool_router,
+            config_path: Arc::new(options.config_path),
+            allowed_dirs: Arc::new(RwLock::new(allowed_dirs)),
+            extra_allowed_dirs: Arc::new(options.allow_dirs),
+            allowed_commands: Arc::new(RwLock::new(command_config.allow_prefix)),
+            allowed_tools: Arc::new(RwLock::new(command_config.allow_tools)),
+            read_only: options.read_only,
+            policy: Arc::new(policy),
+            elicitation_timeout: elicitation_timeout(
+                options.elicitation_timeout,
+                &permission_config,
+            ),
+            session_grants: Arc::new(SessionGrants::default()),
+            terminals: Arc::new(TerminalManager::default()),
+            python: Arc::new(PythonWorkspace::default()),
+            prompts: Arc::new(PromptLibrary::new(options.prompts_dir)),
+            skills: Arc::new(SkillLibrary::new(
+                options
+                    .skills_dir
+                    .filter(|_| options.toolsets.contains(&Toolset::Skills)),
+            )),
+            memory: Arc::new(MemoryStore::new(options.memory_dir)),
+            logger: Arc::new(McpLogger::default()),
+            session: SessionToken::default(),
+        })
     }
 
-    fn get_config_path() -> std::path::PathBuf {
-        homedir::my_home()
-            .ok()
-            .flatten()
-            .unwrap()
-            .join(".dive/mcp/fs.json")
+    /// Create the service for a new MCP session of the HTTP transport
+    ///
+    /// The allow lists and HTTP clients are shared with every other session,
+    /// terminal sessions, the Python workspace, session grants and the log
+    /// level are not.
+    pub fn new_session(&self) -> Self {
+        Self {
+            session_grants: Arc::new(SessionGrants::default()),
+            logger: Arc::new(McpLogger::default()),
+            terminals: Arc::new(TerminalManager::default()),
+            python: Arc::new(PythonWorkspace::default()),
+            session: SessionToken::default(),
+            ..self.clone()
+        }
     }
 
-    fn load_allowed_dirs() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    fn load_allowed_dirs(config_path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         use serde_json::Value;
-        let config_path = Self::get_config_path();
 
         if !config_path.exists() {
             return Ok(Vec::new());
         }
 
-        let content = std::fs::read_to_string(&config_path)?;
+        let content = std::fs::read_to_string(config_path)?;
         let json: Value = serde_json::from_str(&content)?;
 
         let dirs = json
@@ -59,37 +347,231 @@ impl DiveDefaultService {
         Ok(dirs)
     }
 
-    async fn save_allowed_dirs(&self) -> Result<(), Box<dyn std::error::Error>> {
-        use serde_json::json;
-        let config_path = Self::get_config_path();
+    /// Load one section of the config file, falling back to its default
+    fn load_config_section<T: DeserializeOwned + Default>(
+        config_path: &Path,
+        section: &str,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        use serde_json::Value;
+
+        if !config_path.exists() {
+            return Ok(T::default());
+        }
+
+        let content = std::fs::read_to_string(config_path)?;
+        let mut json: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid config file {}: {}", config_path.display(), e))?;
+
+        match json.get_mut(section) {
+            Some(value) => Ok(serde_json::from_value(value.take()).map_err(|e| {
+                format!(
+                    "Invalid \"{}\" section in {}: {}",
+                    section,
+                    config_path.display(),
+                    e
+                )
+            })?),
+            None => Ok(T::default()),
+        }
+    }
+
+    /// Replace one section of the config file, keeping the others
+    ///
+    /// Nothing is written in read-only mode, changes then last for this process only.
+    fn save_config_section(
+        &self,
+        section: &str,
+        value: serde_json::Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use serde_json::{Value, json};
+        if self.read_only {
+            return Ok(());
+        }
+        let config_path = self.config_path.as_path();
 
         // Create parent directory if it doesn't exist
         if let Some(parent) = config_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
+        let mut json = std::fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .filter(|json| json.is_object())
+            .unwrap_or_else(|| json!({}));
+        json[section] = value;
+
+        std::fs::write(config_path, serde_json::to_string_pretty(&json)?)?;
+        Ok(())
+    }
+
+    async fn save_allowed_dirs(&self) -> Result<(), Box<dyn std::error::Error>> {
         let allowed_dirs = self.allowed_dirs.read().await;
-        let json = json!({
-            "fs": {
+        self.save_config_section(
+            "fs",
+            serde_json::json!({
                 "allow_dir": *allowed_dirs
-            }
-        });
+            }),
+        )
+    }
 
-        std::fs::write(&config_path, serde_json::to_string_pretty(&json)?)?;
-        Ok(())
+    async fn save_allowed_commands(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let allowed_commands = self.allowed_commands.read().await;
+        let allowed_tools = self.allowed_tools.read().await;
+        self.save_config_section(
+            "command",
+            serde_json::json!({
+                "allow_prefix": *allowed_commands,
+                "allow_tools": *allowed_tools
+            }),
+        )
     }
 }
 
-#[tool_handler]
 impl ServerHandler for DiveDefaultService {
     fn get_info(&self) -> ServerInfo {
+        let mut capabilities = ServerCapabilities::builder()
+            .enable_logging()
+            .enable_prompts()
+            .enable_prompts_list_changed()
+            .enable_resources()
+            .enable_tools()
+            .enable_tool_list_changed()
+            .build();
+        // The only resources are the installed skills
+        if !self.skills.is_enabled() {
+            capabilities.resources = None;
+        }
         ServerInfo {
             instructions: Some("default mcp server for dive client".into()),
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .enable_tool_list_changed()
-                .build(),
+            capabilities,
             ..Default::default()
         }
     }
+
+    /// Run the tool, dropping it when the client cancels the request
+    ///
+    /// This is the only cancellation path, tools that start processes kill
+    /// them when their future is dropped.
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let name = request.name.clone();
+        let ct = context.ct.clone();
+        let started = Instant::now();
+
+        let tcc = ToolCallContext::new(self, request, context);
+        let result = tokio::select! {
+            result = self.tool_router.call(tcc) => result,
+            _ = ct.cancelled() => {
+                self.log(
+                    LoggingLevel::Info,
+                    "tool",
+                    serde_json::json!({
+                        "tool": name,
+                        "cancelled": true,
+                        "elapsedMs": started.elapsed().as_millis() as u64,
+                    }),
+                )
+                .await;
+                return Err(ErrorData::new(
+                    ErrorCode::INTERNAL_ERROR,
+                    format!("Tool call cancelled: {}", name),
+                    None,
+                ));
+            }
+        };
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match result.as_ref() {
+            Ok(result) if result.is_error == Some(true) => {
+                self.log(
+                    LoggingLevel::Warning,
+                    "tool",
+                    serde_json::json!({ "tool": name, "isError": true, "elapsedMs": elapsed_ms }),
+                )
+                .await
+            }
+            Ok(_) => {
+                self.log(
+                    LoggingLevel::Info,
+                    "tool",
+                    serde_json::json!({ "tool": name, "elapsedMs": elapsed_ms }),
+                )
+                .await
+            }
+            Err(e) => {
+                self.log(
+                    LoggingLevel::Error,
+                    "tool",
+                    serde_json::json!({
+                        "tool": name,
+                        "error": e.message,
+                        "elapsedMs": elapsed_ms,
+                    }),
+                )
+                .await
+            }
+        }
+        result
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.logger.set_level(request.level);
+        Ok(())
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, ErrorData> {
+        Ok(ListPromptsResult::with_all_items(self.prompts.list()))
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, ErrorData> {
+        self.prompts.get(&request.name, request.arguments)
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, ErrorData> {
+        Ok(ListResourcesResult::with_all_items(self.skills.list()))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, ErrorData> {
+        self.skills.read(&request.uri)
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        tracing::info!("client initialized");
+        self.logger.attach(context.peer.clone());
+        let prompts = self.prompts.clone();
+        let ct = self.session.token.clone();
+        tokio::spawn(async move { prompts.watch(context.peer, ct).await });
+    }
 }
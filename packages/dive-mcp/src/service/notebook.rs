@@ -0,0 +1,771 @@
+use crate::service::{DiveDefaultService, structured_result};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::Path;
+use tokio::fs;
+
+/// Characters of output kept per cell when no limit is given
+const DEFAULT_OUTPUT_CHARS: usize = 2000;
+/// Largest notebook file read, in bytes
+const MAX_NOTEBOOK_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum CellType {
+    Code,
+    Markdown,
+    Raw,
+}
+
+impl CellType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CellType::Code => "code",
+            CellType::Markdown => "markdown",
+            CellType::Raw => "raw",
+        }
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ReadNotebookParams {
+    /// Path of the .ipynb file
+    path: String,
+    /// Characters of text output kept per cell (default 2000, 0 hides outputs)
+    max_output_chars: Option<usize>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct InsertCellParams {
+    /// Path of the .ipynb file
+    path: String,
+    /// Source of the new cell
+    source: String,
+    /// Type of the new cell: code (default), markdown or raw
+    cell_type: Option<CellType>,
+    /// Position of the new cell, the cell there and after move down (defaults to the end)
+    index: Option<usize>,
+    /// Insert after the cell with this id instead of at an index
+    after_id: Option<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ReplaceCellParams {
+    /// Path of the .ipynb file
+    path: String,
+    /// Index of the cell, from 0
+    index: Option<usize>,
+    /// Id of the cell, instead of its index
+    id: Option<String>,
+    /// New source of the cell
+    source: String,
+    /// New type of the cell (defaults to the current one)
+    cell_type: Option<CellType>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct DeleteCellParams {
+    /// Path of the .ipynb file
+    path: String,
+    /// Index of the cell, from 0
+    index: Option<usize>,
+    /// Id of the cell, instead of its index
+    id: Option<String>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ClearOutputsParams {
+    /// Path of the .ipynb file
+    path: String,
+    /// Index of the cell to clear, all code cells when neither index nor id is given
+    index: Option<usize>,
+    /// Id of the cell to clear, instead of its index
+    id: Option<String>,
+}
+
+/// Structured result of `read_notebook`
+#[derive(Serialize, schemars::JsonSchema)]
+struct ReadNotebookOutput {
+    /// Path of the notebook
+    path: String,
+    /// Kernel language, like python
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// Cells in notebook order
+    cells: Vec<CellSummary>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct CellSummary {
+    /// Position of the cell, from 0
+    index: usize,
+    /// Cell id, present from nbformat 4.5
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// code, markdown or raw
+    cell_type: String,
+    /// Source of the cell
+    source: String,
+    /// Execution count of a code cell that ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execution_count: Option<u64>,
+    /// Text of the outputs, images and HTML replaced by a placeholder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    /// Whether the output was cut to max_output_chars
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    output_truncated: bool,
+}
+
+/// A notebook with the layout it was read with
+struct Notebook {
+    value: Value,
+    indent: usize,
+    trailing_newline: bool,
+}
+
+impl Notebook {
+    fn parse(text: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(text).map_err(|e| format!("Invalid notebook JSON: {}", e))?;
+        let major = value.get("nbformat").and_then(Value::as_u64).unwrap_or(0);
+        if major < 4 {
+            return Err(format!(
+                "Unsupported notebook format version {}, only nbformat 4 is supported",
+                major
+            ));
+        }
+        if !value.get("cells").is_some_and(Value::is_array) {
+            return Err("Invalid notebook: missing cells".to_string());
+        }
+
+        // Jupyter writes one space per level, other tools may use more
+        let indent = text
+            .lines()
+            .nth(1)
+            .map(|line| line.len() - line.trim_start_matches(' ').len())
+            .filter(|indent| *indent > 0)
+            .unwrap_or(1);
+        Ok(Self {
+            value,
+            indent,
+            trailing_newline: text.ends_with('\n'),
+        })
+    }
+
+    /// Serialize like nbformat: keys in order, the original indent, no ASCII escaping
+    fn render(&self) -> Result<String, String> {
+        let indent = " ".repeat(self.indent);
+        let mut buffer = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        self.value
+            .serialize(&mut serializer)
+            .map_err(|e| e.to_string())?;
+        let mut text = String::from_utf8(buffer).map_err(|e| e.to_string())?;
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    fn cells(&self) -> &[Value] {
+        self.value["cells"].as_array().map_or(&[], Vec::as_slice)
+    }
+
+    fn cells_mut(&mut self) -> &mut Vec<Value> {
+        match self.value.get_mut("cells") {
+            Some(Value::Array(cells)) => cells,
+            _ => unreachable!("checked when parsed"),
+        }
+    }
+
+    fn language(&self) -> Option<String> {
+        let metadata = &self.value["metadata"];
+        metadata["language_info"]["name"]
+            .as_str()
+            .or_else(|| metadata["kernelspec"]["language"].as_str())
+            .map(String::from)
+    }
+
+    /// Cells carry ids from nbformat 4.5
+    fn uses_cell_ids(&self) -> bool {
+        self.value["nbformat_minor"].as_u64().unwrap_or(0) >= 5
+            || self.cells().iter().any(|cell| cell.get("id").is_some())
+    }
+
+    /// Position of the cell picked by index or id
+    fn find_cell(&self, index: Option<usize>, id: Option<&str>) -> Result<usize, String> {
+        let count = self.cells().len();
+        match (index, id) {
+            (Some(_), Some(_)) => Err("Give either index or id, not both".to_string()),
+            (Some(index), None) if index < count => Ok(index),
+            (Some(index), None) => Err(format!(
+                "Cell index {} is out of range, the notebook has {} cells",
+                index, count
+            )),
+            (None, Some(id)) => self
+                .cells()
+                .iter()
+                .position(|cell| cell["id"].as_str() == Some(id))
+                .ok_or_else(|| format!("No cell with id {}", id)),
+            (None, None) => Err("Give the index or the id of the cell".to_string()),
+        }
+    }
+
+    fn new_cell(&self, cell_type: CellType, source: &str) -> Value {
+        // Keys are written sorted, matching nbformat whatever the map order
+        let mut cell = match cell_type {
+            CellType::Code => json!({
+                "cell_type": "code",
+                "execution_count": null,
+                "metadata": {},
+                "outputs": [],
+                "source": source_lines(source),
+            }),
+            _ => json!({
+                "cell_type": cell_type.as_str(),
+                "metadata": {},
+                "source": source_lines(source),
+            }),
+        };
+        if self.uses_cell_ids() {
+            let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+            if let Some(map) = cell.as_object_mut() {
+                map.insert("id".to_string(), Value::String(id));
+                map.sort_keys();
+            }
+        }
+        cell
+    }
+}
+
+/// Split a source into the list of lines nbformat stores, each keeping its newline
+fn source_lines(source: &str) -> Value {
+    Value::Array(
+        source
+            .split_inclusive('\n')
+            .map(|line| Value::String(line.to_string()))
+            .collect(),
+    )
+}
+
+/// Text of a multiline string field, stored as a string or a list of lines
+fn joined(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Remove terminal color codes from tracebacks
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip the escape sequence up to its final letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Readable text of one output, binary data replaced by a placeholder
+fn output_text(output: &Value) -> String {
+    match output["output_type"].as_str() {
+        Some("stream") => joined(&output["text"]),
+        Some("error") => {
+            let traceback = output["traceback"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(strip_ansi)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            if traceback.is_empty() {
+                format!(
+                    "{}: {}\n",
+                    output["ename"].as_str().unwrap_or("Error"),
+                    output["evalue"].as_str().unwrap_or_default()
+                )
+            } else {
+                format!("{}\n", traceback)
+            }
+        }
+        Some("execute_result") | Some("display_data") => {
+            let Some(data) = output["data"].as_object() else {
+                return String::new();
+            };
+            for mime in ["text/plain", "text/markdown", "application/json"] {
+                if let Some(value) = data.get(mime) {
+                    let text = match value {
+                        Value::String(_) | Value::Array(_) if mime != "application/json" => {
+                            joined(value)
+                        }
+                        _ => value.to_string(),
+                    };
+                    return format!("{}\n", text.trim_end_matches('\n'));
+                }
+            }
+            data.keys()
+                .map(|mime| format!("[{} output omitted]\n", mime))
+                .collect()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Cut text to at most `limit` characters
+fn truncate(text: &str, limit: usize) -> (String, bool) {
+    match text.char_indices().nth(limit) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text.to_string(), false),
+    }
+}
+
+fn summarize(notebook: &Notebook, max_output_chars: usize) -> Vec<CellSummary> {
+    notebook
+        .cells()
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| {
+            let outputs: String = cell["outputs"]
+                .as_array()
+                .map(|outputs| outputs.iter().map(output_text).collect())
+                .unwrap_or_default();
+            let (output, output_truncated) = truncate(&outputs, max_output_chars);
+            CellSummary {
+                index,
+                id: cell["id"].as_str().map(String::from),
+                cell_type: cell["cell_type"].as_str().unwrap_or("unknown").to_string(),
+                source: joined(&cell["source"]),
+                execution_count: cell["execution_count"].as_u64(),
+                output: (!output.is_empty()).then_some(output),
+                output_truncated,
+            }
+        })
+        .collect()
+}
+
+/// Compact text view of the cells
+fn render_cells(cells: &[CellSummary]) -> String {
+    let mut text = String::new();
+    for cell in cells {
+        text.push_str(&format!("[{}] {}", cell.index, cell.cell_type));
+        if let Some(id) = cell.id.as_ref() {
+            text.push_str(&format!(" id={}", id));
+        }
+        if let Some(count) = cell.execution_count {
+            text.push_str(&format!(" execution_count={}", count));
+        }
+        text.push('\n');
+        text.push_str(&cell.source);
+        if !cell.source.ends_with('\n') {
+            text.push('\n');
+        }
+        if let Some(output) = cell.output.as_ref() {
+            text.push_str("--- output ---\n");
+            text.push_str(output);
+            if cell.output_truncated {
+                text.push_str("\n[output truncated]");
+            }
+            if !output.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Remove the outputs and execution count of a code cell
+fn clear_cell(cell: &mut Value) -> bool {
+    if cell["cell_type"] != "code" {
+        return false;
+    }
+    let had_outputs = cell["outputs"].as_array().is_some_and(|o| !o.is_empty())
+        || !cell["execution_count"].is_null();
+    cell["outputs"] = json!([]);
+    cell["execution_count"] = Value::Null;
+    had_outputs
+}
+
+fn invalid_params(message: String) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+}
+
+async fn load_notebook(path: &str) -> Result<Notebook, McpError> {
+    let size = fs::metadata(path)
+        .await
+        .map_err(|e| invalid_params(format!("Failed to read {}: {}", path, e)))?
+        .len();
+    if size > MAX_NOTEBOOK_SIZE {
+        return Err(invalid_params(format!(
+            "{} is larger than {} bytes",
+            path, MAX_NOTEBOOK_SIZE
+        )));
+    }
+    let text = fs::read_to_string(path)
+        .await
+        .map_err(|e| invalid_params(format!("Failed to read {}: {}", path, e)))?;
+    Notebook::parse(&text).map_err(invalid_params)
+}
+
+/// Write the notebook through a temporary file so readers never see half of it,
+/// keeping the permissions of the file it replaces
+async fn save_notebook(path: &str, notebook: &Notebook) -> Result<(), McpError> {
+    let internal =
+        |message: String| McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, None);
+    let text = notebook.render().map_err(internal)?;
+    let temp = format!("{}.{}.tmp", path, uuid::Uuid::new_v4().simple());
+    let result = async {
+        fs::write(&temp, text).await?;
+        if let Ok(meta) = fs::metadata(path).await {
+            fs::set_permissions(&temp, meta.permissions()).await?;
+        }
+        fs::rename(&temp, path).await
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp).await;
+        return Err(internal(format!("Failed to write {}: {}", path, e)));
+    }
+    Ok(())
+}
+
+fn edit_result(path: &str, notebook: &Notebook, action: &str, index: usize) -> CallToolResult {
+    let cell = notebook.cells().get(index);
+    let result = json!({
+        "path": path,
+        "action": action,
+        "index": index,
+        "id": cell.and_then(|cell| cell["id"].as_str()),
+        "cellCount": notebook.cells().len(),
+    });
+    CallToolResult::success(vec![Content::text(
+        serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+    )])
+}
+
+#[tool_router(router = tool_router_notebook, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Read a Jupyter notebook (.ipynb) as a list of cells with their source and text outputs. Images, HTML and other binary outputs are replaced by placeholders and long outputs are truncated",
+        output_schema = cached_schema_for_type::<ReadNotebookOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn read_notebook(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ReadNotebookParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let notebook = load_notebook(&params.path).await?;
+        let cells = summarize(
+            &notebook,
+            params.max_output_chars.unwrap_or(DEFAULT_OUTPUT_CHARS),
+        );
+        let text = render_cells(&cells);
+        let mut result = structured_result(&ReadNotebookOutput {
+            path: params.path,
+            language: notebook.language(),
+            cells,
+        })?;
+        result.content = vec![Content::text(text)];
+        Ok(result)
+    }
+
+    #[tool(
+        description = "Insert a cell into a Jupyter notebook at an index, after the cell with a given id, or at the end. Notebook metadata and formatting are kept",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn insert_notebook_cell(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<InsertCellParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        let mut notebook = load_notebook(&params.path).await?;
+        let count = notebook.cells().len();
+        let index = match (params.index, params.after_id.as_deref()) {
+            (Some(_), Some(_)) => {
+                return Err(invalid_params(
+                    "Give either index or after_id, not both".to_string(),
+                ));
+            }
+            (Some(index), None) if index <= count => index,
+            (Some(index), None) => {
+                return Err(invalid_params(format!(
+                    "Cell index {} is out of range, the notebook has {} cells",
+                    index, count
+                )));
+            }
+            (None, Some(id)) => notebook.find_cell(None, Some(id)).map_err(invalid_params)? + 1,
+            (None, None) => count,
+        };
+
+        let cell = notebook.new_cell(params.cell_type.unwrap_or(CellType::Code), &params.source);
+        notebook.cells_mut().insert(index, cell);
+        save_notebook(&params.path, &notebook).await?;
+        Ok(edit_result(&params.path, &notebook, "inserted", index))
+    }
+
+    #[tool(
+        description = "Replace the source of a Jupyter notebook cell picked by index or id, optionally changing its type. The outputs of a code cell are kept, use clear_notebook_outputs to drop them",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn replace_notebook_cell(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ReplaceCellParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        let mut notebook = load_notebook(&params.path).await?;
+        let index = notebook
+            .find_cell(params.index, params.id.as_deref())
+            .map_err(invalid_params)?;
+        let cell = &mut notebook.cells_mut()[index];
+        // Keep the string or list layout the cell already uses
+        cell["source"] = match cell["source"] {
+            Value::String(_) => Value::String(params.source.clone()),
+            _ => source_lines(&params.source),
+        };
+
+        let current = cell["cell_type"].as_str().unwrap_or_default().to_string();
+        if let Some(cell_type) = params.cell_type.filter(|t| t.as_str() != current)
+            && let Some(map) = cell.as_object_mut()
+        {
+            map.insert("cell_type".to_string(), json!(cell_type.as_str()));
+            if cell_type == CellType::Code {
+                map.insert("execution_count".to_string(), Value::Null);
+                map.insert("outputs".to_string(), json!([]));
+            } else {
+                map.remove("execution_count");
+                map.remove("outputs");
+            }
+            map.sort_keys();
+        }
+
+        save_notebook(&params.path, &notebook).await?;
+        Ok(edit_result(&params.path, &notebook, "replaced", index))
+    }
+
+    #[tool(
+        description = "Delete a Jupyter notebook cell picked by index or id",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn delete_notebook_cell(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<DeleteCellParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        let mut notebook = load_notebook(&params.path).await?;
+        let index = notebook
+            .find_cell(params.index, params.id.as_deref())
+            .map_err(invalid_params)?;
+        let cell = notebook.cells_mut().remove(index);
+        save_notebook(&params.path, &notebook).await?;
+
+        let result = json!({
+            "path": params.path,
+            "action": "deleted",
+            "index": index,
+            "id": cell["id"].as_str(),
+            "cellCount": notebook.cells().len(),
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    #[tool(
+        description = "Clear the outputs and execution counts of a Jupyter notebook, or of one cell picked by index or id",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn clear_notebook_outputs(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ClearOutputsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        let mut notebook = load_notebook(&params.path).await?;
+        let cleared = if params.index.is_none() && params.id.is_none() {
+            notebook
+                .cells_mut()
+                .iter_mut()
+                .map(clear_cell)
+                .filter(|cleared| *cleared)
+                .count()
+        } else {
+            let index = notebook
+                .find_cell(params.index, params.id.as_deref())
+                .map_err(invalid_params)?;
+            usize::from(clear_cell(&mut notebook.cells_mut()[index]))
+        };
+        if cleared > 0 {
+            save_notebook(&params.path, &notebook).await?;
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Cleared outputs of {} cells in {}",
+            cleared,
+            Path::new(&params.path).display()
+        ))]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Layout written by nbformat: sorted keys, one space indent, final newline
+    const NOTEBOOK: &str = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 2,
+   "id": "a1b2c3d4",
+   "metadata": {},
+   "outputs": [
+    {
+     "data": {
+      "image/png": "iVBORw0KGgo=",
+      "text/plain": [
+       "<Figure size 640x480 with 1 Axes>"
+      ]
+     },
+     "metadata": {},
+     "output_type": "display_data"
+    },
+    {
+     "name": "stdout",
+     "output_type": "stream",
+     "text": [
+      "héllo\n"
+     ]
+    }
+   ],
+   "source": [
+    "import math\n",
+    "print(math.pi)"
+   ]
+  }
+ ],
+ "metadata": {
+  "language_info": {
+   "name": "python"
+  }
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"#;
+
+    #[test]
+    fn test_notebook_round_trip() {
+        let mut notebook = Notebook::parse(NOTEBOOK).unwrap();
+        assert_eq!(notebook.render().unwrap(), NOTEBOOK);
+        assert_eq!(notebook.language().as_deref(), Some("python"));
+
+        let cells = summarize(&notebook, 100);
+        assert_eq!(cells[0].source, "import math\nprint(math.pi)");
+        assert_eq!(
+            cells[0].output.as_deref(),
+            Some("<Figure size 640x480 with 1 Axes>\nhéllo\n")
+        );
+        let (cut, truncated) = truncate("héllo", 2);
+        assert_eq!((cut.as_str(), truncated), ("hé", true));
+
+        let cell = notebook.new_cell(CellType::Markdown, "# Title\ntext");
+        assert_eq!(cell["source"], json!(["# Title\n", "text"]));
+        assert_eq!(cell["id"].as_str().map(str::len), Some(8));
+        notebook.cells_mut().push(cell);
+        assert_eq!(notebook.find_cell(Some(1), None), Ok(1));
+        assert_eq!(notebook.find_cell(None, Some("a1b2c3d4")), Ok(0));
+        assert!(notebook.find_cell(Some(2), None).is_err());
+
+        assert!(clear_cell(&mut notebook.cells_mut()[0]));
+        assert!(!clear_cell(&mut notebook.cells_mut()[1]));
+        assert_eq!(
+            strip_ansi("\u{1b}[0;31mValueError\u{1b}[0m: bad"),
+            "ValueError: bad"
+        );
+    }
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_save_notebook() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir =
+            std::env::temp_dir().join(format!("dive-notebook-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.ipynb");
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        save_notebook(&path.to_string_lossy(), &notebook)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), NOTEBOOK);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
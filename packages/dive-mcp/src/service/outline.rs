@@ -0,0 +1,537 @@
+use crate::service::replace::collect_files;
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tree_sitter::{Node, Parser};
+
+/// Files larger than this are not parsed
+const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+/// Maximum number of files outlined by one call
+const MAX_FILES: usize = 200;
+/// Maximum number of symbols returned by one call
+const MAX_SYMBOLS: usize = 5000;
+/// Syntax nodes descended into while looking for symbols
+const MAX_NESTING: usize = 200;
+/// Characters of a signature kept
+const SIGNATURE_LIMIT: usize = 200;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct CodeOutlineParams {
+    /// A source file, or a directory whose supported source files are outlined
+    path: String,
+    /// Globs relative to the directory selecting the files, like `src/**/*.rs`. Globs starting with `!` exclude files (default: all supported files)
+    globs: Option<Vec<String>>,
+    /// Also outline hidden files and directories, `.git` is always skipped (default false)
+    include_hidden: Option<bool>,
+}
+
+/// Structured result of `code_outline`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct CodeOutlineOutput {
+    /// Outlined files
+    files: Vec<FileOutline>,
+    /// Whether files or symbols were left out because of the limits
+    truncated: bool,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct FileOutline {
+    /// Path of the file
+    path: String,
+    /// Language the file was parsed as
+    language: String,
+    /// Number of lines of the file
+    lines: usize,
+    /// Whether the parser met syntax errors, the outline may then be incomplete
+    has_errors: bool,
+    /// Top-level symbols
+    symbols: Vec<OutlineSymbol>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct OutlineSymbol {
+    /// Kind of symbol: function, method, class, struct, enum, trait, impl, interface, type, module, ...
+    kind: String,
+    /// Name of the symbol
+    name: String,
+    /// Declaration up to its body, whitespace collapsed
+    signature: String,
+    /// First line, 1-based
+    start_line: usize,
+    /// Last line, inclusive
+    end_line: usize,
+    /// Members of types and modules
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<OutlineSymbol>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Language {
+    Rust,
+    TypeScript,
+    Tsx,
+    JavaScript,
+    Python,
+    Go,
+    Java,
+}
+
+impl Language {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "rs" => Some(Language::Rust),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Language::JavaScript),
+            "py" | "pyi" => Some(Language::Python),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::TypeScript => "typescript",
+            Language::Tsx => "tsx",
+            Language::JavaScript => "javascript",
+            Language::Python => "python",
+            Language::Go => "go",
+            Language::Java => "java",
+        }
+    }
+
+    fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+            Language::Java => tree_sitter_java::LANGUAGE.into(),
+        }
+    }
+
+    /// Kind of symbol a node declares, and whether its members are listed
+    fn classify(&self, node: &Node, in_type: bool) -> Option<(&'static str, bool)> {
+        let function = if in_type { "method" } else { "function" };
+        let symbol = match (self, node.kind()) {
+            (Language::Rust, "function_item") => (function, false),
+            (Language::Rust, "function_signature_item") => ("method", false),
+            (Language::Rust, "struct_item") => ("struct", false),
+            (Language::Rust, "enum_item") => ("enum", false),
+            (Language::Rust, "union_item") => ("union", false),
+            (Language::Rust, "type_item") => ("type", false),
+            (Language::Rust, "const_item") => ("const", false),
+            (Language::Rust, "static_item") => ("static", false),
+            (Language::Rust, "macro_definition") => ("macro", false),
+            (Language::Rust, "trait_item") => ("trait", true),
+            (Language::Rust, "impl_item") => ("impl", true),
+            (Language::Rust, "mod_item") => ("module", true),
+
+            (Language::Python, "function_definition") => (function, false),
+            (Language::Python, "class_definition") => ("class", true),
+
+            (Language::Go, "function_declaration") => ("function", false),
+            (Language::Go, "method_declaration") => ("method", false),
+            (Language::Go, "type_alias") => ("type", false),
+            (Language::Go, "type_spec") => {
+                match node.child_by_field_name("type").map(|t| t.kind()) {
+                    Some("struct_type") => ("struct", false),
+                    Some("interface_type") => ("interface", false),
+                    _ => ("type", false),
+                }
+            }
+
+            (Language::Java, "class_declaration") => ("class", true),
+            (Language::Java, "interface_declaration") => ("interface", true),
+            (Language::Java, "enum_declaration") => ("enum", true),
+            (Language::Java, "record_declaration") => ("record", true),
+            (Language::Java, "annotation_type_declaration") => ("annotation", false),
+            (Language::Java, "method_declaration") => ("method", false),
+            (Language::Java, "constructor_declaration") => ("constructor", false),
+
+            (Language::Java | Language::Rust | Language::Python | Language::Go, _) => return None,
+
+            // TypeScript and JavaScript
+            (_, "function_declaration" | "generator_function_declaration") => ("function", false),
+            (_, "class_declaration" | "abstract_class_declaration") => ("class", true),
+            (_, "method_definition" | "abstract_method_signature") => ("method", false),
+            (_, "interface_declaration") => ("interface", false),
+            (_, "type_alias_declaration") => ("type", false),
+            (_, "enum_declaration") => ("enum", false),
+            (_, "internal_module" | "module") => ("namespace", true),
+            // Functions assigned to variables and class fields
+            (_, "variable_declarator" | "public_field_definition" | "field_definition") => {
+                let value = node.child_by_field_name("value")?;
+                match value.kind() {
+                    "arrow_function" | "function_expression" | "function" => {
+                        (if in_type { "method" } else { "function" }, false)
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(symbol)
+    }
+}
+
+fn node_text<'a>(node: Node, source: &'a [u8]) -> &'a str {
+    node.utf8_text(source).unwrap_or_default()
+}
+
+fn symbol_name(node: &Node, source: &[u8]) -> String {
+    let field = |name: &str| node.child_by_field_name(name).map(|n| node_text(n, source));
+    match node.kind() {
+        "impl_item" => match (field("trait"), field("type")) {
+            (Some(t), Some(ty)) => format!("{} for {}", t, ty),
+            (_, ty) => ty.unwrap_or_default().to_string(),
+        },
+        _ => field("name")
+            .or_else(|| field("property"))
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Text of the declaration before its body, on one line
+fn signature(node: &Node, source: &[u8]) -> String {
+    let body = node.child_by_field_name("body").or_else(|| {
+        node.child_by_field_name("value")
+            .and_then(|value| value.child_by_field_name("body"))
+    });
+    let text = match body {
+        Some(body) => String::from_utf8_lossy(&source[node.start_byte()..body.start_byte()]),
+        None => node_text(*node, source)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .into(),
+    };
+    // Parameter lists spread over lines read as `(a, b)` once joined
+    let collapsed = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace(" )", ")");
+    let collapsed = collapsed.trim_end_matches(['{', ' ']);
+    let mut signature: String = collapsed.chars().take(SIGNATURE_LIMIT).collect();
+    if collapsed.chars().count() > SIGNATURE_LIMIT {
+        signature.push('…');
+    }
+    signature
+}
+
+/// Collects the symbols of a syntax tree, up to a total budget
+struct Outliner<'a> {
+    language: Language,
+    source: &'a [u8],
+    remaining: usize,
+}
+
+impl Outliner<'_> {
+    /// Symbols declared under `node`, skipping the bodies of functions
+    fn symbols(&mut self, node: Node, in_type: bool, nesting: usize) -> Vec<OutlineSymbol> {
+        let mut symbols = Vec::new();
+        if nesting > MAX_NESTING {
+            return symbols;
+        }
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if self.remaining == 0 {
+                break;
+            }
+            let Some((kind, container)) = self.language.classify(&child, in_type) else {
+                symbols.extend(self.symbols(child, in_type, nesting + 1));
+                continue;
+            };
+            self.remaining -= 1;
+            let children = if container {
+                let members_are_methods = !matches!(kind, "module" | "namespace");
+                self.symbols(child, members_are_methods, nesting + 1)
+            } else {
+                Vec::new()
+            };
+            // Python decorators belong to the definition they wrap
+            let start = match node.kind() {
+                "decorated_definition" => node.start_position(),
+                _ => child.start_position(),
+            };
+            symbols.push(OutlineSymbol {
+                kind: kind.to_string(),
+                name: symbol_name(&child, self.source),
+                signature: signature(&child, self.source),
+                start_line: start.row + 1,
+                end_line: child.end_position().row + 1,
+                children,
+            });
+        }
+        symbols
+    }
+}
+
+/// Number of lines as counted by `read_file`, a final newline ends the
+/// last line rather than starting a new one
+fn line_count(source: &[u8]) -> usize {
+    let newlines = source.iter().filter(|&&b| b == b'\n').count();
+    match source.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+fn count_symbols(symbols: &[OutlineSymbol]) -> usize {
+    symbols
+        .iter()
+        .map(|symbol| 1 + count_symbols(&symbol.children))
+        .sum()
+}
+
+/// Parse a source file and list the symbols it declares
+fn outline_source(
+    language: Language,
+    source: &[u8],
+    max_symbols: usize,
+) -> Result<(Vec<OutlineSymbol>, bool), String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&language.grammar())
+        .map_err(|e| format!("Failed to load the {} grammar: {}", language.as_str(), e))?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| "Parsing was cancelled".to_string())?;
+    let root = tree.root_node();
+    let mut outliner = Outliner {
+        language,
+        source,
+        remaining: max_symbols,
+    };
+    let symbols = outliner.symbols(root, false, 0);
+    Ok((symbols, root.has_error()))
+}
+
+fn outline_files(
+    root: &Path,
+    files: Vec<PathBuf>,
+    single_file: bool,
+) -> Result<CodeOutlineOutput, String> {
+    let mut output = CodeOutlineOutput {
+        files: Vec::new(),
+        truncated: false,
+    };
+    let mut remaining = MAX_SYMBOLS;
+    for path in files {
+        let Some(language) = Language::from_path(&path) else {
+            continue;
+        };
+        if output.files.len() == MAX_FILES || remaining == 0 {
+            output.truncated = true;
+            break;
+        }
+        let source = match std::fs::read(&path) {
+            Ok(source) => source,
+            Err(e) if single_file => return Err(format!("Failed to read file: {}", e)),
+            Err(_) => continue,
+        };
+        let (symbols, has_errors) = outline_source(language, &source, remaining)?;
+        remaining -= count_symbols(&symbols);
+        let display_path = match path.strip_prefix(root) {
+            Ok(relative) if !single_file => relative.to_string_lossy().to_string(),
+            _ => path.to_string_lossy().to_string(),
+        };
+        output.files.push(FileOutline {
+            path: display_path,
+            language: language.as_str().to_string(),
+            lines: line_count(&source),
+            has_errors,
+            symbols,
+        });
+    }
+    if remaining == 0 {
+        output.truncated = true;
+    }
+    Ok(output)
+}
+
+fn render_symbols(symbols: &[OutlineSymbol], depth: usize, text: &mut String) {
+    for symbol in symbols {
+        text.push_str(&format!(
+            "{}{}-{} {}\n",
+            "  ".repeat(depth + 1),
+            symbol.start_line,
+            symbol.end_line,
+            symbol.signature
+        ));
+        render_symbols(&symbol.children, depth + 1, text);
+    }
+}
+
+/// Render the outline as indented lines with their line ranges
+fn render_outline(output: &CodeOutlineOutput) -> String {
+    let mut text = String::new();
+    for file in &output.files {
+        text.push_str(&format!(
+            "{} ({}, {} lines{})\n",
+            file.path,
+            file.language,
+            file.lines,
+            if file.has_errors {
+                ", syntax errors"
+            } else {
+                ""
+            }
+        ));
+        render_symbols(&file.symbols, 0, &mut text);
+    }
+    if output.files.is_empty() {
+        text.push_str("No supported source files found\n");
+    }
+    if output.truncated {
+        text.push_str(&format!(
+            "\nOutline truncated at {} files or {} symbols, narrow it with a path or globs\n",
+            MAX_FILES, MAX_SYMBOLS
+        ));
+    }
+    text
+}
+
+#[tool_router(router = tool_router_outline, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "List the functions, types, methods and other symbols of a source file, or of all supported files in a directory, with their line ranges. Supports Rust, TypeScript, JavaScript, Python, Go and Java. Use it to find code and then read only the relevant lines with `read_file` and `start_line`/`end_line`. Directories honor .gitignore",
+        output_schema = cached_schema_for_type::<CodeOutlineOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn code_outline(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<CodeOutlineParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let invalid =
+            |message: String| McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None);
+        let root = PathBuf::from(Self::normalize_path(&params.path));
+        let single_file = root.is_file();
+        if single_file && Language::from_path(&root).is_none() {
+            return Err(invalid(format!(
+                "{} is not a supported source file (Rust, TypeScript, JavaScript, Python, Go, Java)",
+                params.path
+            )));
+        }
+        if single_file && root.metadata().is_ok_and(|m| m.len() > MAX_FILE_SIZE) {
+            return Err(invalid(format!(
+                "{} is larger than {} bytes",
+                params.path, MAX_FILE_SIZE
+            )));
+        }
+
+        let globs = params.globs.unwrap_or_default();
+        let include_hidden = params.include_hidden.unwrap_or(false);
+        let output = run_blocking(move || {
+            let files = collect_files(&root, &globs, include_hidden)
+                .map_err(invalid)?
+                .0
+                .into_iter()
+                .filter(|path| path.metadata().is_ok_and(|m| m.len() <= MAX_FILE_SIZE))
+                .collect();
+            outline_files(&root, files, single_file).map_err(invalid)
+        })
+        .await?;
+
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(render_outline(&output))];
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(language: Language, source: &str) -> Vec<(String, String, usize, usize)> {
+        let (symbols, _) = outline_source(language, source.as_bytes(), MAX_SYMBOLS).unwrap();
+        let mut flat = Vec::new();
+        let mut stack: Vec<&OutlineSymbol> = symbols.iter().rev().collect();
+        while let Some(symbol) = stack.pop() {
+            flat.push((
+                symbol.kind.clone(),
+                symbol.name.clone(),
+                symbol.start_line,
+                symbol.end_line,
+            ));
+            stack.extend(symbol.children.iter().rev());
+        }
+        flat
+    }
+
+    #[test]
+    fn test_outline() {
+        let rust = "struct Point {\n    x: i32,\n}\n\nimpl Point {\n    pub fn new(\n        x: i32,\n    ) -> Self {\n        Point { x }\n    }\n}\n";
+        assert_eq!(
+            outline(Language::Rust, rust),
+            [
+                ("struct".into(), "Point".into(), 1, 3),
+                ("impl".into(), "Point".into(), 5, 11),
+                ("method".into(), "new".into(), 6, 10),
+            ]
+        );
+        let (symbols, _) = outline_source(Language::Rust, rust.as_bytes(), 10).unwrap();
+        assert_eq!(
+            symbols[1].children[0].signature,
+            "pub fn new(x: i32) -> Self"
+        );
+
+        let python = "@decorator\ndef top():\n    def inner():\n        pass\n\nclass A:\n    @property\n    def run(self):\n        pass\n";
+        assert_eq!(
+            outline(Language::Python, python),
+            [
+                ("function".into(), "top".into(), 1, 4),
+                ("class".into(), "A".into(), 6, 9),
+                ("method".into(), "run".into(), 7, 9),
+            ]
+        );
+
+        for text in ["", "a", "a\n", "a\nb", "a\r\nb\r\n", "\n\n"] {
+            assert_eq!(
+                line_count(text.as_bytes()),
+                text.lines().count(),
+                "{:?}",
+                text
+            );
+        }
+
+        let typescript = "export const handler = async (x: number) => {\n  return x;\n};\nexport class Store {\n  get(key: string) {}\n}\ninterface Item { id: string }\n";
+        assert_eq!(
+            outline(Language::TypeScript, typescript),
+            [
+                ("function".into(), "handler".into(), 1, 3),
+                ("class".into(), "Store".into(), 4, 6),
+                ("method".into(), "get".into(), 5, 5),
+                ("interface".into(), "Item".into(), 7, 7),
+            ]
+        );
+    }
+}
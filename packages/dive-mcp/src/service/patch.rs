@@ -0,0 +1,1118 @@
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::CallToolResult,
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of context lines a hunk may ignore
+const MAX_FUZZ: usize = 3;
+/// Number of expected lines quoted when a hunk does not match
+const QUOTED_LINES: usize = 3;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ApplyPatchParams {
+    /// Unified diff to apply, may touch several files (`diff --git`, `---`/`+++` headers and `@@` hunks)
+    patch: String,
+    /// Directory the paths in the diff are relative to (defaults to the server working directory)
+    base_dir: Option<String>,
+    /// Leading path components to strip from the paths in the diff, like `patch -p`. Defaults to 1 for `a/` and `b/` prefixed paths, otherwise 0
+    strip: Option<usize>,
+    /// Number of context lines a hunk may ignore at its start and end when it does not match exactly (default 0, max 3)
+    fuzz: Option<usize>,
+    /// Ignore whitespace differences when matching context and removed lines (default false)
+    ignore_whitespace: Option<bool>,
+    /// Maximum number of lines a hunk may be moved from its stated position (default: anywhere in the file)
+    max_offset: Option<usize>,
+    /// Only check that the patch applies, without changing any file (default false)
+    dry_run: Option<bool>,
+}
+
+/// Structured result of `apply_patch`
+#[derive(Serialize, schemars::JsonSchema)]
+struct ApplyPatchOutput {
+    /// Whether the files were changed, false for a dry run
+    applied: bool,
+    /// Result for every file in the patch
+    files: Vec<PatchFileResult>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct PatchFileResult {
+    /// Absolute path of the file
+    path: String,
+    /// What happened to the file
+    status: FileStatus,
+    /// Previous path of a renamed file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    /// Number of lines added
+    lines_added: usize,
+    /// Number of lines removed
+    lines_removed: usize,
+    /// Where each hunk was applied
+    hunks: Vec<HunkResult>,
+}
+
+#[derive(Serialize, schemars::JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Modified,
+    Created,
+    Deleted,
+    Renamed,
+}
+
+#[derive(Serialize, schemars::JsonSchema, PartialEq, Debug)]
+struct HunkResult {
+    /// Line of the original file the hunk was applied at
+    line: usize,
+    /// Lines between the position stated in the hunk header and the actual one
+    offset: isize,
+    /// Context lines ignored to make the hunk match
+    fuzz: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug)]
+struct Hunk {
+    old_start: usize,
+    old_count: usize,
+    new_start: usize,
+    new_count: usize,
+    lines: Vec<HunkLine>,
+    /// The old side of the hunk ends without a newline
+    old_no_newline: bool,
+    /// The new side of the hunk ends without a newline
+    new_no_newline: bool,
+}
+
+impl Hunk {
+    fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )
+    }
+
+    /// Lines the hunk expects in the original file
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn leading_context(&self) -> usize {
+        self.lines
+            .iter()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+    }
+
+    fn trailing_context(&self) -> usize {
+        self.lines
+            .iter()
+            .rev()
+            .take_while(|line| matches!(line, HunkLine::Context(_)))
+            .count()
+    }
+}
+
+/// Changes to one file, paths are relative to the base directory with the
+/// prefix already stripped, `None` standing for `/dev/null`
+#[derive(Debug, Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+    /// Git marked the file as new or deleted
+    git_created: bool,
+    git_deleted: bool,
+}
+
+impl FilePatch {
+    fn status(&self) -> FileStatus {
+        match (&self.old_path, &self.new_path) {
+            _ if self.git_created => FileStatus::Created,
+            _ if self.git_deleted => FileStatus::Deleted,
+            (None, _) => FileStatus::Created,
+            (_, None) => FileStatus::Deleted,
+            (Some(old), Some(new)) if old != new => FileStatus::Renamed,
+            _ => FileStatus::Modified,
+        }
+    }
+
+    /// The path the patch leaves a file at, or deletes
+    fn target(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .filter(|_| !self.git_deleted)
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// A file patch being parsed, paths still carry their prefix
+#[derive(Default)]
+struct PendingFile {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    /// Paths from `rename from`/`rename to`, which never have a prefix
+    rename_from: Option<String>,
+    rename_to: Option<String>,
+    has_headers: bool,
+    hunks: Vec<Hunk>,
+    git_created: bool,
+    git_deleted: bool,
+}
+
+impl PendingFile {
+    fn finish(self, strip: Option<usize>) -> Result<FilePatch, String> {
+        // git uses a/ and b/ prefixes, the /dev/null side has none
+        let prefixed = [(&self.old_path, "a/"), (&self.new_path, "b/")]
+            .iter()
+            .all(|(path, prefix)| path.as_ref().is_none_or(|p| p.starts_with(prefix)));
+        let strip = strip.unwrap_or(if prefixed { 1 } else { 0 });
+
+        let old_path = match self.rename_from {
+            Some(path) => Some(path),
+            None => self.old_path.map(|p| strip_path(&p, strip)).transpose()?,
+        };
+        let new_path = match self.rename_to {
+            Some(path) => Some(path),
+            None => self.new_path.map(|p| strip_path(&p, strip)).transpose()?,
+        };
+        if old_path.is_none() && new_path.is_none() {
+            return Err("File patch without a path".to_string());
+        }
+        Ok(FilePatch {
+            old_path,
+            new_path,
+            hunks: self.hunks,
+            git_created: self.git_created,
+            git_deleted: self.git_deleted,
+        })
+    }
+}
+
+/// Parse the path of a `---`/`+++` header, dropping any timestamp
+fn header_path(text: &str) -> Option<String> {
+    let path = text.split('\t').next().unwrap_or_default().trim_end();
+    let path = path
+        .strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path);
+    (path != "/dev/null").then(|| path.to_string())
+}
+
+/// Parse the `a/x b/y` paths of a `diff --git` line
+fn git_paths(text: &str) -> Option<(String, String)> {
+    let (old, new) = text.split_once(" b/")?;
+    Some((old.to_string(), format!("b/{}", new)))
+}
+
+fn strip_path(path: &str, strip: usize) -> Result<String, String> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if components.len() <= strip {
+        return Err(format!(
+            "Cannot strip {} leading components from {}",
+            strip, path
+        ));
+    }
+    let rest = components[strip..].join("/");
+    Ok(if strip == 0 && path.starts_with('/') {
+        format!("/{}", rest)
+    } else {
+        rest
+    })
+}
+
+/// Parse `-start[,count]` or `+start[,count]`, the count defaults to 1
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (start, count) = match text.split_once(',') {
+        Some((start, count)) => (start, count.parse().ok()?),
+        None => (text, 1),
+    };
+    Some((start.parse().ok()?, count))
+}
+
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let mut parts = line.strip_prefix("@@ ")?.split(' ');
+    let (old_start, old_count) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// Parse a unified diff touching one or more files
+///
+/// Text outside file sections, like a commit message, is ignored.
+fn parse_patch(patch: &str, strip: Option<usize>) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files = Vec::new();
+    let mut current: Option<PendingFile> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            if let Some(done) = current.take() {
+                files.push(done.finish(strip)?);
+            }
+            let (old_path, new_path) = git_paths(rest)
+                .ok_or_else(|| format!("Cannot parse paths of line {}: {}", i + 1, line))?;
+            current = Some(PendingFile {
+                old_path: Some(old_path),
+                new_path: Some(new_path),
+                ..Default::default()
+            });
+        } else if line.starts_with("--- ")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| next.starts_with("+++ "))
+        {
+            // Without git headers, a new ---/+++ pair starts the next file
+            let pending = match current.take() {
+                Some(pending) if !pending.has_headers && pending.hunks.is_empty() => pending,
+                Some(done) => {
+                    files.push(done.finish(strip)?);
+                    PendingFile::default()
+                }
+                None => PendingFile::default(),
+            };
+            current = Some(PendingFile {
+                old_path: header_path(&line[4..]),
+                new_path: header_path(&lines[i + 1][4..]),
+                has_headers: true,
+                ..pending
+            });
+            i += 1;
+        } else if line.starts_with("@@") {
+            let Some(pending) = current.as_mut() else {
+                return Err(format!("Hunk without file headers at line {}", i + 1));
+            };
+            let hunk_line = i + 1;
+            let (old_start, old_count, new_start, new_count) = parse_hunk_header(line)
+                .ok_or_else(|| format!("Invalid hunk header at line {}: {}", hunk_line, line))?;
+            let mut hunk = Hunk {
+                old_start,
+                old_count,
+                new_start,
+                new_count,
+                lines: Vec::new(),
+                old_no_newline: false,
+                new_no_newline: false,
+            };
+
+            let (mut old_left, mut new_left) = (old_count, new_count);
+            while old_left > 0 || new_left > 0 {
+                i += 1;
+                let Some(body) = lines.get(i) else {
+                    return Err(format!(
+                        "Hunk at line {} ends before its {} old and {} new lines",
+                        hunk_line, old_count, new_count
+                    ));
+                };
+                let (kind, text) = match body.chars().next() {
+                    // Some tools drop the space of empty context lines
+                    None => (' ', ""),
+                    Some(kind) => (kind, &body[kind.len_utf8()..]),
+                };
+                let counts = match kind {
+                    ' ' => (1, 1),
+                    '-' => (1, 0),
+                    '+' => (0, 1),
+                    '\\' => continue,
+                    _ => (usize::MAX, usize::MAX),
+                };
+                if counts.0 > old_left || counts.1 > new_left {
+                    return Err(format!(
+                        "Hunk at line {} does not match its header {}, line {} is unexpected: {}",
+                        hunk_line,
+                        hunk.header(),
+                        i + 1,
+                        body
+                    ));
+                }
+                old_left -= counts.0;
+                new_left -= counts.1;
+                hunk.lines.push(match kind {
+                    ' ' => HunkLine::Context(text.to_string()),
+                    '-' => HunkLine::Remove(text.to_string()),
+                    _ => HunkLine::Add(text.to_string()),
+                });
+            }
+
+            // "\ No newline at end of file" applies to the line before it
+            while lines.get(i + 1).is_some_and(|next| next.starts_with('\\')) {
+                i += 1;
+                match hunk.lines.last() {
+                    Some(HunkLine::Remove(_)) => hunk.old_no_newline = true,
+                    Some(HunkLine::Add(_)) => hunk.new_no_newline = true,
+                    Some(HunkLine::Context(_)) => {
+                        hunk.old_no_newline = true;
+                        hunk.new_no_newline = true;
+                    }
+                    None => {}
+                }
+            }
+            // A marker in the middle of the hunk ends the old side
+            if hunk.lines.iter().any(|l| matches!(l, HunkLine::Add(_)))
+                && lines[hunk_line..=i]
+                    .windows(2)
+                    .any(|w| w[0].starts_with('-') && w[1].starts_with('\\'))
+            {
+                hunk.old_no_newline = true;
+            }
+            pending.hunks.push(hunk);
+        } else if let Some(pending) = current.as_mut().filter(|p| p.hunks.is_empty()) {
+            if line.starts_with("new file mode") {
+                pending.git_created = true;
+            } else if line.starts_with("deleted file mode") {
+                pending.git_deleted = true;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                pending.rename_from = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                pending.rename_to = Some(path.to_string());
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                return Err(format!("Binary patches are not supported (line {})", i + 1));
+            }
+        }
+        i += 1;
+    }
+    if let Some(done) = current.take() {
+        files.push(done.finish(strip)?);
+    }
+
+    if files.is_empty() {
+        return Err("No file changes found in the patch".to_string());
+    }
+    Ok(files)
+}
+
+/// Text of a file split into lines, remembering how to write it back
+#[derive(Debug, PartialEq)]
+struct TextFile {
+    lines: Vec<Line>,
+    /// Ending of added lines, the one most lines of the file use
+    crlf: bool,
+    final_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    text: String,
+    /// The line's own ending, `None` for added lines and a last line
+    /// without newline, which take the ending of the file
+    crlf: Option<bool>,
+}
+
+impl Line {
+    fn added(text: &str) -> Self {
+        Self {
+            text: text.strip_suffix('\r').unwrap_or(text).to_string(),
+            crlf: None,
+        }
+    }
+}
+
+impl TextFile {
+    fn parse(content: &str) -> Self {
+        let final_newline = content.is_empty() || content.ends_with('\n');
+        let lines: Vec<Line> = content
+            .split_inclusive('\n')
+            .map(|line| match line.strip_suffix('\n') {
+                Some(line) => match line.strip_suffix('\r') {
+                    Some(text) => Line {
+                        text: text.to_string(),
+                        crlf: Some(true),
+                    },
+                    None => Line {
+                        text: line.to_string(),
+                        crlf: Some(false),
+                    },
+                },
+                None => Line::added(line),
+            })
+            .collect();
+        let crlf_lines = lines.iter().filter(|l| l.crlf == Some(true)).count();
+        let lf_lines = lines.iter().filter(|l| l.crlf == Some(false)).count();
+        Self {
+            lines,
+            crlf: crlf_lines > lf_lines,
+            final_newline,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            text.push_str(&line.text);
+            if index + 1 < self.lines.len() || self.final_newline {
+                text.push_str(if line.crlf.unwrap_or(self.crlf) {
+                    "\r\n"
+                } else {
+                    "\n"
+                });
+            }
+        }
+        text
+    }
+}
+
+struct MatchOptions {
+    fuzz: usize,
+    ignore_whitespace: bool,
+    max_offset: Option<usize>,
+}
+
+impl MatchOptions {
+    fn line_matches(&self, actual: &str, expected: &str) -> bool {
+        let expected = expected.strip_suffix('\r').unwrap_or(expected);
+        if self.ignore_whitespace {
+            actual.split_whitespace().eq(expected.split_whitespace())
+        } else {
+            actual == expected
+        }
+    }
+
+    /// Find `pattern` in `lines[from..]`, nearest to `expected` first
+    fn find(
+        &self,
+        lines: &[Line],
+        pattern: &[&str],
+        from: usize,
+        expected: usize,
+    ) -> Option<usize> {
+        let last = lines.len().checked_sub(pattern.len())?;
+        if from > last {
+            return None;
+        }
+        let expected = expected.clamp(from, last);
+        let matches_at = |start: usize| {
+            lines[start..start + pattern.len()]
+                .iter()
+                .zip(pattern)
+                .all(|(actual, expected)| self.line_matches(&actual.text, expected))
+        };
+
+        let reach = (expected - from).max(last - expected);
+        let reach = self.max_offset.map_or(reach, |max| reach.min(max));
+        for distance in 0..=reach {
+            if expected + distance <= last && matches_at(expected + distance) {
+                return Some(expected + distance);
+            }
+            if distance > 0 && distance <= expected - from && matches_at(expected - distance) {
+                return Some(expected - distance);
+            }
+        }
+        None
+    }
+}
+
+/// Apply hunks to a file, returning where each was applied
+fn apply_hunks(
+    file: &TextFile,
+    hunks: &[Hunk],
+    options: &MatchOptions,
+) -> Result<(TextFile, Vec<HunkResult>), Vec<String>> {
+    let mut lines = Vec::with_capacity(file.lines.len());
+    let mut final_newline = file.final_newline;
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut pos = 0;
+    let mut offset: isize = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let old = hunk.old_lines();
+        // The start of a hunk without old lines is the line it inserts after
+        let stated = if old.is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = (stated as isize + offset).max(0) as usize;
+
+        let (leading, trailing) = (hunk.leading_context(), hunk.trailing_context());
+        let found = (0..=options.fuzz).find_map(|fuzz| {
+            let (skip_start, skip_end) = (fuzz.min(leading), fuzz.min(trailing));
+            if fuzz > 0 && (skip_start + skip_end == 0 || skip_start + skip_end >= old.len()) {
+                return None;
+            }
+            let pattern = &old[skip_start..old.len() - skip_end];
+            options
+                .find(&file.lines, pattern, pos, expected + skip_start)
+                .map(|start| (start, fuzz, skip_start, skip_end, pattern.len()))
+        });
+        let Some((start, fuzz, skip_start, skip_end, matched)) = found else {
+            let quoted = old
+                .iter()
+                .take(QUOTED_LINES)
+                .map(|line| format!("\n    {}", line))
+                .collect::<String>();
+            errors.push(format!(
+                "hunk {} ({}) does not match near line {}, expected:{}",
+                index + 1,
+                hunk.header(),
+                expected + 1,
+                quoted
+            ));
+            continue;
+        };
+
+        lines.extend_from_slice(&file.lines[pos..start]);
+        let kept = &hunk.lines[skip_start..hunk.lines.len() - skip_end];
+        let mut source = start;
+        for line in kept {
+            match line {
+                HunkLine::Context(_) => {
+                    // Keep the file's own text, it may differ in whitespace
+                    lines.push(file.lines[source].clone());
+                    source += 1;
+                }
+                HunkLine::Remove(_) => source += 1,
+                HunkLine::Add(text) => lines.push(Line::added(text)),
+            }
+        }
+        pos = start + matched;
+        if pos == file.lines.len() && skip_end == 0 {
+            if hunk.new_no_newline {
+                final_newline = false;
+            } else if hunk.old_no_newline || file.lines.is_empty() {
+                final_newline = true;
+            }
+        }
+
+        let actual = start.saturating_sub(skip_start);
+        offset = actual as isize - stated as isize;
+        results.push(HunkResult {
+            line: actual + 1,
+            offset,
+            fuzz,
+        });
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    lines.extend_from_slice(&file.lines[pos..]);
+    Ok((
+        TextFile {
+            lines,
+            crlf: file.crlf,
+            final_newline,
+        },
+        results,
+    ))
+}
+
+/// A validated change, ready to be written
+pub(super) enum Change {
+    Write {
+        path: PathBuf,
+        content: String,
+        /// Copy permissions from this file
+        mode_from: Option<PathBuf>,
+    },
+    Remove {
+        path: PathBuf,
+    },
+}
+
+/// Read the patched files and compute their new content, reporting every
+/// hunk that does not apply
+///
+/// `paths` holds the resolved target and original path of each patch, the
+/// same paths the permission check was made for.
+fn prepare(
+    patches: &[FilePatch],
+    paths: &[(PathBuf, Option<PathBuf>)],
+    options: &MatchOptions,
+) -> Result<(Vec<Change>, Vec<PatchFileResult>), Vec<String>> {
+    let mut changes = Vec::new();
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+
+    for (patch, (target, source)) in patches.iter().zip(paths) {
+        let status = patch.status();
+        let source = source.clone();
+        let display = target.to_string_lossy().to_string();
+        let mut fail = |message: String| errors.push(format!("{}: {}", display, message));
+
+        let original = match (&source, status) {
+            (_, FileStatus::Created) => {
+                if target.exists() {
+                    fail("file already exists".to_string());
+                    continue;
+                }
+                String::new()
+            }
+            (Some(source), _) => match fs::read(source) {
+                Ok(bytes) => match String::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(_) => {
+                        fail("not a UTF-8 text file".to_string());
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    fail(format!("cannot read {}: {}", source.display(), e));
+                    continue;
+                }
+            },
+            (None, _) => {
+                fail("missing original path".to_string());
+                continue;
+            }
+        };
+        if status == FileStatus::Renamed && target.exists() {
+            fail("rename target already exists".to_string());
+            continue;
+        }
+
+        let text = TextFile::parse(&original);
+        let (patched, hunks) = match apply_hunks(&text, &patch.hunks, options) {
+            Ok(applied) => applied,
+            Err(hunk_errors) => {
+                hunk_errors.into_iter().for_each(&mut fail);
+                continue;
+            }
+        };
+        if status == FileStatus::Deleted && !patch.hunks.is_empty() && !patched.lines.is_empty() {
+            fail(format!(
+                "deleting the file leaves {} lines not in the patch",
+                patched.lines.len()
+            ));
+            continue;
+        }
+
+        let (mut lines_added, mut lines_removed) = (0, 0);
+        for line in patch.hunks.iter().flat_map(|hunk| &hunk.lines) {
+            match line {
+                HunkLine::Add(_) => lines_added += 1,
+                HunkLine::Remove(_) => lines_removed += 1,
+                HunkLine::Context(_) => {}
+            }
+        }
+
+        match status {
+            FileStatus::Deleted => changes.push(Change::Remove {
+                path: target.clone(),
+            }),
+            _ => changes.push(Change::Write {
+                path: target.clone(),
+                content: patched.render(),
+                mode_from: source.clone().filter(|_| status != FileStatus::Created),
+            }),
+        }
+        if status == FileStatus::Renamed
+            && let Some(source) = source.as_ref()
+        {
+            changes.push(Change::Remove {
+                path: source.clone(),
+            });
+        }
+
+        results.push(PatchFileResult {
+            path: display,
+            status,
+            from: source
+                .filter(|_| status == FileStatus::Renamed)
+                .map(|p| p.to_string_lossy().to_string()),
+            lines_added,
+            lines_removed,
+            hunks,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok((changes, results))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Steps to revert if a later change fails
+enum Undo {
+    Restore { backup: PathBuf, path: PathBuf },
+    Remove(PathBuf),
+}
+
+/// Path next to `path` for a temporary file
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Write all changes, or restore every file if one of them fails
+///
+/// New contents are staged in temporary files first, originals are moved
+/// aside and only removed once every file is in place.
+pub(super) fn commit(changes: &[Change]) -> Result<(), String> {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let mut created_dirs: Vec<PathBuf> = Vec::new();
+    let mut staged: Vec<PathBuf> = Vec::new();
+    let mut undo: Vec<Undo> = Vec::new();
+
+    let result = (|| -> Result<(), String> {
+        for change in changes {
+            let Change::Write {
+                path,
+                content,
+                mode_from,
+            } = change
+            else {
+                continue;
+            };
+            if let Some(parent) = path.parent() {
+                let missing: Vec<PathBuf> = parent
+                    .ancestors()
+                    .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+                    .map(Path::to_path_buf)
+                    .collect();
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("cannot create {}: {}", parent.display(), e))?;
+                created_dirs.extend(missing);
+            }
+            let temp = sibling(path, &format!("dive-patch-{}", id));
+            fs::write(&temp, content)
+                .map_err(|e| format!("cannot write {}: {}", temp.display(), e))?;
+            staged.push(temp.clone());
+            if let Some(permissions) = mode_from
+                .as_ref()
+                .and_then(|from| fs::metadata(from).ok())
+                .map(|meta| meta.permissions())
+            {
+                let _ = fs::set_permissions(&temp, permissions);
+            }
+        }
+
+        let mut staged_files = staged.iter();
+        for change in changes {
+            let path = match change {
+                Change::Write { path, .. } | Change::Remove { path } => path,
+            };
+            if path.exists() {
+                let backup = sibling(path, &format!("dive-patch-{}.orig", id));
+                fs::rename(path, &backup)
+                    .map_err(|e| format!("cannot move {}: {}", path.display(), e))?;
+                undo.push(Undo::Restore {
+                    backup,
+                    path: path.clone(),
+                });
+            }
+            if let Change::Write { .. } = change {
+                let temp = staged_files.next().ok_or("staged file missing")?;
+                fs::rename(temp, path)
+                    .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+                undo.push(Undo::Remove(path.clone()));
+            }
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            for step in undo {
+                if let Undo::Restore { backup, .. } = step {
+                    let _ = fs::remove_file(backup);
+                }
+            }
+            Ok(())
+        }
+        Err(e) => {
+            for step in undo.into_iter().rev() {
+                let _ = match step {
+                    Undo::Restore { backup, path } => fs::rename(backup, path),
+                    Undo::Remove(path) => fs::remove_file(path),
+                };
+            }
+            for temp in staged {
+                let _ = fs::remove_file(temp);
+            }
+            // Deepest first across all files, only directories left empty are removed
+            created_dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+            for dir in created_dirs {
+                let _ = fs::remove_dir(dir);
+            }
+            Err(e)
+        }
+    }
+}
+
+#[tool_router(router = tool_router_patch, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Apply a unified diff that may create, modify, rename and delete several files. Every hunk is checked against the current files first, with optional fuzz, offset and whitespace tolerance, then the user is asked once for all touched paths. Either every file is changed or none is. Returns where each hunk applied",
+        output_schema = cached_schema_for_type::<ApplyPatchOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn apply_patch(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ApplyPatchParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let invalid =
+            |message: String| McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None);
+
+        let patches = parse_patch(&params.patch, params.strip).map_err(invalid)?;
+        let fuzz = params.fuzz.unwrap_or(0);
+        if fuzz > MAX_FUZZ {
+            return Err(invalid(format!("fuzz must be at most {}", MAX_FUZZ)));
+        }
+        let base = match params.base_dir.as_deref() {
+            Some(dir) => PathBuf::from(Self::normalize_path(dir)),
+            None => std::env::current_dir().map_err(|e| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INTERNAL_ERROR,
+                    format!("Failed to get working directory: {}", e),
+                    None,
+                )
+            })?,
+        };
+        if !base.is_dir() {
+            return Err(invalid(format!("{} is not a directory", base.display())));
+        }
+
+        // Resolve every path once, the permission check and the writes use the same paths
+        let resolve = |path: &str| {
+            let resolved = Self::normalize_path(&base.join(path).to_string_lossy());
+            if Path::new(&resolved)
+                .components()
+                .any(|c| c == std::path::Component::ParentDir)
+            {
+                return Err(invalid(format!("{} leaves the base directory", path)));
+            }
+            Ok(resolved)
+        };
+        let mut paths: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+        let mut requests: Vec<(String, &str)> = Vec::new();
+        for patch in &patches {
+            let target = resolve(patch.target())?;
+            if paths.iter().any(|(t, _)| t.as_os_str() == target.as_str()) {
+                return Err(invalid(format!(
+                    "{} is changed more than once in the patch",
+                    target
+                )));
+            }
+            let source = patch.old_path.as_deref().map(resolve).transpose()?;
+
+            let status = patch.status();
+            let operation = match status {
+                FileStatus::Deleted => "delete",
+                _ => "write",
+            };
+            requests.push((target.clone(), operation));
+            if status == FileStatus::Renamed
+                && let Some(source) = source.as_ref()
+            {
+                requests.push((source.clone(), "delete"));
+            }
+            paths.push((PathBuf::from(target), source.map(PathBuf::from)));
+        }
+
+        let dry_run = params.dry_run.unwrap_or(false);
+        if dry_run {
+            for request in requests.iter_mut() {
+                request.1 = "read";
+            }
+        }
+        self.check_paths_permission_with_elicitation(&requests, &peer)
+            .await?;
+
+        let options = MatchOptions {
+            fuzz,
+            ignore_whitespace: params.ignore_whitespace.unwrap_or(false),
+            max_offset: params.max_offset,
+        };
+        let files = run_blocking(move || {
+            let (changes, files) = prepare(&patches, &paths, &options).map_err(|errors| {
+                invalid(format!(
+                    "Patch does not apply, no file was changed:\n{}",
+                    errors.join("\n")
+                ))
+            })?;
+            if !dry_run {
+                commit(&changes).map_err(|e| {
+                    McpError::new(
+                        rmcp::model::ErrorCode::INTERNAL_ERROR,
+                        format!("Failed to apply patch, no file was changed: {}", e),
+                        None,
+                    )
+                })?;
+            }
+            Ok(files)
+        })
+        .await?;
+
+        structured_result(&ApplyPatchOutput {
+            applied: !dry_run,
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+Update greeting
+
+diff --git a/src/main.rs b/src/main.rs
+index 83db48f..bf269f4 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hello\");
++    println!(\"hello, world\");
+ }
+diff --git a/NOTES b/NOTES
+new file mode 100644
+--- /dev/null
++++ b/NOTES
+@@ -0,0 +1 @@
++done
+\\ No newline at end of file
+";
+
+    #[test]
+    fn test_parse_and_apply() {
+        let files = parse_patch(PATCH, None).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].target(), "src/main.rs");
+        assert_eq!(files[0].status(), FileStatus::Modified);
+        assert_eq!(files[1].target(), "NOTES");
+        assert_eq!(files[1].status(), FileStatus::Created);
+
+        let exact = MatchOptions {
+            fuzz: 0,
+            ignore_whitespace: false,
+            max_offset: None,
+        };
+        // The hunk still applies two lines further down
+        let original = TextFile::parse("// header\n\nfn main() {\n    println!(\"hello\");\n}\n");
+        let (patched, hunks) = apply_hunks(&original, &files[0].hunks, &exact).unwrap();
+        assert_eq!(
+            patched.render(),
+            "// header\n\nfn main() {\n    println!(\"hello, world\");\n}\n"
+        );
+        assert_eq!(
+            hunks,
+            vec![HunkResult {
+                line: 3,
+                offset: 2,
+                fuzz: 0
+            }]
+        );
+
+        let (created, _) = apply_hunks(&TextFile::parse(""), &files[1].hunks, &exact).unwrap();
+        assert_eq!(created.render(), "done");
+
+        // A changed context line needs fuzz
+        let drifted = TextFile::parse("fn main() {\n    println!(\"hello\");\n}  // end\n");
+        assert!(apply_hunks(&drifted, &files[0].hunks, &exact).is_err());
+        let fuzzy = MatchOptions { fuzz: 1, ..exact };
+        let (patched, hunks) = apply_hunks(&drifted, &files[0].hunks, &fuzzy).unwrap();
+        assert_eq!(
+            patched.render(),
+            "fn main() {\n    println!(\"hello, world\");\n}  // end\n"
+        );
+        assert_eq!(hunks[0].fuzz, 1);
+
+        assert!(parse_patch("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-a\n", None).is_err());
+    }
+
+    #[test]
+    fn test_line_endings() {
+        // Each line keeps its own ending, added lines take the usual one
+        let file = TextFile::parse("a\r\nb\nc\r\nd");
+        assert!(file.crlf);
+        assert_eq!(file.render(), "a\r\nb\nc\r\nd");
+
+        let patch =
+            "--- a/f\n+++ b/f\n@@ -2,3 +2,4 @@\n b\n-c\n+C\n+e\n d\n\\ No newline at end of file\n";
+        let files = parse_patch(patch, None).unwrap();
+        let exact = MatchOptions {
+            fuzz: 0,
+            ignore_whitespace: false,
+            max_offset: None,
+        };
+        let (patched, _) = apply_hunks(&file, &files[0].hunks, &exact).unwrap();
+        assert_eq!(patched.render(), "a\r\nb\nC\r\ne\r\nd");
+
+        assert!(!TextFile::parse("a\nb\r\nc\n").crlf);
+        assert_eq!(TextFile::parse("").render(), "");
+    }
+    #[test]
+    fn test_commit_rollback() {
+        let root =
+            std::env::temp_dir().join(format!("dive-patch-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), "old\n").unwrap();
+        // The temporary file name still fits, the backup of the original does not
+        let long = root.join("x".repeat(208));
+        fs::write(&long, "keep\n").unwrap();
+        let listing = |dir: &Path| {
+            let mut names: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let before = listing(&root);
+
+        let write = |path: PathBuf, content: &str| Change::Write {
+            path,
+            content: content.to_string(),
+            mode_from: None,
+        };
+        let changes = [
+            write(root.join("a.txt"), "new\n"),
+            write(root.join("new/dir/b.txt"), "b\n"),
+            Change::Remove {
+                path: root.join("gone.txt"),
+            },
+            write(long.clone(), "changed\n"),
+        ];
+        assert!(commit(&changes).is_err());
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "old\n");
+        assert_eq!(fs::read_to_string(&long).unwrap(), "keep\n");
+        assert_eq!(listing(&root), before);
+
+        // The same changes without the failing one are all written
+        assert!(commit(&changes[..2]).is_ok());
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "new\n");
+        assert_eq!(
+            fs::read_to_string(root.join("new/dir/b.txt")).unwrap(),
+            "b\n"
+        );
+        assert_eq!(
+            listing(&root),
+            ["a.txt".to_string(), "new".to_string(), "x".repeat(208)]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
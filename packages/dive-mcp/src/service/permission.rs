@@ -0,0 +1,504 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::service::DiveDefaultService;
+#[cfg(not(feature = "local_ipc"))]
+use rmcp::model::CreateElicitationRequestParam;
+use rmcp::{
+    ErrorData as McpError, Peer,
+    model::{ElicitationAction, ElicitationSchema, EnumSchema, LoggingLevel, PrimitiveSchema},
+    service::RoleServer,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+/// Default time the user has to answer a permission request
+const DEFAULT_ELICITATION_TIMEOUT: u64 = 600;
+/// Characters of a permission request kept in the log, requests can embed
+/// whole snippets of user code
+const LOG_REQUEST_LIMIT: usize = 120;
+
+/// Permission choice enum values
+const PERMISSION_ALWAYS: &str = "always";
+const PERMISSION_SESSION: &str = "session";
+const PERMISSION_YES: &str = "yes";
+const PERMISSION_NO: &str = "no";
+
+/// Path scope enum values
+const SCOPE_FILE: &str = "file";
+const SCOPE_FOLDER: &str = "folder";
+const SCOPE_REPO: &str = "repo";
+
+/// Permission settings, read from the `permission` section of the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PermissionConfig {
+    /// Seconds the user has to answer a permission request before it is denied
+    pub elicitation_timeout_secs: Option<u64>,
+}
+
+/// Permissions granted for the lifetime of one MCP connection
+#[derive(Default)]
+pub struct SessionGrants {
+    pub paths: RwLock<Vec<String>>,
+    pub commands: RwLock<Vec<String>>,
+    pub tools: RwLock<Vec<String>>,
+}
+
+/// Answer to a permission request
+pub enum PermissionChoice {
+    /// Allowed, and the choice should be remembered
+    Always,
+    /// Allowed until the MCP connection closes
+    Session,
+    /// Allowed this time only
+    Once,
+    /// Denied, declined or cancelled by the user
+    Denied,
+    /// The user cannot be asked, the client lacks elicitation or the
+    /// permission policy is non-interactive
+    Unsupported,
+}
+
+impl PermissionChoice {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PermissionChoice::Always => "always",
+            PermissionChoice::Session => "session",
+            PermissionChoice::Once => "once",
+            PermissionChoice::Denied => "denied",
+            PermissionChoice::Unsupported => "unsupported",
+        }
+    }
+}
+
+/// Whether a path lies within one of the granted paths
+///
+/// Grants match whole path components, `/a/b` covers `/a/b/c` but not `/a/bc`.
+pub fn is_granted(abs_path: &str, grants: &[String]) -> bool {
+    let path = Path::new(abs_path);
+    grants.iter().any(|grant| path.starts_with(grant))
+}
+
+/// Paths a grant for a path can cover
+pub struct PathScopes {
+    /// The exact file or directory
+    file: String,
+    /// The directory holding the file, or the directory itself
+    folder: String,
+    /// Root of the enclosing git repository
+    repo: Option<String>,
+}
+
+impl PathScopes {
+    pub fn new(abs_path: &str) -> Self {
+        let path = Path::new(abs_path);
+        let folder = if path.is_dir() {
+            path
+        } else {
+            path.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(path)
+        };
+        let repo = folder
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(|dir| dir.to_string_lossy().to_string());
+
+        Self {
+            file: abs_path.to_string(),
+            folder: folder.to_string_lossy().to_string(),
+            repo,
+        }
+    }
+
+    /// The path granted for a scope value, folders are the default
+    fn target(&self, scope: Option<&str>) -> String {
+        match scope {
+            Some(SCOPE_FILE) => self.file.clone(),
+            Some(SCOPE_REPO) => self.repo.clone().unwrap_or_else(|| self.folder.clone()),
+            _ => self.folder.clone(),
+        }
+    }
+
+    fn schema(&self) -> PrimitiveSchema {
+        let mut values = vec![SCOPE_FOLDER.to_string()];
+        let mut names = vec![format!("This folder ({})", self.folder)];
+        if self.file != self.folder {
+            values.push(SCOPE_FILE.to_string());
+            names.push(format!("Only this file ({})", self.file));
+        }
+        if let Some(repo) = self.repo.as_ref() {
+            values.push(SCOPE_REPO.to_string());
+            names.push(format!("The whole git repository ({})", repo));
+        }
+        PrimitiveSchema::Enum(
+            EnumSchema::new(values)
+                .enum_names(names)
+                .description("What to allow when not choosing \"Yes\" (defaults to this folder)"),
+        )
+    }
+}
+
+/// Create the permission elicitation schema
+fn create_permission_schema(always_label: &str, scopes: Option<&PathScopes>) -> ElicitationSchema {
+    let mut properties = std::collections::BTreeMap::new();
+    properties.insert(
+        "choice".to_string(),
+        PrimitiveSchema::Enum(
+            EnumSchema::new(vec![
+                PERMISSION_ALWAYS.to_string(),
+                PERMISSION_SESSION.to_string(),
+                PERMISSION_YES.to_string(),
+                PERMISSION_NO.to_string(),
+            ])
+            .enum_names(vec![
+                always_label.to_string(),
+                "For this session (until the client disconnects)".to_string(),
+                "Yes (allow this time)".to_string(),
+                "No (deny access)".to_string(),
+            ])
+            .description("Select your permission choice"),
+        ),
+    );
+    if let Some(scopes) = scopes {
+        properties.insert("scope".to_string(), scopes.schema());
+    }
+
+    ElicitationSchema::new(properties).with_required(vec!["choice".to_string()])
+}
+
+/// Create the schema of a one-off confirmation, which cannot be remembered
+fn create_confirmation_schema(yes_label: &str) -> ElicitationSchema {
+    let mut properties = std::collections::BTreeMap::new();
+    properties.insert(
+        "choice".to_string(),
+        PrimitiveSchema::Enum(
+            EnumSchema::new(vec![PERMISSION_YES.to_string(), PERMISSION_NO.to_string()])
+                .enum_names(vec![yes_label.to_string(), "No (cancel)".to_string()])
+                .description("Select your choice"),
+        ),
+    );
+
+    ElicitationSchema::new(properties).with_required(vec!["choice".to_string()])
+}
+
+/// Map the elicitation result to a permission choice and the chosen scope
+fn parse_choice(
+    action: ElicitationAction,
+    content: Option<serde_json::Value>,
+) -> Result<(PermissionChoice, Option<String>), McpError> {
+    match action {
+        ElicitationAction::Accept => {
+            if let Some(content) = content {
+                let choice = content
+                    .get("choice")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(PERMISSION_NO);
+                let scope = content
+                    .get("scope")
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+
+                let choice = match choice {
+                    PERMISSION_ALWAYS => PermissionChoice::Always,
+                    PERMISSION_SESSION => PermissionChoice::Session,
+                    PERMISSION_YES => PermissionChoice::Once,
+                    _ => PermissionChoice::Denied,
+                };
+                Ok((choice, scope))
+            } else {
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    "No permission choice provided".to_string(),
+                    None,
+                ))
+            }
+        }
+        ElicitationAction::Decline | ElicitationAction::Cancel => {
+            Ok((PermissionChoice::Denied, None))
+        }
+    }
+}
+
+/// Shortened request and its hash for the log, so the full text can be
+/// matched without being written out
+fn log_request(message: &str) -> serde_json::Map<String, serde_json::Value> {
+    let mut request: String = message.chars().take(LOG_REQUEST_LIMIT).collect();
+    if request.len() < message.len() {
+        request.push('…');
+    }
+    let mut data = serde_json::Map::new();
+    data.insert("request".to_string(), request.into());
+    data.insert(
+        "requestSha256".to_string(),
+        format!("{:x}", Sha256::digest(message.as_bytes())).into(),
+    );
+    data
+}
+
+/// Outcome of asking the user
+enum Elicited {
+    Answer(ElicitationAction, Option<serde_json::Value>),
+    Unsupported,
+    Expired,
+}
+
+impl DiveDefaultService {
+    /// Ask the user for permission and log the decision
+    pub(super) async fn request_permission(
+        &self,
+        message: String,
+        always_label: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<PermissionChoice, McpError> {
+        self.ask_and_log(message, create_permission_schema(always_label, None), peer)
+            .await
+            .map(|(choice, _)| choice)
+    }
+
+    /// Ask the user to confirm a single action, answered with `Once` or `Denied`
+    pub(super) async fn request_confirmation(
+        &self,
+        message: String,
+        yes_label: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<PermissionChoice, McpError> {
+        self.ask_and_log(message, create_confirmation_schema(yes_label), peer)
+            .await
+            .map(|(choice, _)| choice)
+    }
+
+    /// Ask the user for permission on a path, also returning the path the
+    /// grant should cover
+    pub(super) async fn request_path_permission(
+        &self,
+        message: String,
+        scopes: &PathScopes,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(PermissionChoice, String), McpError> {
+        let schema = create_permission_schema("Always (remember this choice)", Some(scopes));
+        self.ask_and_log(message, schema, peer)
+            .await
+            .map(|(choice, scope)| (choice, scopes.target(scope.as_deref())))
+    }
+
+    async fn ask_and_log(
+        &self,
+        message: String,
+        schema: ElicitationSchema,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(PermissionChoice, Option<String>), McpError> {
+        let elicited = if self.is_non_interactive() {
+            Ok(Elicited::Unsupported)
+        } else {
+            self.elicit(message.clone(), schema, peer).await
+        };
+        let result = match elicited {
+            Ok(Elicited::Answer(action, content)) => parse_choice(action, content),
+            Ok(Elicited::Unsupported) => Ok((PermissionChoice::Unsupported, None)),
+            Ok(Elicited::Expired) => Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                format!(
+                    "Permission denied: no answer within {} seconds",
+                    self.elicitation_timeout.as_secs()
+                ),
+                None,
+            )),
+            Err(e) => Err(e),
+        };
+
+        let (level, mut data) = match result.as_ref() {
+            Ok((choice, scope)) => (
+                LoggingLevel::Info,
+                serde_json::json!({
+                    "decision": choice.as_str(),
+                    "scope": scope,
+                }),
+            ),
+            Err(e) => (
+                LoggingLevel::Warning,
+                serde_json::json!({ "error": e.message }),
+            ),
+        };
+        if let Some(data) = data.as_object_mut() {
+            data.extend(log_request(&message));
+        }
+        self.log(level, "permission", data).await;
+        result
+    }
+
+    /// Ask the user (using MCP peer elicitation)
+    #[cfg(not(feature = "local_ipc"))]
+    async fn elicit(
+        &self,
+        message: String,
+        schema: ElicitationSchema,
+        peer: &Peer<RoleServer>,
+    ) -> Result<Elicited, McpError> {
+        // Check if client supports elicitation
+        if !peer.supports_elicitation() {
+            return Ok(Elicited::Unsupported);
+        }
+
+        let result = peer
+            .create_elicitation_with_timeout(
+                CreateElicitationRequestParam {
+                    message,
+                    requested_schema: schema,
+                },
+                Some(self.elicitation_timeout),
+            )
+            .await;
+
+        match result {
+            Ok(result) => Ok(Elicited::Answer(result.action, result.content)),
+            Err(rmcp::service::ServiceError::Timeout { .. }) => Ok(Elicited::Expired),
+            Err(e) => Err(McpError::new(
+                rmcp::model::ErrorCode::INTERNAL_ERROR,
+                format!("Failed to request permission: {}", e),
+                None,
+            )),
+        }
+    }
+
+    /// Ask the user (using local IPC / libdive)
+    #[cfg(feature = "local_ipc")]
+    async fn elicit(
+        &self,
+        message: String,
+        schema: ElicitationSchema,
+        _peer: &Peer<RoleServer>,
+    ) -> Result<Elicited, McpError> {
+        let result = tokio::time::timeout(
+            self.elicitation_timeout,
+            crate::local_ipc::request_elicitation(message, schema),
+        )
+        .await;
+
+        match result {
+            Ok(Ok(result)) => Ok(Elicited::Answer(
+                result.action,
+                result.content.map(serde_json::Value::Object),
+            )),
+            Ok(Err(e)) => Err(McpError::new(
+                rmcp::model::ErrorCode::INTERNAL_ERROR,
+                format!("Failed to request permission via local IPC: {}", e),
+                None,
+            )),
+            Err(_) => Ok(Elicited::Expired),
+        }
+    }
+}
+
+/// Resolve the elicitation timeout from the command line and config file
+pub fn elicitation_timeout(cli: Option<u64>, config: &PermissionConfig) -> Duration {
+    Duration::from_secs(
+        cli.or(config.elicitation_timeout_secs)
+            .unwrap_or(DEFAULT_ELICITATION_TIMEOUT)
+            .max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_granted() {
+        let grants = vec!["/a/b".to_string(), "/c/file.txt".to_string()];
+        assert!(is_granted("/a/b", &grants));
+        assert!(is_granted("/a/b/c/d.txt", &grants));
+        assert!(!is_granted("/a/bc", &grants));
+        assert!(!is_granted("/a/bc/d.txt", &grants));
+        assert!(!is_granted("/a", &grants));
+        assert!(is_granted("/c/file.txt", &grants));
+        assert!(!is_granted("/c/file.txt.bak", &grants));
+    }
+
+    #[test]
+    fn test_path_scopes() {
+        let root =
+            std::env::temp_dir().join(format!("dive-scopes-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+        let src = root.join("src").to_string_lossy().to_string();
+        let file = root.join("src/main.rs").to_string_lossy().to_string();
+
+        let scopes = PathScopes::new(&file);
+        assert_eq!(scopes.target(None), src);
+        assert_eq!(scopes.target(Some(SCOPE_FOLDER)), src);
+        assert_eq!(scopes.target(Some(SCOPE_FILE)), file);
+        assert_eq!(scopes.target(Some(SCOPE_REPO)), root_str);
+        assert_eq!(scopes.target(Some("unknown")), src);
+
+        // A directory is its own folder, outside a repository the repo scope
+        // falls back to the folder
+        let scopes = PathScopes::new(&src);
+        assert_eq!(scopes.target(Some(SCOPE_FILE)), src);
+        std::fs::remove_dir_all(root.join(".git")).unwrap();
+        let scopes = PathScopes::new(&src);
+        assert_eq!(scopes.target(Some(SCOPE_REPO)), src);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        let accept = |content| parse_choice(ElicitationAction::Accept, Some(content)).unwrap();
+
+        let (choice, scope) = accept(serde_json::json!({ "choice": "always", "scope": "repo" }));
+        assert_eq!(choice.as_str(), "always");
+        assert_eq!(scope.as_deref(), Some("repo"));
+        assert_eq!(
+            accept(serde_json::json!({ "choice": "session" }))
+                .0
+                .as_str(),
+            "session"
+        );
+        assert_eq!(
+            accept(serde_json::json!({ "choice": "yes" })).0.as_str(),
+            "once"
+        );
+        assert_eq!(
+            accept(serde_json::json!({ "choice": "no" })).0.as_str(),
+            "denied"
+        );
+        assert_eq!(
+            accept(serde_json::json!({ "choice": "maybe" })).0.as_str(),
+            "denied"
+        );
+        assert_eq!(accept(serde_json::json!({})).0.as_str(), "denied");
+
+        assert!(parse_choice(ElicitationAction::Accept, None).is_err());
+        for action in [ElicitationAction::Decline, ElicitationAction::Cancel] {
+            let (choice, scope) = parse_choice(action, None).unwrap();
+            assert_eq!(choice.as_str(), "denied");
+            assert!(scope.is_none());
+        }
+    }
+
+    #[test]
+    fn test_elicitation_timeout() {
+        let config = |secs| PermissionConfig {
+            elicitation_timeout_secs: secs,
+        };
+        assert_eq!(
+            elicitation_timeout(None, &config(None)),
+            Duration::from_secs(DEFAULT_ELICITATION_TIMEOUT)
+        );
+        assert_eq!(
+            elicitation_timeout(None, &config(Some(30))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            elicitation_timeout(Some(5), &config(Some(30))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            elicitation_timeout(Some(0), &config(None)),
+            Duration::from_secs(1)
+        );
+    }
+}
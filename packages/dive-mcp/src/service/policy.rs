@@ -0,0 +1,262 @@
+use crate::service::DiveDefaultService;
+use crate::service::command::is_compound;
+use rmcp::model::LoggingLevel;
+use serde::Deserialize;
+use std::path::{Component, Path};
+
+/// Operations approved in the read directories of the policy
+const READ_OPERATIONS: &[&str] = &["read", "list"];
+/// Commands approved in the scratch directories, they only read files
+///
+/// The agent can write to the scratch dirs, so no command here may take an
+/// option that runs another program or read config able to do so: `rg --pre`,
+/// `sort --compress-program` and git with `core.fsmonitor` or `core.pager`
+/// would run a file the agent wrote.
+const NON_DESTRUCTIVE_COMMANDS: &[&str] = &[
+    "ls",
+    "cat",
+    "head",
+    "tail",
+    "wc",
+    "grep",
+    "diff",
+    "cmp",
+    "stat",
+    "file",
+    "du",
+    "tree",
+    "pwd",
+    "echo",
+    "uniq",
+    "cut",
+    "md5sum",
+    "sha256sum",
+];
+/// Characters the policy cannot reason about in a command line
+const UNSUPPORTED_SYNTAX: &[char] = &['\'', '"', '\\', '~', '*', '?', '[', '{', '='];
+/// Level of automatic approvals, shown with the default level of the
+/// process log and of MCP clients so every approval is recorded
+const APPROVAL_LEVEL: LoggingLevel = LoggingLevel::Warning;
+
+/// Permission policy applied when the user cannot be asked, read from the
+/// `policy` section of the config file
+///
+/// Anything not covered by a rule is denied.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    /// Directories where read operations are approved
+    pub read_dirs: Vec<String>,
+    /// Directories where every file operation and read-only commands are approved
+    pub scratch_dirs: Vec<String>,
+    /// Decide with the policy even when the client supports elicitation
+    pub non_interactive: bool,
+}
+
+impl PolicyConfig {
+    /// Add the directories and flags given on the command line
+    pub fn merge(
+        mut self,
+        read_dirs: &[String],
+        scratch_dirs: &[String],
+        non_interactive: bool,
+    ) -> Self {
+        self.read_dirs = self
+            .read_dirs
+            .iter()
+            .chain(read_dirs)
+            .map(|dir| DiveDefaultService::normalize_path(dir))
+            .collect();
+        self.scratch_dirs = self
+            .scratch_dirs
+            .iter()
+            .chain(scratch_dirs)
+            .map(|dir| DiveDefaultService::normalize_path(dir))
+            .collect();
+        self.non_interactive |= non_interactive;
+        self
+    }
+
+    /// Whether the policy has any rule, without rules every request is denied
+    /// with the plain "no elicitation" error
+    pub fn is_empty(&self) -> bool {
+        self.read_dirs.is_empty() && self.scratch_dirs.is_empty() && !self.non_interactive
+    }
+
+    /// The rule approving an operation on a path, if any
+    fn path_rule(&self, abs_path: &str, operation: &str) -> Option<&'static str> {
+        let path = Path::new(abs_path);
+        if self.scratch_dirs.iter().any(|dir| path.starts_with(dir)) {
+            return Some("scratch_dirs");
+        }
+        if READ_OPERATIONS.contains(&operation)
+            && self.read_dirs.iter().any(|dir| path.starts_with(dir))
+        {
+            return Some("read_dirs");
+        }
+        None
+    }
+
+    fn in_scratch_dir(&self, path: &Path) -> bool {
+        self.scratch_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// The rule approving a command run in `cwd`, if any
+    ///
+    /// Only commands of the non-destructive list are approved, run in a
+    /// scratch dir with every argument that can name a path resolving inside
+    /// a scratch dir. Anything the policy cannot parse is left to the user.
+    fn command_rule(&self, command: &str, cwd: &str) -> Option<&'static str> {
+        if is_compound(command)
+            || command.contains(UNSUPPORTED_SYNTAX)
+            || !self.in_scratch_dir(Path::new(cwd))
+        {
+            return None;
+        }
+
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let program_words = NON_DESTRUCTIVE_COMMANDS.iter().find_map(|allowed| {
+            let allowed: Vec<&str> = allowed.split(' ').collect();
+            words.starts_with(&allowed).then_some(allowed.len())
+        })?;
+
+        // Flags are only allowed without attached values that could name a path
+        let args_inside = words[program_words..].iter().all(|arg| {
+            if arg.starts_with('-') {
+                return !arg.contains('/');
+            }
+            let path = Path::new(cwd).join(arg);
+            let path = DiveDefaultService::normalize_path(&path.to_string_lossy());
+            let path = Path::new(&path);
+            !path.components().any(|c| c == Component::ParentDir) && self.in_scratch_dir(path)
+        });
+        args_inside.then_some("scratch_dirs")
+    }
+}
+
+impl DiveDefaultService {
+    /// Whether permission requests skip elicitation and go straight to the policy
+    pub(super) fn is_non_interactive(&self) -> bool {
+        self.policy.non_interactive
+    }
+
+    async fn log_decision(&self, logger: &str, subject: serde_json::Value, rule: Option<&str>) {
+        let decision = if rule.is_some() { "approved" } else { "denied" };
+        let mut data = serde_json::json!({ "decision": decision, "rule": rule });
+        if let (Some(data), Some(subject)) = (data.as_object_mut(), subject.as_object()) {
+            data.extend(subject.clone());
+        }
+        self.log(APPROVAL_LEVEL, logger, data).await;
+    }
+
+    async fn log_policy_decision(&self, subject: serde_json::Value, rule: Option<&str>) {
+        self.log_decision("policy", subject, rule).await;
+    }
+
+    /// Record a request approved by a remembered grant without asking the user
+    pub(super) async fn log_auto_approval(&self, subject: serde_json::Value, grant: &str) {
+        self.log_decision("permission", subject, Some(grant)).await;
+    }
+
+    /// Decide a path operation with the policy, returns whether it is approved
+    pub(super) async fn policy_allows_path(&self, abs_path: &str, operation: &str) -> bool {
+        let rule = self.policy.path_rule(abs_path, operation);
+        self.log_policy_decision(
+            serde_json::json!({ "path": abs_path, "operation": operation }),
+            rule,
+        )
+        .await;
+        rule.is_some()
+    }
+
+    /// Decide a command with the policy, returns whether it is approved
+    pub(super) async fn policy_allows_command(&self, command: &str, cwd: &str) -> bool {
+        let rule = self.policy.command_rule(command, cwd);
+        self.log_policy_decision(serde_json::json!({ "command": command, "cwd": cwd }), rule)
+            .await;
+        rule.is_some()
+    }
+
+    /// Record a request the policy has no rule for
+    pub(super) async fn policy_denies(&self, subject: serde_json::Value) {
+        self.log_policy_decision(subject, None).await;
+    }
+
+    /// Whether denials should mention the policy instead of missing elicitation
+    pub(super) fn has_policy(&self) -> bool {
+        !self.policy.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_rules() {
+        let policy = PolicyConfig {
+            read_dirs: vec!["/data".to_string()],
+            scratch_dirs: vec!["/scratch".to_string()],
+            non_interactive: true,
+        };
+
+        assert_eq!(policy.path_rule("/data/a.txt", "read"), Some("read_dirs"));
+        assert_eq!(policy.path_rule("/data/a.txt", "write"), None);
+        assert_eq!(policy.path_rule("/database/a.txt", "read"), None);
+        assert_eq!(
+            policy.path_rule("/scratch/out", "delete"),
+            Some("scratch_dirs")
+        );
+    }
+
+    #[test]
+    fn test_policy_command_rule() {
+        let policy = PolicyConfig {
+            read_dirs: vec!["/data".to_string()],
+            scratch_dirs: vec!["/scratch".to_string()],
+            non_interactive: true,
+        };
+
+        let approved = [
+            "ls -la",
+            "cat out/a.txt",
+            "uniq -c out/a.txt",
+            "grep -n todo /scratch/src",
+        ];
+        for command in approved {
+            assert_eq!(
+                policy.command_rule(command, "/scratch/build"),
+                Some("scratch_dirs"),
+                "{}",
+                command
+            );
+        }
+        let denied = [
+            "rm -rf out",
+            "python build.py",
+            "git push",
+            "cat /etc/passwd",
+            "cat ../../etc/passwd",
+            "ls; rm -rf /",
+            "cat '/etc/passwd'",
+            "grep --file=/etc/passwd x",
+            "ls ~",
+            // Options and config that run a program the agent may have written
+            "rg --pre ./evil x",
+            "sort --compress-program ./x a.txt",
+            "git status",
+            "git diff",
+            "git log",
+            "git show HEAD",
+        ];
+        for command in denied {
+            assert_eq!(
+                policy.command_rule(command, "/scratch/build"),
+                None,
+                "{}",
+                command
+            );
+        }
+        assert_eq!(policy.command_rule("ls", "/data"), None);
+    }
+}
@@ -0,0 +1,513 @@
+use notify::{RecursiveMode, Watcher};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    model::{
+        GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole,
+    },
+    service::RoleServer,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// File extension of prompt templates
+const TEMPLATE_EXTENSION: &str = "md";
+/// Changes arriving within this window send a single `list_changed` notification
+const CHANGE_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Type of a prompt argument
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ArgumentType {
+    #[default]
+    String,
+    Number,
+    Integer,
+    Boolean,
+}
+
+impl ArgumentType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ArgumentType::String => "string",
+            ArgumentType::Number => "number",
+            ArgumentType::Integer => "integer",
+            ArgumentType::Boolean => "boolean",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArgumentSpec {
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: ArgumentType,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    required: bool,
+    /// Value used when the argument is not given
+    #[serde(default)]
+    default: Option<Value>,
+    /// Values the argument is restricted to
+    #[serde(default, rename = "enum")]
+    choices: Option<Vec<Value>>,
+}
+
+/// Front-matter of a prompt template
+#[derive(Debug, Deserialize)]
+struct FrontMatter {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    arguments: Vec<ArgumentSpec>,
+}
+
+/// Split markdown into its `---` delimited YAML front-matter and the body
+///
+/// Without a closing `---` line the whole content is the body.
+pub(super) fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+
+    let mut end = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            return (Some(&rest[..end]), &rest[end + line.len()..]);
+        }
+        end += line.len();
+    }
+    (None, content)
+}
+
+/// A prompt template loaded from a markdown file
+#[derive(Debug)]
+struct PromptTemplate {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    arguments: Vec<ArgumentSpec>,
+    body: String,
+}
+
+impl PromptTemplate {
+    /// Parse a template: optional `---` delimited YAML front-matter, then the prompt body
+    ///
+    /// A leading `---` block that is not a YAML mapping is a horizontal rule, the
+    /// whole file is then the body.
+    fn parse(default_name: &str, content: &str) -> Result<Self, String> {
+        let (front_matter, body) = match split_front_matter(content) {
+            (Some(yaml), body) => match serde_norway::from_str::<serde_norway::Value>(yaml) {
+                // Empty front-matter means no metadata
+                Ok(value) if value.is_null() => (None, body),
+                Ok(value) if value.is_mapping() => (Some(value), body),
+                _ => (None, content.strip_prefix('\u{feff}').unwrap_or(content)),
+            },
+            (None, body) => (None, body),
+        };
+        let front_matter: Option<FrontMatter> = front_matter
+            .map(serde_norway::from_value)
+            .transpose()
+            .map_err(|e| format!("invalid front-matter: {}", e))?;
+
+        let front_matter = front_matter.unwrap_or(FrontMatter {
+            name: None,
+            title: None,
+            description: None,
+            arguments: Vec::new(),
+        });
+        for (i, argument) in front_matter.arguments.iter().enumerate() {
+            if front_matter.arguments[..i]
+                .iter()
+                .any(|a| a.name == argument.name)
+            {
+                return Err(format!("duplicate argument: {}", argument.name));
+            }
+            if let Some(default) = argument.default.as_ref() {
+                check_value(argument, default)?;
+            }
+        }
+
+        Ok(Self {
+            name: front_matter
+                .name
+                .unwrap_or_else(|| default_name.to_string()),
+            title: front_matter.title,
+            description: front_matter.description,
+            arguments: front_matter.arguments,
+            body: body.to_string(),
+        })
+    }
+
+    fn to_prompt(&self) -> Prompt {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|argument| {
+                let mut description = argument.description.clone().unwrap_or_default();
+                if argument.kind != ArgumentType::String {
+                    description = format!("{} ({})", description, argument.kind.as_str())
+                        .trim()
+                        .to_string();
+                }
+                if let Some(choices) = argument.choices.as_ref() {
+                    let choices: Vec<String> = choices.iter().map(display_value).collect();
+                    description = format!("{} One of: {}", description, choices.join(", "))
+                        .trim()
+                        .to_string();
+                }
+                PromptArgument {
+                    name: argument.name.clone(),
+                    title: None,
+                    description: (!description.is_empty()).then_some(description),
+                    required: Some(argument.required),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut prompt = Prompt::new(
+            self.name.clone(),
+            self.description.clone(),
+            (!arguments.is_empty()).then_some(arguments),
+        );
+        prompt.title = self.title.clone();
+        prompt
+    }
+
+    /// Substitute `{{argument}}` placeholders with the checked arguments
+    fn render(&self, arguments: &JsonObject) -> Result<String, String> {
+        if let Some(unknown) = arguments
+            .keys()
+            .find(|key| !self.arguments.iter().any(|a| &a.name == *key))
+        {
+            return Err(format!("unknown argument: {}", unknown));
+        }
+
+        let mut values = std::collections::HashMap::new();
+        for argument in self.arguments.iter() {
+            let value = match arguments.get(&argument.name) {
+                Some(value) => coerce_value(argument, value)?,
+                None if argument.required => {
+                    return Err(format!("missing required argument: {}", argument.name));
+                }
+                None => argument.default.clone().unwrap_or(Value::Null),
+            };
+            values.insert(argument.name.as_str(), display_value(&value));
+        }
+        Ok(fill_placeholders(&self.body, &values))
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Replace `{{name}}` placeholders in one pass, allowing whitespace inside the braces
+///
+/// Placeholders of unknown names are kept as they are.
+fn fill_placeholders(body: &str, values: &std::collections::HashMap<&str, String>) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + end + 2];
+        result.push_str(&rest[..start]);
+        match values.get(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(placeholder),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Check a value against the argument type and choices
+fn check_value(argument: &ArgumentSpec, value: &Value) -> Result<(), String> {
+    let valid = match argument.kind {
+        ArgumentType::String => value.is_string(),
+        ArgumentType::Number => value.is_number(),
+        ArgumentType::Integer => value.is_i64() || value.is_u64(),
+        ArgumentType::Boolean => value.is_boolean(),
+    };
+    if !valid {
+        return Err(format!(
+            "argument {} must be of type {}",
+            argument.name,
+            argument.kind.as_str()
+        ));
+    }
+    if let Some(choices) = argument.choices.as_ref()
+        && !choices.contains(value)
+    {
+        let choices: Vec<String> = choices.iter().map(display_value).collect();
+        return Err(format!(
+            "argument {} must be one of: {}",
+            argument.name,
+            choices.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Convert an argument to its declared type
+///
+/// MCP clients send prompt arguments as strings, so typed arguments are parsed.
+fn coerce_value(argument: &ArgumentSpec, value: &Value) -> Result<Value, String> {
+    let value = match (argument.kind, value) {
+        (ArgumentType::String, _) | (_, Value::Null) => value.clone(),
+        (_, Value::String(s)) => {
+            let s = s.trim();
+            match argument.kind {
+                ArgumentType::Boolean => match s.to_ascii_lowercase().as_str() {
+                    "true" | "yes" | "1" => Value::Bool(true),
+                    "false" | "no" | "0" => Value::Bool(false),
+                    _ => value.clone(),
+                },
+                _ => serde_json::from_str::<serde_json::Number>(s)
+                    .map(Value::Number)
+                    .unwrap_or_else(|_| value.clone()),
+            }
+        }
+        _ => value.clone(),
+    };
+    check_value(argument, &value)?;
+    Ok(value)
+}
+
+/// Prompt templates stored as markdown files in a directory
+pub struct PromptLibrary {
+    dir: Option<PathBuf>,
+}
+
+impl PromptLibrary {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// Load every template, skipping malformed files and duplicate names
+    fn load(&self) -> Vec<PromptTemplate> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case(TEMPLATE_EXTENSION))
+            })
+            .collect();
+        paths.sort();
+
+        let mut templates: Vec<PromptTemplate> = Vec::new();
+        for path in paths {
+            let default_name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let template = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| PromptTemplate::parse(&default_name, &content));
+            match template {
+                Ok(template) if templates.iter().any(|t| t.name == template.name) => {
+                    tracing::warn!(
+                        "skipping prompt {}: duplicate name {}",
+                        path.display(),
+                        template.name
+                    );
+                }
+                Ok(template) => templates.push(template),
+                Err(e) => tracing::warn!("skipping prompt {}: {}", path.display(), e),
+            }
+        }
+        templates
+    }
+
+    pub fn list(&self) -> Vec<Prompt> {
+        self.load().iter().map(PromptTemplate::to_prompt).collect()
+    }
+
+    pub fn get(
+        &self,
+        name: &str,
+        arguments: Option<JsonObject>,
+    ) -> Result<GetPromptResult, McpError> {
+        let template = self
+            .load()
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INVALID_PARAMS,
+                    format!("Prompt not found: {}", name),
+                    None,
+                )
+            })?;
+
+        let text = template
+            .render(&arguments.unwrap_or_default())
+            .map_err(|e| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INVALID_PARAMS,
+                    format!("Invalid arguments for prompt {}: {}", name, e),
+                    None,
+                )
+            })?;
+
+        Ok(GetPromptResult {
+            description: template.description,
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    /// Send `prompts/list_changed` to the peer whenever a template changes
+    ///
+    /// Runs until the session is cancelled or the peer goes away.
+    pub async fn watch(&self, peer: Peer<RoleServer>, ct: CancellationToken) {
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::warn!("cannot create prompt directory {}: {}", dir.display(), e);
+            return;
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event
+                && event.paths.iter().any(|path| is_template(path))
+            {
+                let _ = tx.send(());
+            }
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                tracing::warn!("cannot watch prompt directory {}: {}", dir.display(), e);
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            tracing::warn!("cannot watch prompt directory {}: {}", dir.display(), e);
+            return;
+        }
+
+        loop {
+            tokio::select! {
+                changed = rx.recv() => if changed.is_none() {
+                    break;
+                },
+                _ = ct.cancelled() => break,
+            }
+            // Editors write files in several steps, wait for them to settle
+            tokio::time::sleep(CHANGE_DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+
+            if let Err(e) = peer.notify_prompt_list_changed().await {
+                tracing::debug!("stop watching prompts: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+fn is_template(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(TEMPLATE_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "---
+name: review
+description: Review a change
+arguments:
+  - name: language
+    required: true
+  - name: strict
+    type: boolean
+    default: false
+---
+Review this {{ language }} code. Strict: {{strict}}. Keep {{other}}.
+";
+
+    #[test]
+    fn test_parse_and_render() {
+        let template = PromptTemplate::parse("file", TEMPLATE).unwrap();
+        assert_eq!(template.name, "review");
+        assert_eq!(template.arguments.len(), 2);
+
+        let mut arguments = JsonObject::new();
+        arguments.insert("language".into(), Value::String("Rust {{strict}}".into()));
+        assert_eq!(
+            template.render(&arguments).unwrap(),
+            "Review this Rust {{strict}} code. Strict: false. Keep {{other}}.\n"
+        );
+
+        arguments.insert("strict".into(), Value::String("yes".into()));
+        assert!(
+            template
+                .render(&arguments)
+                .unwrap()
+                .contains("Strict: true")
+        );
+
+        arguments.insert("strict".into(), Value::String("maybe".into()));
+        assert!(template.render(&arguments).is_err());
+        assert!(template.render(&JsonObject::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_without_front_matter() {
+        let template = PromptTemplate::parse("plain", "Just text").unwrap();
+        assert_eq!(template.name, "plain");
+        assert_eq!(template.body, "Just text");
+        assert!(PromptTemplate::parse("bad", "---\nname: [x]\n---\nx").is_err());
+
+        // A leading horizontal rule is not front-matter
+        for content in [
+            "---\nJust text",
+            "---\nJust text\n---\nmore",
+            "---\n- a list\n---\nbody",
+            "\u{feff}---\nname: [\n---\nx",
+        ] {
+            let template = PromptTemplate::parse("rule", content).unwrap();
+            assert_eq!(template.name, "rule");
+            assert_eq!(template.body, content.trim_start_matches('\u{feff}'));
+        }
+
+        for content in ["---\n---\nbody", "---\r\n---\r\nbody", "---\n\n---\nbody"] {
+            let template = PromptTemplate::parse("empty", content).unwrap();
+            assert_eq!(template.name, "empty");
+            assert!(template.arguments.is_empty());
+            assert_eq!(template.body, "body");
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use crate::service::DiveDefaultService;
+use crate::service::command::{
+    DEFAULT_TIMEOUT, MAX_TIMEOUT, OUTPUT_LIMIT, Outcome, ProcessOutput, run_process,
+};
+use crate::service::permission::PermissionChoice;
+use base64::{Engine as _, engine::general_purpose};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content, ResourceContents},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Permission key of Python snippets, separate from the `run_command` rules
+const PYTHON_PERMISSION: &str = "run_python";
+/// Default memory limit in megabytes
+const DEFAULT_MEMORY_MB: u64 = 1024;
+/// Maximum memory limit in megabytes
+const MAX_MEMORY_MB: u64 = 8192;
+/// Time allowed for creating the virtualenv or installing packages
+const SETUP_TIMEOUT: Duration = Duration::from_secs(300);
+/// Maximum number of scratch files returned per run
+const MAX_RETURNED_FILES: usize = 10;
+/// Scratch files larger than this are listed but not returned
+const MAX_RETURNED_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// Characters of the snippet shown in the permission request
+const PREVIEW_LIMIT: usize = 2000;
+
+/// Virtualenv and scratch directory of one MCP connection
+///
+/// Both live under `~/.dive/host_cache/python/<session>` and are removed
+/// when the connection ends. They only keep the packages of one connection
+/// apart, the code runs with the permissions of the user and has the same
+/// file system and network access.
+pub struct PythonWorkspace {
+    root: Option<PathBuf>,
+    /// Python interpreter of the virtualenv, created on first use
+    venv_python: OnceCell<PathBuf>,
+    /// Serializes package installs into the virtualenv
+    install_lock: Mutex<()>,
+}
+
+impl Default for PythonWorkspace {
+    fn default() -> Self {
+        Self::new(homedir::my_home().ok().flatten().map(|home| {
+            home.join(".dive/host_cache/python")
+                .join(uuid::Uuid::new_v4().simple().to_string())
+        }))
+    }
+}
+
+impl Drop for PythonWorkspace {
+    fn drop(&mut self) {
+        if let Some(root) = self.root.as_ref()
+            && root.exists()
+        {
+            let _ = std::fs::remove_dir_all(root);
+        }
+    }
+}
+
+impl PythonWorkspace {
+    fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            venv_python: OnceCell::new(),
+            install_lock: Mutex::new(()),
+        }
+    }
+
+    fn root(&self) -> Result<&Path, McpError> {
+        self.root.as_deref().ok_or_else(|| {
+            internal_error("Cannot determine the home directory for the Python workspace")
+        })
+    }
+
+    fn scratch_dir(&self) -> Result<PathBuf, McpError> {
+        Ok(self.root()?.join("scratch"))
+    }
+
+    /// Create the virtualenv with the bundled interpreter if it does not exist yet
+    async fn ensure_venv(&self) -> Result<&Path, McpError> {
+        let root = self.root()?;
+        let python = self
+            .venv_python
+            .get_or_try_init(|| async {
+                let venv_dir = root.join("venv");
+                tokio::fs::create_dir_all(root.join("scratch"))
+                    .await
+                    .map_err(|e| {
+                        internal_error(format!("Failed to create Python workspace: {}", e))
+                    })?;
+
+                // A failed or cancelled `uv venv` must not leave a broken virtualenv behind
+                let mut partial = RemoveOnDrop(Some(venv_dir.clone()));
+                let mut cmd = tokio::process::Command::new(uv_path());
+                cmd.arg("venv")
+                    .arg("--quiet")
+                    .arg("--python")
+                    .arg(bundled_python())
+                    .arg(&venv_dir);
+                run_setup(cmd, "create the virtualenv").await?;
+                partial.0 = None;
+
+                Ok::<_, McpError>(if cfg!(windows) {
+                    venv_dir.join("Scripts/python.exe")
+                } else {
+                    venv_dir.join("bin/python")
+                })
+            })
+            .await?;
+        Ok(python)
+    }
+
+    /// Install packages into the virtualenv with uv
+    async fn install(&self, python: &Path, packages: &[String]) -> Result<(), McpError> {
+        let _guard = self.install_lock.lock().await;
+        let mut cmd = tokio::process::Command::new(uv_path());
+        cmd.arg("pip")
+            .arg("install")
+            .arg("--quiet")
+            .arg("--python")
+            .arg(python)
+            .args(packages);
+        run_setup(cmd, "install packages").await
+    }
+}
+
+/// Removes a directory when dropped, unless it was taken out
+struct RemoveOnDrop(Option<PathBuf>);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        if let Some(dir) = self.0.take() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+fn internal_error(message: impl Into<String>) -> McpError {
+    McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message.into(), None)
+}
+
+/// Interpreter installed by the desktop app, or the one on `PATH`
+fn bundled_python() -> PathBuf {
+    let bundled = homedir::my_home().ok().flatten().map(|home| {
+        if cfg!(windows) {
+            home.join(".dive/bin/python/python.exe")
+        } else {
+            home.join(".dive/bin/python/bin/python3")
+        }
+    });
+    match bundled.filter(|path| path.is_file()) {
+        Some(path) => path,
+        None if cfg!(windows) => PathBuf::from("python"),
+        None => PathBuf::from("python3"),
+    }
+}
+
+/// uv next to the `TOOL_UVX_PATH` uvx, in `~/.dive/bin/uv`, or on `PATH`
+fn uv_path() -> PathBuf {
+    let uv = if cfg!(windows) { "uv.exe" } else { "uv" };
+    let from_env = std::env::var_os("TOOL_UVX_PATH")
+        .map(PathBuf::from)
+        .and_then(|uvx| uvx.parent().map(|dir| dir.join(uv)));
+    let bundled = homedir::my_home()
+        .ok()
+        .flatten()
+        .map(|home| home.join(".dive/bin/uv").join(uv));
+
+    [from_env, bundled]
+        .into_iter()
+        .flatten()
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(uv))
+}
+
+/// Run a uv command, failing with its stderr
+async fn run_setup(mut cmd: tokio::process::Command, action: &str) -> Result<(), McpError> {
+    let output = cmd.stdin(Stdio::null()).kill_on_drop(true).output();
+    let output = tokio::time::timeout(SETUP_TIMEOUT, output)
+        .await
+        .map_err(|_| internal_error(format!("Timed out trying to {}", action)))?
+        .map_err(|e| internal_error(format!("Failed to {} with uv: {}", action, e)))?;
+
+    if !output.status.success() {
+        return Err(internal_error(format!(
+            "Failed to {}:\n{}",
+            action,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Modification times of the files under a directory
+fn snapshot(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let mut files = HashMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if let Ok(modified) = metadata.modified() {
+                files.insert(entry.path(), modified);
+            }
+        }
+    }
+    files
+}
+
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "html" => "text/html",
+        "txt" | "md" | "log" => "text/plain",
+        "pdf" => "application/pdf",
+        _ => return None,
+    })
+}
+
+/// Content returned to the model for a file written to the scratch dir
+fn file_content(path: &Path, bytes: Vec<u8>) -> Content {
+    let mime = mime_type(path);
+    let uri = format!("file://{}", path.to_string_lossy().replace('\\', "/"));
+
+    match mime {
+        Some(mime) if mime.starts_with("image/") && mime != "image/svg+xml" => {
+            Content::image(general_purpose::STANDARD.encode(&bytes), mime)
+        }
+        _ => match String::from_utf8(bytes) {
+            Ok(text) => Content::resource(ResourceContents::TextResourceContents {
+                uri,
+                mime_type: Some(mime.unwrap_or("text/plain").to_string()),
+                text,
+                meta: None,
+            }),
+            Err(e) => Content::resource(ResourceContents::BlobResourceContents {
+                uri,
+                mime_type: Some(mime.unwrap_or("application/octet-stream").to_string()),
+                blob: general_purpose::STANDARD.encode(e.as_bytes()),
+                meta: None,
+            }),
+        },
+    }
+}
+
+/// Apply the CPU time and memory limits to the child process
+#[cfg(unix)]
+fn limit_resources(cmd: &mut tokio::process::Command, cpu_secs: u64, memory_mb: u64) {
+    let memory_bytes = memory_mb.saturating_mul(1024 * 1024);
+    // SAFETY: only async-signal-safe libc calls run between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            let cpu = libc::rlimit {
+                rlim_cur: cpu_secs as libc::rlim_t,
+                rlim_max: cpu_secs as libc::rlim_t,
+            };
+            let memory = libc::rlimit {
+                rlim_cur: memory_bytes as libc::rlim_t,
+                rlim_max: memory_bytes as libc::rlim_t,
+            };
+            // Some platforms (macOS) do not enforce every limit, the wall clock
+            // timeout still applies there
+            libc::setrlimit(libc::RLIMIT_CPU, &cpu);
+            libc::setrlimit(libc::RLIMIT_AS, &memory);
+            Ok(())
+        });
+    }
+}
+
+/// Signal that terminated the process, if any
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct RunPythonParams {
+    /// Python source code to run
+    code: String,
+    /// Packages to install with uv before running, e.g. `pandas` or `numpy==2.1`
+    #[serde(default)]
+    packages: Vec<String>,
+    /// Wall-clock timeout in seconds (default 60, max 600)
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    /// CPU time limit in seconds (defaults to the timeout, unix only)
+    #[serde(default)]
+    cpu_secs: Option<u64>,
+    /// Memory limit in megabytes (default 1024, max 8192, unix only)
+    #[serde(default)]
+    memory_mb: Option<u64>,
+}
+
+#[tool_router(router = tool_router_python, vis = "pub")]
+impl DiveDefaultService {
+    /// Check permission to run a Python snippet with elicitation support
+    async fn check_python_permission(
+        &self,
+        code: &str,
+        packages: &[String],
+        peer: &Peer<RoleServer>,
+    ) -> Result<(), McpError> {
+        let mut preview: String = code.chars().take(PREVIEW_LIMIT).collect();
+        if preview.len() < code.len() {
+            preview.push_str("\n...");
+        }
+        let mut message = format!("Permission required to run Python code:\n{}", preview);
+        if !packages.is_empty() {
+            message.push_str(&format!("\n\nwith packages: {}", packages.join(", ")));
+        }
+        message.push_str("\n\nAllow execution?");
+
+        match self
+            .request_tool_permission(
+                PYTHON_PERMISSION,
+                message,
+                "Always (allow Python snippets)",
+                peer,
+            )
+            .await?
+        {
+            PermissionChoice::Always | PermissionChoice::Session | PermissionChoice::Once => Ok(()),
+            PermissionChoice::Denied => Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                "Python code denied by user".to_string(),
+                None,
+            )),
+            PermissionChoice::Unsupported => {
+                // The policy has no rule for Python
+                self.policy_denies(serde_json::json!({ "tool": "run_python" }))
+                    .await;
+                let message = if self.has_policy() {
+                    "Python code denied by permission policy"
+                } else {
+                    "Python code denied: client does not support elicitation for permission request."
+                };
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    message.to_string(),
+                    None,
+                ))
+            }
+        }
+    }
+
+    #[tool(
+        description = "Run a Python snippet in a virtualenv of this session and return its exit status, stdout and stderr. Packages can be installed with uv first. The working directory is a scratch dir (also in the DIVE_SCRATCH_DIR environment variable); files written there, such as matplotlib plots saved with savefig, are returned as images or resources. The code is not sandboxed: it runs with the user's file system and network access. The user must approve the code",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
+    )]
+    async fn run_python(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<RunPythonParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_read_only("execute")?;
+
+        if params.code.trim().is_empty() {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                "Code must not be empty".to_string(),
+                None,
+            ));
+        }
+        if let Some(package) = params
+            .packages
+            .iter()
+            .find(|p| p.trim().is_empty() || p.starts_with('-'))
+        {
+            return Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("Invalid package: {:?}", package),
+                None,
+            ));
+        }
+
+        self.check_python_permission(&params.code, &params.packages, &peer)
+            .await?;
+
+        let timeout = params
+            .timeout_secs
+            .unwrap_or(DEFAULT_TIMEOUT)
+            .clamp(1, MAX_TIMEOUT);
+        #[cfg_attr(not(unix), allow(unused_variables))]
+        let cpu_secs = params.cpu_secs.unwrap_or(timeout).clamp(1, MAX_TIMEOUT);
+        #[cfg_attr(not(unix), allow(unused_variables))]
+        let memory_mb = params
+            .memory_mb
+            .unwrap_or(DEFAULT_MEMORY_MB)
+            .clamp(64, MAX_MEMORY_MB);
+
+        let python = self.python.ensure_venv().await?;
+        if !params.packages.is_empty() {
+            self.python.install(python, &params.packages).await?;
+        }
+
+        let scratch_dir = self.python.scratch_dir()?;
+        let before = snapshot(&scratch_dir);
+
+        // `-I` keeps user site-packages and PYTHON* variables out, the code is read from stdin
+        let mut cmd = tokio::process::Command::new(python);
+        cmd.arg("-I")
+            .arg("-")
+            .current_dir(&scratch_dir)
+            .env("DIVE_SCRATCH_DIR", &scratch_dir)
+            .env("MPLBACKEND", "Agg")
+            .env("PYTHONIOENCODING", "utf-8");
+        #[cfg(unix)]
+        limit_resources(&mut cmd, cpu_secs, memory_mb);
+
+        let output = run_process(
+            cmd,
+            "Python",
+            Some(params.code.clone()),
+            Duration::from_secs(timeout),
+        )
+        .await?;
+
+        let (exit_code, signal, timed_out) = match &output.outcome {
+            Outcome::Exited(status) => (status.code(), exit_signal(status), false),
+            Outcome::TimedOut => (None, None, true),
+        };
+        let ProcessOutput {
+            stdout,
+            stdout_total,
+            stderr,
+            stderr_total,
+            ..
+        } = output;
+
+        // Files created or modified by this run
+        let mut written: Vec<(PathBuf, SystemTime)> = snapshot(&scratch_dir)
+            .into_iter()
+            .filter(|(path, modified)| before.get(path) != Some(modified))
+            .collect();
+        written.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut files = Vec::new();
+        let mut contents = Vec::new();
+        for (path, _) in written.iter() {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            let mut returned = false;
+            if contents.len() < MAX_RETURNED_FILES
+                && size <= MAX_RETURNED_FILE_SIZE
+                && let Ok(bytes) = tokio::fs::read(path).await
+            {
+                contents.push(file_content(path, bytes));
+                returned = true;
+            }
+            files.push(serde_json::json!({
+                "path": path.strip_prefix(&scratch_dir).unwrap_or(path).to_string_lossy(),
+                "size": size,
+                "returned": returned,
+            }));
+        }
+
+        let result = serde_json::json!({
+            "exitCode": exit_code,
+            "signal": signal,
+            "timedOut": timed_out,
+            "stdout": stdout,
+            "stderr": stderr,
+            "stdoutTruncated": stdout_total > OUTPUT_LIMIT,
+            "stderrTruncated": stderr_total > OUTPUT_LIMIT,
+            "scratchDir": scratch_dir.to_string_lossy(),
+            "files": files,
+        });
+        let mut content = vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )];
+        content.extend(contents);
+
+        if timed_out {
+            Ok(CallToolResult::error(content))
+        } else {
+            Ok(CallToolResult::success(content))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("dive-python-{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    #[test]
+    fn test_workspace_removed_on_drop() {
+        let root = temp_root();
+        std::fs::create_dir_all(root.join("venv/bin")).unwrap();
+        std::fs::create_dir_all(root.join("scratch")).unwrap();
+
+        let workspace = PythonWorkspace::new(Some(root.clone()));
+        assert_eq!(workspace.scratch_dir().unwrap(), root.join("scratch"));
+        drop(workspace);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_remove_on_drop() {
+        let dir = temp_root();
+        std::fs::create_dir_all(&dir).unwrap();
+        drop(RemoveOnDrop(Some(dir.clone())));
+        assert!(!dir.exists());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut kept = RemoveOnDrop(Some(dir.clone()));
+        kept.0 = None;
+        drop(kept);
+        assert!(dir.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_and_mime_type() {
+        let dir = temp_root();
+        std::fs::create_dir_all(dir.join("plots")).unwrap();
+        std::fs::write(dir.join("data.csv"), "a,b").unwrap();
+        std::fs::write(dir.join("plots/fig.png"), [0u8; 4]).unwrap();
+
+        let files = snapshot(&dir);
+        assert_eq!(files.len(), 2);
+        assert!(files.contains_key(&dir.join("plots/fig.png")));
+        assert_eq!(mime_type(&dir.join("plots/fig.PNG")), Some("image/png"));
+        assert_eq!(mime_type(&dir.join("data.csv")), Some("text/csv"));
+        assert_eq!(mime_type(&dir.join("model.bin")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+use crate::service::patch::{Change, commit};
+use crate::service::permission::PermissionChoice;
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use regex::{NoExpand, Regex, RegexBuilder};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files larger than this are not searched
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+/// Maximum number of files changed by one call
+const MAX_CHANGED_FILES: usize = 500;
+/// Maximum size of the compiled pattern
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+/// Files listed by name in the preview
+const PREVIEW_FILES: usize = 20;
+/// Changed lines shown in the sample diff
+const SAMPLE_LINES: usize = 10;
+/// Bytes checked for a null byte to detect binary files
+const BINARY_CHECK_SIZE: usize = 8192;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct ReplaceInFilesParams {
+    /// Directory to search, or a single file
+    root: String,
+    /// Text to find, a regular expression when `regex` is true
+    pattern: String,
+    /// Replacement text, `$1` or `${name}` refer to capture groups when `regex` is true
+    replacement: String,
+    /// Treat `pattern` as a regular expression (default false, literal text)
+    regex: Option<bool>,
+    /// Match case-insensitively (default false)
+    case_insensitive: Option<bool>,
+    /// Globs relative to the root selecting the files, like `**/*.rs`. Globs starting with `!` exclude files (default: all files)
+    globs: Option<Vec<String>>,
+    /// Also search hidden files and directories, `.git` is always skipped (default false)
+    include_hidden: Option<bool>,
+    /// Only compute the changes and return the preview, without asking or writing (default false)
+    dry_run: Option<bool>,
+}
+
+/// Structured result of `replace_in_files`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReplaceInFilesOutput {
+    /// Whether the files were written, false for a dry run
+    applied: bool,
+    /// Number of files with matches
+    files_matched: usize,
+    /// Number of replacements over all files
+    total_replacements: usize,
+    /// Files with matches
+    files: Vec<ReplaceFileResult>,
+    /// Number of binary files skipped
+    binary_files_skipped: usize,
+    /// Number of files skipped because they are not UTF-8 text
+    non_utf8_files_skipped: usize,
+    /// Files that could not be read, with the reason
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unreadable: Vec<ReplaceFailure>,
+    /// Summary and sample diff shown to the user
+    preview: String,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct ReplaceFileResult {
+    /// Path relative to the root
+    path: String,
+    /// Number of replacements in the file
+    replacements: usize,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct ReplaceFailure {
+    /// Path relative to the root
+    path: String,
+    /// Why the file could not be read
+    error: String,
+}
+
+/// Files to change found under the root, and the files left alone
+struct Scan {
+    changes: Vec<FileChange>,
+    binary_files_skipped: usize,
+    non_utf8_files_skipped: usize,
+    unreadable: Vec<ReplaceFailure>,
+}
+
+/// New content computed for one file
+struct FileChange {
+    path: PathBuf,
+    display: String,
+    replacements: usize,
+    original: String,
+    replaced: String,
+}
+
+fn build_regex(params: &ReplaceInFilesParams) -> Result<Regex, String> {
+    let pattern = if params.regex.unwrap_or(false) {
+        params.pattern.clone()
+    } else {
+        regex::escape(&params.pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(params.case_insensitive.unwrap_or(false))
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+/// List the files under `root` matching the globs, honoring `.gitignore`
+///
+/// Directories and files the walk cannot read are returned as errors next to
+/// the files found.
+pub(super) fn collect_files(
+    root: &Path,
+    globs: &[String],
+    include_hidden: bool,
+) -> Result<(Vec<PathBuf>, Vec<ignore::Error>), String> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root);
+    for glob in globs {
+        overrides
+            .add(glob)
+            .map_err(|e| format!("Invalid glob {}: {}", glob, e))?;
+    }
+    let overrides = overrides.build().map_err(|e| e.to_string())?;
+
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let walker = ignore::WalkBuilder::new(root)
+        .hidden(!include_hidden)
+        .require_git(false)
+        .overrides(overrides)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        let is_small_file = entry.file_type().is_some_and(|t| t.is_file())
+            && entry.metadata().is_ok_and(|m| m.len() <= MAX_FILE_SIZE);
+        if is_small_file {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+    Ok((files, errors))
+}
+
+/// Path shown to the user, relative to the root
+fn display_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .ok()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Report a walk error against the path it happened on
+fn walk_failure(root: &Path, error: ignore::Error) -> ReplaceFailure {
+    match error {
+        ignore::Error::WithDepth { err, .. } => walk_failure(root, *err),
+        ignore::Error::WithPath { path, err } => ReplaceFailure {
+            path: display_path(root, &path),
+            error: err.to_string(),
+        },
+        error => ReplaceFailure {
+            path: display_path(root, root),
+            error: error.to_string(),
+        },
+    }
+}
+
+/// Read the files and compute the replaced content of those with matches
+///
+/// `expand` replaces `$1` and `${name}` in the replacement with capture groups.
+fn scan(
+    root: &Path,
+    files: Vec<PathBuf>,
+    walk_errors: Vec<ignore::Error>,
+    regex: &Regex,
+    replacement: &str,
+    expand: bool,
+) -> Result<Scan, String> {
+    let mut scan = Scan {
+        changes: Vec::new(),
+        binary_files_skipped: 0,
+        non_utf8_files_skipped: 0,
+        unreadable: walk_errors
+            .into_iter()
+            .map(|error| walk_failure(root, error))
+            .collect(),
+    };
+    for path in files {
+        let display = display_path(root, &path);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                scan.unreadable.push(ReplaceFailure {
+                    path: display,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        // Same check as `is_binary_file`, a null byte in the first chunk
+        if bytes[..bytes.len().min(BINARY_CHECK_SIZE)].contains(&0) {
+            scan.binary_files_skipped += 1;
+            continue;
+        }
+        let Ok(original) = String::from_utf8(bytes) else {
+            scan.non_utf8_files_skipped += 1;
+            continue;
+        };
+        let replacements = regex.find_iter(&original).count();
+        if replacements == 0 {
+            continue;
+        }
+        let replaced = if expand {
+            regex.replace_all(&original, replacement)
+        } else {
+            regex.replace_all(&original, NoExpand(replacement))
+        }
+        .into_owned();
+        if replaced == original {
+            continue;
+        }
+
+        if scan.changes.len() == MAX_CHANGED_FILES {
+            return Err(format!(
+                "More than {} files would change, narrow the root or globs",
+                MAX_CHANGED_FILES
+            ));
+        }
+        scan.changes.push(FileChange {
+            path,
+            display,
+            replacements,
+            original,
+            replaced,
+        });
+    }
+    Ok(scan)
+}
+
+/// Pairs of changed lines, when the replacement kept the line count
+fn changed_lines<'a>(
+    original: &'a str,
+    replaced: &'a str,
+) -> Option<Vec<(usize, &'a str, &'a str)>> {
+    let (old, new): (Vec<&str>, Vec<&str>) =
+        (original.lines().collect(), replaced.lines().collect());
+    if old.len() != new.len() {
+        return None;
+    }
+    Some(
+        old.into_iter()
+            .zip(new)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(index, (old, new))| (index + 1, old, new))
+            .collect(),
+    )
+}
+
+/// Summary of the changes with a short sample diff
+fn preview(params: &ReplaceInFilesParams, root: &str, changes: &[FileChange]) -> String {
+    let total: usize = changes.iter().map(|c| c.replacements).sum();
+    let mut text = format!(
+        "Replace {:?} with {:?} in {} files under {} ({} matches)\n\n",
+        params.pattern,
+        params.replacement,
+        changes.len(),
+        root,
+        total
+    );
+    for change in changes.iter().take(PREVIEW_FILES) {
+        text.push_str(&format!("  {}: {}\n", change.display, change.replacements));
+    }
+    if changes.len() > PREVIEW_FILES {
+        text.push_str(&format!(
+            "  ...and {} more files\n",
+            changes.len() - PREVIEW_FILES
+        ));
+    }
+
+    text.push_str("\nSample:\n");
+    let mut shown = 0;
+    for change in changes {
+        if shown >= SAMPLE_LINES {
+            break;
+        }
+        match changed_lines(&change.original, &change.replaced) {
+            Some(lines) => {
+                for (line, old, new) in lines.into_iter().take(SAMPLE_LINES - shown) {
+                    text.push_str(&format!(
+                        "{}:{}\n- {}\n+ {}\n",
+                        change.display, line, old, new
+                    ));
+                    shown += 1;
+                }
+            }
+            None => {
+                text.push_str(&format!(
+                    "{}: {} matches, changes the number of lines\n",
+                    change.display, change.replacements
+                ));
+                shown += 1;
+            }
+        }
+    }
+    text
+}
+
+#[tool_router(router = tool_router_replace, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Search and replace text in many files at once. Takes a literal or a regular expression, a root directory and optional globs, skips binary files and files ignored by .gitignore. All changes are computed first and shown to the user as a summary with a sample diff, files are only written once the user accepts",
+        output_schema = cached_schema_for_type::<ReplaceInFilesOutput>(),
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn replace_in_files(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<ReplaceInFilesParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let dry_run = params.dry_run.unwrap_or(false);
+        let operation = if dry_run { "read" } else { "write" };
+        self.check_path_permission_with_elicitation(&params.root, operation, &peer)
+            .await?;
+
+        let invalid =
+            |message: String| McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None);
+        let regex = build_regex(&params).map_err(invalid)?;
+        let root = PathBuf::from(Self::normalize_path(&params.root));
+        let display_root = root.to_string_lossy().to_string();
+
+        let walk_root = root.clone();
+        let globs = params.globs.clone().unwrap_or_default();
+        let include_hidden = params.include_hidden.unwrap_or(false);
+        let replacement = params.replacement.clone();
+        let expand = params.regex.unwrap_or(false);
+        let Scan {
+            changes,
+            binary_files_skipped,
+            non_utf8_files_skipped,
+            unreadable,
+        } = run_blocking(move || {
+            let (files, walk_errors) =
+                collect_files(&walk_root, &globs, include_hidden).map_err(invalid)?;
+            scan(&walk_root, files, walk_errors, &regex, &replacement, expand).map_err(invalid)
+        })
+        .await?;
+
+        let mut summary = preview(&params, &display_root, &changes);
+        if non_utf8_files_skipped > 0 {
+            summary.push_str(&format!(
+                "\nSkipped {} files that are not UTF-8 text\n",
+                non_utf8_files_skipped
+            ));
+        }
+        if !unreadable.is_empty() {
+            summary.push_str(&format!("\nCould not read {} files:\n", unreadable.len()));
+            for failure in &unreadable {
+                summary.push_str(&format!("  {}: {}\n", failure.path, failure.error));
+            }
+        }
+        let mut output = ReplaceInFilesOutput {
+            applied: false,
+            files_matched: changes.len(),
+            total_replacements: changes.iter().map(|c| c.replacements).sum(),
+            files: changes
+                .iter()
+                .map(|c| ReplaceFileResult {
+                    path: c.display.clone(),
+                    replacements: c.replacements,
+                })
+                .collect(),
+            binary_files_skipped,
+            non_utf8_files_skipped,
+            unreadable,
+            preview: summary.clone(),
+        };
+        if dry_run || changes.is_empty() {
+            let mut result = structured_result(&output)?;
+            result.content = vec![Content::text(summary)];
+            return Ok(result);
+        }
+
+        // Replacements are confirmed with their preview, even inside allowed directories
+        match self
+            .request_confirmation(
+                format!("{}\nApply these changes?", summary),
+                "Yes (replace in all files)",
+                &peer,
+            )
+            .await?
+        {
+            PermissionChoice::Once | PermissionChoice::Always | PermissionChoice::Session => {}
+            PermissionChoice::Denied => {
+                return Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    "Replacement cancelled by user".to_string(),
+                    None,
+                ));
+            }
+            PermissionChoice::Unsupported => {
+                if !self.policy_allows_path(&display_root, "write").await {
+                    let message = if self.has_policy() {
+                        format!(
+                            "Replacement denied by permission policy: write operation on {}",
+                            display_root
+                        )
+                    } else {
+                        "Replacements must be confirmed by the user. Client does not support elicitation for permission request. Use dry_run to preview them.".to_string()
+                    };
+                    return Err(McpError::new(
+                        rmcp::model::ErrorCode::INVALID_REQUEST,
+                        message,
+                        None,
+                    ));
+                }
+            }
+        }
+
+        // Either every file is written or none is, the user may take a while
+        // to answer and newer edits are never overwritten
+        run_blocking(move || {
+            let stale: Vec<&str> = changes
+                .iter()
+                .filter(|change| {
+                    fs::read_to_string(&change.path)
+                        .map_or(true, |current| current != change.original)
+                })
+                .map(|change| change.display.as_str())
+                .collect();
+            if !stale.is_empty() {
+                return Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    format!(
+                        "Files changed since the preview, no file was changed: {}",
+                        stale.join(", ")
+                    ),
+                    None,
+                ));
+            }
+            let writes: Vec<Change> = changes
+                .into_iter()
+                .map(|change| Change::Write {
+                    mode_from: Some(change.path.clone()),
+                    path: change.path,
+                    content: change.replaced,
+                })
+                .collect();
+            commit(&writes).map_err(|e| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INTERNAL_ERROR,
+                    format!("Failed to replace, no file was changed: {}", e),
+                    None,
+                )
+            })
+        })
+        .await?;
+        output.applied = true;
+
+        let mut text = format!(
+            "Replaced {} matches in {} files",
+            output.total_replacements, output.files_matched
+        );
+        if output.non_utf8_files_skipped > 0 {
+            text.push_str(&format!(
+                "\nSkipped {} files that are not UTF-8 text",
+                output.non_utf8_files_skipped
+            ));
+        }
+        for failure in &output.unreadable {
+            text.push_str(&format!(
+                "\nCould not read {}: {}",
+                failure.path, failure.error
+            ));
+        }
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(text)];
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_preview() {
+        let params = ReplaceInFilesParams {
+            root: ".".to_string(),
+            pattern: "a.b".to_string(),
+            replacement: "$x".to_string(),
+            regex: None,
+            case_insensitive: Some(true),
+            globs: None,
+            include_hidden: None,
+            dry_run: None,
+        };
+        // Literal patterns escape regex syntax
+        let regex = build_regex(&params).unwrap();
+        assert!(regex.is_match("A.B"));
+        assert!(!regex.is_match("axb"));
+
+        let original = "let a.b = 1;\nkeep\nA.b + a.b\n".to_string();
+        let replaced = regex
+            .replace_all(&original, NoExpand(&params.replacement))
+            .into_owned();
+        assert_eq!(replaced, "let $x = 1;\nkeep\n$x + $x\n");
+        assert_eq!(
+            changed_lines(&original, &replaced).unwrap(),
+            vec![
+                (1, "let a.b = 1;", "let $x = 1;"),
+                (3, "A.b + a.b", "$x + $x")
+            ]
+        );
+
+        let changes = vec![FileChange {
+            path: PathBuf::from("src/lib.rs"),
+            display: "src/lib.rs".to_string(),
+            replacements: 3,
+            original,
+            replaced,
+        }];
+        let text = preview(&params, "/repo", &changes);
+        assert!(text.starts_with("Replace \"a.b\" with \"$x\" in 1 files under /repo (3 matches)"));
+        assert!(text.contains("src/lib.rs:3\n- A.b + a.b\n+ $x + $x\n"));
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_scan() {
+        let root =
+            std::env::temp_dir().join(format!("dive-replace-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root.join("locked")).unwrap();
+        std::fs::write(root.join("a.txt"), "foo bar foo\n").unwrap();
+        std::fs::write(root.join("bin.dat"), b"foo\0").unwrap();
+        std::fs::write(root.join("latin1.txt"), b"foo \xe9\n").unwrap();
+        std::fs::write(root.join("locked/b.txt"), "foo\n").unwrap();
+        let locked = |mode| {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(root.join("locked"), std::fs::Permissions::from_mode(mode))
+                .unwrap();
+        };
+        locked(0o000);
+        // Root reads the directory anyway
+        let walk_fails = std::fs::read_dir(root.join("locked")).is_err();
+
+        let (mut files, walk_errors) = collect_files(&root, &[], false).unwrap();
+        assert_eq!(walk_errors.len(), walk_fails as usize);
+        // A file removed after the walk
+        files.push(root.join("gone.txt"));
+        let regex = Regex::new("foo").unwrap();
+        let scan = scan(&root, files, walk_errors, &regex, "baz", false).unwrap();
+        locked(0o755);
+
+        let changed: Vec<&str> = scan.changes.iter().map(|c| c.display.as_str()).collect();
+        if walk_fails {
+            assert_eq!(changed, ["a.txt"]);
+            assert_eq!(scan.unreadable[0].path, "locked");
+        } else {
+            assert_eq!(changed, ["a.txt", "locked/b.txt"]);
+        }
+        assert_eq!(scan.changes[0].replaced, "baz bar baz\n");
+        assert_eq!(scan.changes[0].replacements, 2);
+        assert_eq!(scan.binary_files_skipped, 1);
+        assert_eq!(scan.non_utf8_files_skipped, 1);
+        let last = scan.unreadable.last().unwrap();
+        assert_eq!(last.path, "gone.txt");
+        assert_eq!(scan.unreadable.len(), walk_fails as usize + 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,496 @@
+use crate::service::DiveDefaultService;
+use crate::service::prompts::split_front_matter;
+use base64::{Engine as _, engine::general_purpose};
+use rmcp::{
+    ErrorData as McpError,
+    handler::server::wrapper::Parameters,
+    model::{
+        AnnotateAble, CallToolResult, Content, RawResource, ReadResourceResult, Resource,
+        ResourceContents,
+    },
+    tool, tool_router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Instructions file every skill directory must contain
+const SKILL_FILE: &str = "SKILL.md";
+/// URI scheme skills are exposed under as resources
+const SKILL_SCHEME: &str = "skill://";
+/// Path of the manifest below a skill URI
+const MANIFEST_PATH: &str = "manifest.json";
+/// Limits from the Agent Skills specification
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+const MAX_COMPATIBILITY_LENGTH: usize = 500;
+/// Maximum number of bundled files listed per skill
+const MAX_LISTED_FILES: usize = 500;
+/// Bundled files larger than this are not returned by `load_skill`
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Front-matter of `SKILL.md`
+#[derive(Debug, Deserialize, Serialize)]
+struct SkillManifest {
+    name: String,
+    description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compatibility: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+    #[serde(
+        default,
+        rename = "allowed-tools",
+        skip_serializing_if = "Option::is_none"
+    )]
+    allowed_tools: Option<String>,
+}
+
+impl SkillManifest {
+    /// Check the manifest against the Agent Skills rules
+    fn validate(&self, dir_name: &str) -> Result<(), String> {
+        let name = self.name.as_str();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "name must be 1 to {} characters long",
+                MAX_NAME_LENGTH
+            ));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            || name.starts_with('-')
+            || name.ends_with('-')
+            || name.contains("--")
+        {
+            return Err(format!(
+                "name {:?} may only contain lowercase letters, digits and single hyphens",
+                name
+            ));
+        }
+        if name != dir_name {
+            return Err(format!(
+                "name {:?} does not match the directory name {:?}",
+                name, dir_name
+            ));
+        }
+        if self.description.trim().is_empty() {
+            return Err("description must not be empty".to_string());
+        }
+        if self.description.chars().count() > MAX_DESCRIPTION_LENGTH {
+            return Err(format!(
+                "description is longer than {} characters",
+                MAX_DESCRIPTION_LENGTH
+            ));
+        }
+        if self
+            .compatibility
+            .as_ref()
+            .is_some_and(|c| c.chars().count() > MAX_COMPATIBILITY_LENGTH)
+        {
+            return Err(format!(
+                "compatibility is longer than {} characters",
+                MAX_COMPATIBILITY_LENGTH
+            ));
+        }
+        Ok(())
+    }
+}
+
+struct Skill {
+    dir: PathBuf,
+    manifest: SkillManifest,
+    instructions: String,
+}
+
+/// A skill directory whose manifest could not be loaded
+struct InvalidSkill {
+    dir_name: String,
+    error: String,
+}
+
+type SkillEntry = Result<Skill, InvalidSkill>;
+
+fn load_skill_dir(dir: &Path) -> SkillEntry {
+    let dir_name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let invalid = |error: String| InvalidSkill {
+        dir_name: dir_name.clone(),
+        error,
+    };
+
+    let content = std::fs::read_to_string(dir.join(SKILL_FILE))
+        .map_err(|e| invalid(format!("cannot read {}: {}", SKILL_FILE, e)))?;
+    let (front_matter, body) = split_front_matter(&content);
+    let front_matter =
+        front_matter.ok_or_else(|| invalid(format!("{} has no front-matter", SKILL_FILE)))?;
+    let manifest: SkillManifest = serde_norway::from_str(front_matter)
+        .map_err(|e| invalid(format!("invalid front-matter: {}", e)))?;
+    manifest.validate(&dir_name).map_err(invalid)?;
+
+    Ok(Skill {
+        dir: dir.to_path_buf(),
+        manifest,
+        instructions: body.to_string(),
+    })
+}
+
+/// Files bundled with a skill, relative to its directory
+fn bundled_files(dir: &Path) -> Vec<(String, u64)> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir)
+                && relative != Path::new(SKILL_FILE)
+            {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                files.push((relative, metadata.len()));
+            }
+        }
+    }
+    files.sort();
+    files.truncate(MAX_LISTED_FILES);
+    files
+}
+
+/// Skills installed by Dive, one directory with a `SKILL.md` each
+pub struct SkillLibrary {
+    dir: Option<PathBuf>,
+}
+
+impl SkillLibrary {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// Whether skills are served, they are disabled without a directory
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Load every skill directory, keeping malformed ones so they can be reported
+    fn load(&self) -> Vec<SkillEntry> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut dirs: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        dirs.sort();
+
+        dirs.iter()
+            .map(|dir| {
+                let entry = load_skill_dir(dir);
+                if let Err(invalid) = entry.as_ref() {
+                    tracing::warn!("invalid skill {}: {}", dir.display(), invalid.error);
+                }
+                entry
+            })
+            .collect()
+    }
+
+    /// Find a skill by name, failing with the reason when it is malformed
+    fn find(&self, name: &str) -> Result<Skill, McpError> {
+        let mut available = Vec::new();
+        for entry in self.load() {
+            match entry {
+                Ok(skill) if skill.manifest.name == name => return Ok(skill),
+                Ok(skill) => available.push(skill.manifest.name),
+                Err(invalid) if invalid.dir_name == name => {
+                    return Err(McpError::new(
+                        rmcp::model::ErrorCode::INVALID_PARAMS,
+                        format!("Skill {} is malformed: {}", name, invalid.error),
+                        None,
+                    ));
+                }
+                Err(_) => {}
+            }
+        }
+
+        Err(McpError::new(
+            rmcp::model::ErrorCode::RESOURCE_NOT_FOUND,
+            format!(
+                "Skill not found: {} (available: {})",
+                name,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ),
+            None,
+        ))
+    }
+
+    /// One resource per skill, malformed skills describe what is wrong
+    pub fn list(&self) -> Vec<Resource> {
+        self.load()
+            .into_iter()
+            .map(|entry| match entry {
+                Ok(skill) => {
+                    let mut resource = RawResource::new(
+                        format!("{}{}", SKILL_SCHEME, skill.manifest.name),
+                        skill.manifest.name.clone(),
+                    );
+                    resource.description = Some(skill.manifest.description.clone());
+                    resource.mime_type = Some("text/markdown".to_string());
+                    resource.no_annotation()
+                }
+                Err(invalid) => {
+                    let mut resource = RawResource::new(
+                        format!("{}{}", SKILL_SCHEME, invalid.dir_name),
+                        invalid.dir_name.clone(),
+                    );
+                    resource.description = Some(format!("Invalid skill: {}", invalid.error));
+                    resource.no_annotation()
+                }
+            })
+            .collect()
+    }
+
+    /// Read a `skill://` URI
+    ///
+    /// `skill://<name>` returns the manifest and the instructions,
+    /// `skill://<name>/<path>` the manifest, the instructions or a bundled file alone.
+    pub fn read(&self, uri: &str) -> Result<ReadResourceResult, McpError> {
+        let not_found = || {
+            McpError::new(
+                rmcp::model::ErrorCode::RESOURCE_NOT_FOUND,
+                format!("Resource not found: {}", uri),
+                None,
+            )
+        };
+        let rest = uri.strip_prefix(SKILL_SCHEME).ok_or_else(not_found)?;
+        let (name, path) = match rest.split_once('/') {
+            Some((name, path)) => (name, path.trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let skill = self.find(name)?;
+
+        let manifest = || {
+            serde_json::to_string_pretty(&skill.manifest)
+                .map(|text| ResourceContents::TextResourceContents {
+                    uri: format!("{}{}/{}", SKILL_SCHEME, name, MANIFEST_PATH),
+                    mime_type: Some("application/json".to_string()),
+                    text,
+                    meta: None,
+                })
+                .map_err(|e| {
+                    McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, e.to_string(), None)
+                })
+        };
+        let instructions = || ResourceContents::TextResourceContents {
+            uri: format!("{}{}/{}", SKILL_SCHEME, name, SKILL_FILE),
+            mime_type: Some("text/markdown".to_string()),
+            text: skill.instructions.clone(),
+            meta: None,
+        };
+
+        let contents = match path {
+            "" => vec![manifest()?, instructions()],
+            MANIFEST_PATH => vec![manifest()?],
+            SKILL_FILE => vec![instructions()],
+            path => vec![bundled_file_contents(&skill, path).map_err(|e| {
+                McpError::new(rmcp::model::ErrorCode::RESOURCE_NOT_FOUND, e.message, None)
+            })?],
+        };
+        Ok(ReadResourceResult { contents })
+    }
+}
+
+/// Read a bundled file, refusing paths that leave the skill directory
+fn bundled_file_contents(skill: &Skill, relative: &str) -> Result<ResourceContents, McpError> {
+    let invalid =
+        |message: String| McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None);
+
+    let skill_dir = std::fs::canonicalize(&skill.dir)
+        .map_err(|e| McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, e.to_string(), None))?;
+    let path = std::fs::canonicalize(skill.dir.join(relative))
+        .map_err(|e| invalid(format!("Cannot open skill file {}: {}", relative, e)))?;
+    if !path.starts_with(&skill_dir) || !path.is_file() {
+        return Err(invalid(format!("Not a file of the skill: {}", relative)));
+    }
+
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if size > MAX_FILE_SIZE {
+        return Err(invalid(format!(
+            "Skill file {} is too large ({} bytes, limit {})",
+            relative, size, MAX_FILE_SIZE
+        )));
+    }
+
+    let bytes = std::fs::read(&path)
+        .map_err(|e| invalid(format!("Cannot read skill file {}: {}", relative, e)))?;
+    let uri = format!(
+        "{}{}/{}",
+        SKILL_SCHEME,
+        skill.manifest.name,
+        relative.trim_start_matches("./")
+    );
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => ResourceContents::TextResourceContents {
+            uri,
+            mime_type: None,
+            text,
+            meta: None,
+        },
+        Err(e) => ResourceContents::BlobResourceContents {
+            uri,
+            mime_type: Some("application/octet-stream".to_string()),
+            blob: general_purpose::STANDARD.encode(e.as_bytes()),
+            meta: None,
+        },
+    })
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct LoadSkillParams {
+    /// Name of the skill to load
+    name: String,
+    /// Bundled files to return, relative to the skill directory
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+#[tool_router(router = tool_router_skills, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "Load an installed Dive skill: returns its manifest, its instructions and the list of files bundled with it. Pass `files` to also return the content of bundled files",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn load_skill(
+        &self,
+        Parameters(params): Parameters<LoadSkillParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let skill = self.skills.find(&params.name)?;
+
+        let files: Vec<_> = bundled_files(&skill.dir)
+            .into_iter()
+            .map(|(path, size)| serde_json::json!({ "path": path, "size": size }))
+            .collect();
+        let summary = serde_json::json!({
+            "manifest": skill.manifest,
+            "directory": skill.dir.to_string_lossy(),
+            "files": files,
+        });
+
+        let mut content = vec![
+            Content::text(
+                serde_json::to_string_pretty(&summary).unwrap_or_else(|_| summary.to_string()),
+            ),
+            Content::text(skill.instructions.clone()),
+        ];
+        for file in params.files.iter() {
+            content.push(Content::resource(bundled_file_contents(&skill, file)?));
+        }
+
+        Ok(CallToolResult::success(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(name: &str) -> SkillManifest {
+        SkillManifest {
+            name: name.to_string(),
+            description: "Does things".to_string(),
+            license: None,
+            compatibility: None,
+            metadata: BTreeMap::new(),
+            allowed_tools: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(manifest("pdf-tools").validate("pdf-tools").is_ok());
+        assert!(manifest("pdf-tools").validate("other").is_err());
+        assert!(manifest("PDF").validate("PDF").is_err());
+        assert!(manifest("-pdf").validate("-pdf").is_err());
+        assert!(manifest("pdf--tools").validate("pdf--tools").is_err());
+        assert!(manifest("").validate("").is_err());
+    }
+
+    #[test]
+    fn test_read_sub_uris() {
+        let dir =
+            std::env::temp_dir().join(format!("dive-skills-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("pdf-tools/scripts")).unwrap();
+        std::fs::write(
+            dir.join("pdf-tools/SKILL.md"),
+            "---\nname: pdf-tools\ndescription: Does things\n---\nUse it",
+        )
+        .unwrap();
+        std::fs::write(dir.join("pdf-tools/scripts/run.py"), "print()").unwrap();
+        let library = SkillLibrary::new(Some(dir.clone()));
+
+        let uris = |uri: &str| -> Vec<String> {
+            library
+                .read(uri)
+                .unwrap()
+                .contents
+                .into_iter()
+                .map(|contents| match contents {
+                    ResourceContents::TextResourceContents { uri, .. }
+                    | ResourceContents::BlobResourceContents { uri, .. } => uri,
+                })
+                .collect()
+        };
+        let all = uris("skill://pdf-tools");
+        assert_eq!(
+            all,
+            [
+                "skill://pdf-tools/manifest.json",
+                "skill://pdf-tools/SKILL.md"
+            ]
+        );
+        for uri in all.iter() {
+            assert_eq!(&uris(uri), std::slice::from_ref(uri));
+        }
+        assert_eq!(
+            uris("skill://pdf-tools/scripts/run.py"),
+            ["skill://pdf-tools/scripts/run.py"]
+        );
+
+        for uri in [
+            "skill://pdf-tools/missing.txt",
+            "skill://other",
+            "file:///etc/passwd",
+        ] {
+            let error = library.read(uri).unwrap_err();
+            assert_eq!(error.code, rmcp::model::ErrorCode::RESOURCE_NOT_FOUND);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
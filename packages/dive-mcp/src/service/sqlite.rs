@@ -0,0 +1,551 @@
+use crate::service::permission::PermissionChoice;
+use crate::service::{DiveDefaultService, run_blocking, structured_result};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::{common::cached_schema_for_type, wrapper::Parameters},
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, OpenFlags, Statement, limits::Limit};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Rows returned when no limit is given
+const DEFAULT_ROW_LIMIT: usize = 100;
+/// Maximum number of rows returned by a query
+const MAX_ROW_LIMIT: usize = 1000;
+/// Query timeout when none is given, in seconds
+const DEFAULT_TIMEOUT: u64 = 10;
+/// Maximum query timeout, in seconds
+const MAX_TIMEOUT: u64 = 120;
+/// Virtual machine instructions between two timeout checks
+const PROGRESS_OPS: i32 = 1000;
+/// Characters of a cell shown in the Markdown table
+const CELL_LIMIT: usize = 200;
+/// Blobs up to this size are shown as hex literals
+const BLOB_HEX_LIMIT: usize = 64;
+/// Bound values listed in the confirmation of a write statement
+const CONFIRM_PARAMS: usize = 20;
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct SqliteSchemaParams {
+    /// The path to the SQLite database file
+    path: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct SqliteQueryParams {
+    /// The path to the SQLite database file
+    path: String,
+    /// A single read-only statement (SELECT, WITH, read-only PRAGMA)
+    sql: String,
+    /// Values bound to the `?` placeholders of the statement
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+    /// Maximum number of rows to return (default 100, max 1000)
+    #[serde(default)]
+    limit: Option<usize>,
+    /// Timeout in seconds (default 10, max 120)
+    #[serde(default)]
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct SqliteExecuteParams {
+    /// The path to the SQLite database file
+    path: String,
+    /// A single statement modifying the database (INSERT, UPDATE, DELETE, CREATE, ...)
+    sql: String,
+    /// Values bound to the `?` placeholders of the statement
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+    /// Timeout in seconds (default 10, max 120)
+    #[serde(default)]
+    timeout: Option<u64>,
+}
+
+/// Structured result of `sqlite_query`
+#[derive(Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SqliteQueryOutput {
+    /// Column names, in the order of the values of each row
+    columns: Vec<String>,
+    /// Result rows, one array of values per row
+    rows: Vec<Vec<serde_json::Value>>,
+    /// Number of rows returned
+    row_count: usize,
+    /// Whether more rows were available beyond the limit
+    truncated: bool,
+}
+
+pub(super) fn sql_error(e: rusqlite::Error) -> McpError {
+    let message = match e {
+        rusqlite::Error::SqliteFailure(ref error, _)
+            if error.code == rusqlite::ErrorCode::OperationInterrupted =>
+        {
+            "Statement timed out and was interrupted".to_string()
+        }
+        e => format!("SQLite error: {}", e),
+    };
+    McpError::new(rmcp::model::ErrorCode::INVALID_PARAMS, message, None)
+}
+
+/// Open a database file without creating it, with ATTACH disabled so the
+/// statement cannot reach files outside the checked path
+fn open_database(path: &str, read_only: bool) -> Result<Connection, McpError> {
+    let mode = if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    };
+    let conn =
+        Connection::open_with_flags(path, mode | OpenFlags::SQLITE_OPEN_NO_MUTEX).map_err(|e| {
+            McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("Failed to open database {}: {}", path, e),
+                None,
+            )
+        })?;
+    conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)
+        .map_err(sql_error)?;
+    if read_only {
+        conn.pragma_update(None, "query_only", true)
+            .map_err(sql_error)?;
+    }
+    Ok(conn)
+}
+
+/// Interrupt statements running longer than `timeout` seconds
+pub(super) fn set_timeout(conn: &Connection, timeout: Option<u64>) {
+    let timeout = Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT).clamp(1, MAX_TIMEOUT));
+    let deadline = Instant::now() + timeout;
+    conn.progress_handler(PROGRESS_OPS, Some(move || Instant::now() > deadline));
+}
+
+/// Convert JSON parameters to SQLite values
+fn bind_values(params: &[serde_json::Value]) -> Vec<Value> {
+    params
+        .iter()
+        .map(|param| match param {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(n.as_f64().unwrap_or(0.0)),
+            },
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            other => Value::Text(other.to_string()),
+        })
+        .collect()
+}
+
+/// Convert a column value to JSON, blobs become a hex literal or a size note
+fn json_value(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => serde_json::json!(i),
+        ValueRef::Real(f) => serde_json::json!(f),
+        ValueRef::Text(text) => serde_json::json!(String::from_utf8_lossy(text)),
+        ValueRef::Blob(blob) if blob.len() <= BLOB_HEX_LIMIT => {
+            let hex: String = blob.iter().map(|b| format!("{:02x}", b)).collect();
+            serde_json::json!(format!("x'{}'", hex))
+        }
+        ValueRef::Blob(blob) => serde_json::json!(format!("<blob {} bytes>", blob.len())),
+    }
+}
+
+fn markdown_cell(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => "NULL".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let mut cell: String = text
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+        .chars()
+        .take(CELL_LIMIT)
+        .collect();
+    if text.chars().count() > CELL_LIMIT {
+        cell.push('…');
+    }
+    cell
+}
+
+/// Confirmation asked before a write statement, with the values it binds
+fn confirmation_message(path: &str, sql: &str, params: &[serde_json::Value]) -> String {
+    let mut message = format!("Run this statement on the database {}?\n\n{}", path, sql);
+    if !params.is_empty() {
+        message.push_str("\n\nParameters:");
+    }
+    for (index, param) in params.iter().take(CONFIRM_PARAMS).enumerate() {
+        let text = param.to_string();
+        let mut value: String = text.chars().take(CELL_LIMIT).collect();
+        if text.chars().count() > CELL_LIMIT {
+            value.push('…');
+        }
+        message.push_str(&format!("\n  ?{} = {}", index + 1, value));
+    }
+    if params.len() > CONFIRM_PARAMS {
+        message.push_str(&format!(
+            "\n  ...and {} more",
+            params.len() - CONFIRM_PARAMS
+        ));
+    }
+    message
+}
+
+/// Render the result as a Markdown table
+pub(super) fn markdown_table(output: &SqliteQueryOutput) -> String {
+    if output.columns.is_empty() {
+        return "Statement returned no columns".to_string();
+    }
+
+    let header = output
+        .columns
+        .iter()
+        .map(|column| markdown_cell(&serde_json::json!(column)))
+        .collect::<Vec<_>>();
+    let mut lines = vec![
+        format!("| {} |", header.join(" | ")),
+        format!("|{}", " --- |".repeat(header.len())),
+    ];
+    for row in &output.rows {
+        let cells = row.iter().map(markdown_cell).collect::<Vec<_>>();
+        lines.push(format!("| {} |", cells.join(" | ")));
+    }
+
+    let mut table = lines.join("\n");
+    if output.truncated {
+        table.push_str(&format!(
+            "\n\nShowing the first {} rows, more are available",
+            output.row_count
+        ));
+    } else {
+        table.push_str(&format!("\n\n{} row(s)", output.row_count));
+    }
+    table
+}
+
+fn run_query(params: SqliteQueryParams) -> Result<SqliteQueryOutput, McpError> {
+    let conn = open_database(&params.path, true)?;
+    set_timeout(&conn, params.timeout);
+
+    let mut stmt = conn.prepare(&params.sql).map_err(sql_error)?;
+    if !stmt.readonly() {
+        return Err(McpError::new(
+            rmcp::model::ErrorCode::INVALID_PARAMS,
+            "Only read-only statements are allowed, use sqlite_execute to modify the database"
+                .to_string(),
+            None,
+        ));
+    }
+
+    collect_rows(&mut stmt, &params.params, params.limit)
+}
+
+/// Run a prepared statement and collect up to `limit` rows
+pub(super) fn collect_rows(
+    stmt: &mut Statement<'_>,
+    params: &[serde_json::Value],
+    limit: Option<usize>,
+) -> Result<SqliteQueryOutput, McpError> {
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let limit = limit.unwrap_or(DEFAULT_ROW_LIMIT).clamp(1, MAX_ROW_LIMIT);
+    let values = bind_values(params);
+    let mut result_rows = stmt
+        .query(rusqlite::params_from_iter(values))
+        .map_err(sql_error)?;
+
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    while let Some(row) = result_rows.next().map_err(sql_error)? {
+        if rows.len() == limit {
+            truncated = true;
+            break;
+        }
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            values.push(json_value(row.get_ref(i).map_err(sql_error)?));
+        }
+        rows.push(values);
+    }
+
+    Ok(SqliteQueryOutput {
+        columns,
+        row_count: rows.len(),
+        rows,
+        truncated,
+    })
+}
+
+fn read_schema(path: &str) -> Result<serde_json::Value, McpError> {
+    let conn = open_database(path, true)?;
+    set_timeout(&conn, None);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT name, type, sql FROM sqlite_schema \
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )
+        .map_err(sql_error)?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .map_err(sql_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_error)?;
+
+    let mut tables = Vec::new();
+    for (name, object_type, sql) in objects {
+        let mut columns_stmt = conn
+            .prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1)")
+            .map_err(sql_error)?;
+        let columns = columns_stmt
+            .query_map([&name], |row| {
+                Ok(serde_json::json!({
+                    "name": row.get::<_, String>(0)?,
+                    "type": row.get::<_, String>(1)?,
+                    "notNull": row.get::<_, bool>(2)?,
+                    "default": row.get::<_, Option<String>>(3)?,
+                    "primaryKey": row.get::<_, i64>(4)? > 0,
+                }))
+            })
+            .map_err(sql_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sql_error)?;
+        tables.push(serde_json::json!({
+            "name": name,
+            "type": object_type,
+            "columns": columns,
+            "sql": sql,
+        }));
+    }
+
+    Ok(serde_json::json!({ "path": path, "tables": tables }))
+}
+
+fn run_execute(params: SqliteExecuteParams) -> Result<serde_json::Value, McpError> {
+    let conn = open_database(&params.path, false)?;
+    set_timeout(&conn, params.timeout);
+
+    let mut stmt = conn.prepare(&params.sql).map_err(sql_error)?;
+    let changes = stmt
+        .execute(rusqlite::params_from_iter(bind_values(&params.params)))
+        .map_err(sql_error)?;
+
+    Ok(serde_json::json!({
+        "path": params.path,
+        "changes": changes,
+        "lastInsertRowid": conn.last_insert_rowid(),
+    }))
+}
+
+#[tool_router(router = tool_router_sqlite, vis = "pub")]
+impl DiveDefaultService {
+    #[tool(
+        description = "List the tables and views of a SQLite database file with their columns and CREATE statements. The file is opened read-only",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn sqlite_schema(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<SqliteSchemaParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let result = run_blocking(move || read_schema(&params.path)).await?;
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    #[tool(
+        description = "Run a read-only SQL statement (SELECT, WITH, read-only PRAGMA) on a SQLite database file opened read-only. Returns the rows as structured content and as a Markdown table, up to `limit` rows",
+        output_schema = cached_schema_for_type::<SqliteQueryOutput>(),
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn sqlite_query(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<SqliteQueryParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "read", &peer)
+            .await?;
+
+        let output = run_blocking(move || run_query(params)).await?;
+        let mut result = structured_result(&output)?;
+        result.content = vec![Content::text(markdown_table(&output))];
+        Ok(result)
+    }
+
+    #[tool(
+        description = "Run a single SQL statement modifying a SQLite database file (INSERT, UPDATE, DELETE, CREATE, ...). The user must confirm every statement",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn sqlite_execute(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<SqliteExecuteParams>,
+    ) -> Result<CallToolResult, McpError> {
+        self.check_path_permission_with_elicitation(&params.path, "write", &peer)
+            .await?;
+
+        // Writes are confirmed one by one, even inside allowed directories
+        let message = confirmation_message(&params.path, &params.sql, &params.params);
+        match self
+            .request_confirmation(message, "Yes (run the statement)", &peer)
+            .await?
+        {
+            PermissionChoice::Once | PermissionChoice::Always | PermissionChoice::Session => {}
+            PermissionChoice::Denied => {
+                return Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    "Statement cancelled by user".to_string(),
+                    None,
+                ));
+            }
+            PermissionChoice::Unsupported => {
+                let abs_path = Self::normalize_path(&params.path);
+                if !self.policy_allows_path(&abs_path, "write").await {
+                    let message = if self.has_policy() {
+                        format!(
+                            "Statement denied by permission policy: write operation on {}",
+                            abs_path
+                        )
+                    } else {
+                        "Write statements must be confirmed by the user. Client does not support elicitation for permission request.".to_string()
+                    };
+                    return Err(McpError::new(
+                        rmcp::model::ErrorCode::INVALID_REQUEST,
+                        message,
+                        None,
+                    ));
+                }
+            }
+        }
+
+        let result = run_blocking(move || run_execute(params)).await?;
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_table() {
+        let output = SqliteQueryOutput {
+            columns: vec!["id".to_string(), "note".to_string()],
+            rows: vec![vec![serde_json::json!(1), serde_json::json!("a|b\nc")]],
+            row_count: 1,
+            truncated: false,
+        };
+        assert_eq!(
+            markdown_table(&output),
+            "| id | note |\n| --- | --- |\n| 1 | a\\|b c |\n\n1 row(s)"
+        );
+
+        assert_eq!(json_value(ValueRef::Blob(&[0xde, 0xad])), "x'dead'");
+        assert!(matches!(
+            bind_values(&[serde_json::json!(true), serde_json::json!(1.5)])[..],
+            [Value::Integer(1), Value::Real(_)]
+        ));
+    }
+    #[test]
+    fn test_execute() {
+        let path = std::env::temp_dir()
+            .join(format!("dive-sqlite-{}.db", uuid::Uuid::new_v4().simple()))
+            .to_string_lossy()
+            .to_string();
+        // Databases are never created, an empty file is an empty database
+        std::fs::write(&path, b"").unwrap();
+        let execute = |sql: &str, params: Vec<serde_json::Value>| {
+            run_execute(SqliteExecuteParams {
+                path: path.clone(),
+                sql: sql.to_string(),
+                params,
+                timeout: None,
+            })
+        };
+        execute(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, note TEXT)",
+            vec![],
+        )
+        .unwrap();
+        let result = execute(
+            "INSERT INTO notes (note) VALUES (?), (?)",
+            vec![serde_json::json!("a"), serde_json::json!(null)],
+        )
+        .unwrap();
+        assert_eq!(result["changes"], 2);
+        assert_eq!(result["lastInsertRowid"], 2);
+        let result = execute(
+            "UPDATE notes SET note = ? WHERE id = ?",
+            vec![serde_json::json!("b"), serde_json::json!(1)],
+        )
+        .unwrap();
+        assert_eq!(result["changes"], 1);
+        assert!(execute("INSERT INTO missing VALUES (1)", vec![]).is_err());
+
+        let output = run_query(SqliteQueryParams {
+            path: path.clone(),
+            sql: "SELECT id, note FROM notes ORDER BY id".to_string(),
+            params: vec![],
+            limit: None,
+            timeout: None,
+        })
+        .unwrap();
+        assert_eq!(
+            output.rows,
+            [
+                vec![serde_json::json!(1), serde_json::json!("b")],
+                vec![serde_json::json!(2), serde_json::Value::Null]
+            ]
+        );
+        let _ = std::fs::remove_file(&path);
+
+        let long = "x".repeat(CELL_LIMIT + 10);
+        let message = confirmation_message(
+            "/db",
+            "UPDATE t SET x = ? WHERE id = ?",
+            &[serde_json::json!(long), serde_json::json!(7)],
+        );
+        assert!(message.starts_with("Run this statement on the database /db?"));
+        assert!(message.contains("Parameters:\n  ?1 = \"xxx"));
+        assert!(message.contains("…\n  ?2 = 7"));
+        let params = vec![serde_json::json!(0); CONFIRM_PARAMS + 2];
+        assert!(confirmation_message("/db", "", &params).ends_with("...and 2 more"));
+    }
+}
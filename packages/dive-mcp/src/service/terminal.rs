@@ -0,0 +1,643 @@
+use crate::service::DiveDefaultService;
+use crate::service::permission::PermissionChoice;
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize, native_pty_system};
+use rmcp::{
+    ErrorData as McpError, Peer,
+    handler::server::wrapper::Parameters,
+    model::{CallToolResult, Content},
+    service::RoleServer,
+    tool, tool_router,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Permission key of terminal sessions, separate from the `run_command` rules
+const TERMINAL_PERMISSION: &str = "terminal";
+/// Maximum number of terminal sessions per MCP connection
+const MAX_SESSIONS: usize = 8;
+/// Terminal sessions without any tool call for this long are killed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// How often idle sessions are checked
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Unread output kept per session, older output is dropped
+const BUFFER_LIMIT: usize = 1024 * 1024;
+/// Maximum bytes returned by one read
+const READ_LIMIT: usize = 64 * 1024;
+/// Maximum time a read waits for new output
+const MAX_WAIT_MS: u64 = 10_000;
+
+/// Output of a terminal not yet returned to the model
+#[derive(Default)]
+struct OutputBuffer {
+    data: Vec<u8>,
+    /// Bytes dropped because the buffer was full
+    dropped: usize,
+    /// The pty reached end of file
+    closed: bool,
+}
+
+struct TerminalSession {
+    command: String,
+    cwd: String,
+    output: Arc<Mutex<OutputBuffer>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    child: Mutex<Box<dyn Child + Send + Sync>>,
+    last_active: Mutex<Instant>,
+    // Keeps the pty open for the lifetime of the session
+    _master: Mutex<Box<dyn MasterPty + Send>>,
+}
+
+impl TerminalSession {
+    fn touch(&self) {
+        if let Ok(mut last_active) = self.last_active.lock() {
+            *last_active = Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_active
+            .lock()
+            .map(|t| t.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Exit code, or `None` while the process is running
+    fn exit_code(&self) -> Option<u32> {
+        self.child
+            .lock()
+            .ok()
+            .and_then(|mut child| child.try_wait().ok().flatten())
+            .map(|status| status.exit_code())
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        if let Ok(mut child) = self.child.lock() {
+            let _ = child.kill();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sessions {
+    active: HashMap<String, Arc<TerminalSession>>,
+    /// Sessions still being started, they count against the limit
+    starting: usize,
+}
+
+/// Terminal sessions of one MCP connection
+#[derive(Default)]
+pub struct TerminalManager {
+    sessions: Mutex<Sessions>,
+    next_id: AtomicU64,
+}
+
+impl TerminalManager {
+    fn get(&self, id: &str) -> Option<Arc<TerminalSession>> {
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| sessions.active.get(id).cloned())
+    }
+
+    fn remove(&self, id: &str) -> Option<Arc<TerminalSession>> {
+        self.sessions
+            .lock()
+            .ok()
+            .and_then(|mut sessions| sessions.active.remove(id))
+    }
+
+    /// Reserve a slot for a new session, `None` when the limit is reached
+    fn reserve(&self) -> Option<SessionSlot<'_>> {
+        let mut sessions = self.sessions.lock().ok()?;
+        if sessions.active.len() + sessions.starting >= MAX_SESSIONS {
+            return None;
+        }
+        sessions.starting += 1;
+        Some(SessionSlot(self))
+    }
+}
+
+/// A reserved session slot, released when dropped without a session
+struct SessionSlot<'a>(&'a TerminalManager);
+
+impl SessionSlot<'_> {
+    fn fill(self, id: String, session: Arc<TerminalSession>) {
+        if let Ok(mut sessions) = self.0.sessions.lock() {
+            sessions.active.insert(id, session);
+        }
+    }
+}
+
+impl Drop for SessionSlot<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut sessions) = self.0.sessions.lock() {
+            sessions.starting -= 1;
+        }
+    }
+}
+
+/// Kill the session once it has been idle for too long
+async fn watch_idle(manager: Weak<TerminalManager>, id: String) {
+    loop {
+        tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        match manager.get(&id) {
+            Some(session) if session.idle_for() >= IDLE_TIMEOUT => {
+                tracing::info!("killing idle terminal session {}", id);
+                manager.remove(&id);
+                return;
+            }
+            Some(_) => {}
+            None => return,
+        }
+    }
+}
+
+/// Copy pty output into the session buffer until the pty closes
+fn pump_output(mut reader: Box<dyn Read + Send>, output: Arc<Mutex<OutputBuffer>>) {
+    let mut buffer = [0u8; 8192];
+    loop {
+        let bytes_read = match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let Ok(mut output) = output.lock() else {
+            return;
+        };
+        output.data.extend_from_slice(&buffer[..bytes_read]);
+        if output.data.len() > BUFFER_LIMIT {
+            let overflow = output.data.len() - BUFFER_LIMIT;
+            output.data.drain(..overflow);
+            output.dropped += overflow;
+        }
+    }
+    if let Ok(mut output) = output.lock() {
+        output.closed = true;
+    }
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement) from terminal output
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            if c != '\r' {
+                result.push(c);
+            }
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters end with a byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' {
+                        chars.next_if_eq(&'\\');
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+fn default_shell() -> String {
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string())
+    }
+}
+
+fn session_not_found(id: &str) -> McpError {
+    McpError::new(
+        rmcp::model::ErrorCode::INVALID_PARAMS,
+        format!("Terminal session not found: {}", id),
+        None,
+    )
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct TerminalStartParams {
+    /// The working directory, must be within the allowed directories
+    cwd: String,
+    /// Command line to run, an interactive shell when omitted
+    #[serde(default)]
+    command: Option<String>,
+    /// Terminal height in rows (default 24)
+    #[serde(default)]
+    rows: Option<u16>,
+    /// Terminal width in columns (default 120)
+    #[serde(default)]
+    cols: Option<u16>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct TerminalWriteParams {
+    /// The terminal session id
+    session_id: String,
+    /// Text to send, include "\n" to press enter
+    input: String,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct TerminalReadParams {
+    /// The terminal session id
+    session_id: String,
+    /// Milliseconds to wait for new output when none is available (max 10000)
+    #[serde(default)]
+    wait_ms: Option<u64>,
+}
+
+#[derive(Deserialize, schemars::JsonSchema)]
+struct TerminalKillParams {
+    /// The terminal session id
+    session_id: String,
+}
+
+#[tool_router(router = tool_router_terminal, vis = "pub")]
+impl DiveDefaultService {
+    /// Check permission to start a terminal session with elicitation support
+    ///
+    /// A terminal can run anything once started, so it is granted as a whole
+    /// under its own key instead of a command rule shared with `run_command`.
+    async fn check_terminal_permission(
+        &self,
+        command: &str,
+        cwd: &str,
+        peer: &Peer<RoleServer>,
+    ) -> Result<(), McpError> {
+        let message = format!(
+            "Permission required to start a terminal session running:\n{}\n\nin {}\n\nAllow?",
+            command, cwd
+        );
+        match self
+            .request_tool_permission(
+                TERMINAL_PERMISSION,
+                message,
+                "Always (allow terminal sessions)",
+                peer,
+            )
+            .await?
+        {
+            PermissionChoice::Always | PermissionChoice::Session | PermissionChoice::Once => Ok(()),
+            PermissionChoice::Denied => Err(McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                format!("Terminal session denied by user: {}", command),
+                None,
+            )),
+            // Whatever runs in the PTY can be driven by terminal_write, a pager
+            // escapes to a shell, so the policy never approves a session
+            PermissionChoice::Unsupported => {
+                let message = if self.has_policy() {
+                    self.policy_denies(serde_json::json!({ "command": command, "cwd": cwd }))
+                        .await;
+                    format!(
+                        "Terminal session denied by permission policy, sessions need the user's approval: {}",
+                        command
+                    )
+                } else {
+                    "Terminal session denied: client does not support elicitation for permission request.".to_string()
+                };
+                Err(McpError::new(
+                    rmcp::model::ErrorCode::INVALID_REQUEST,
+                    message,
+                    None,
+                ))
+            }
+        }
+    }
+
+    #[tool(
+        description = "Start a persistent terminal session (PTY) running a command or an interactive shell. Use terminal_write and terminal_read to interact with it",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
+    )]
+    async fn terminal_start(
+        &self,
+        peer: Peer<RoleServer>,
+        Parameters(params): Parameters<TerminalStartParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let slot = self.terminals.reserve().ok_or_else(|| {
+            McpError::new(
+                rmcp::model::ErrorCode::INVALID_REQUEST,
+                format!(
+                    "Too many terminal sessions (max {}), kill one first",
+                    MAX_SESSIONS
+                ),
+                None,
+            )
+        })?;
+
+        // Check permission with elicitation
+        self.check_path_permission_with_elicitation(&params.cwd, "execute", &peer)
+            .await?;
+        let cwd = Self::normalize_path(&params.cwd);
+        let command = match params.command.as_deref().map(str::trim) {
+            Some(command) if !command.is_empty() => command.to_string(),
+            _ => default_shell(),
+        };
+        self.check_terminal_permission(&command, &cwd, &peer)
+            .await?;
+
+        let internal_error =
+            |message: String| McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, None);
+
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: params.rows.unwrap_or(24),
+                cols: params.cols.unwrap_or(120),
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| internal_error(format!("Failed to open terminal: {}", e)))?;
+
+        let mut builder = match params.command.as_deref().map(str::trim) {
+            Some(command) if !command.is_empty() => {
+                let mut builder = if cfg!(windows) {
+                    CommandBuilder::new("cmd")
+                } else {
+                    CommandBuilder::new("sh")
+                };
+                builder.arg(if cfg!(windows) { "/C" } else { "-c" });
+                builder.arg(command);
+                builder
+            }
+            _ => CommandBuilder::new_default_prog(),
+        };
+        builder.cwd(&cwd);
+
+        let child = pair
+            .slave
+            .spawn_command(builder)
+            .map_err(|e| internal_error(format!("Failed to start command: {}", e)))?;
+        drop(pair.slave);
+
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| internal_error(format!("Failed to read terminal: {}", e)))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| internal_error(format!("Failed to write terminal: {}", e)))?;
+
+        let output = Arc::new(Mutex::new(OutputBuffer::default()));
+        {
+            let output = output.clone();
+            std::thread::spawn(move || pump_output(reader, output));
+        }
+
+        let id = format!(
+            "term-{}",
+            self.terminals.next_id.fetch_add(1, Ordering::Relaxed) + 1
+        );
+        let session = Arc::new(TerminalSession {
+            command: command.clone(),
+            cwd: cwd.clone(),
+            output,
+            writer: Mutex::new(writer),
+            child: Mutex::new(child),
+            last_active: Mutex::new(Instant::now()),
+            _master: Mutex::new(pair.master),
+        });
+        slot.fill(id.clone(), session);
+        tokio::spawn(watch_idle(Arc::downgrade(&self.terminals), id.clone()));
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Started terminal session {} running `{}` in {}",
+            id, command, cwd
+        ))]))
+    }
+
+    #[tool(
+        description = "Send input to a terminal session",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false,
+            open_world_hint = true
+        )
+    )]
+    async fn terminal_write(
+        &self,
+        Parameters(params): Parameters<TerminalWriteParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let session = self
+            .terminals
+            .get(&params.session_id)
+            .ok_or_else(|| session_not_found(&params.session_id))?;
+        session.touch();
+
+        let input = params.input;
+        let write_session = session.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut writer = write_session
+                .writer
+                .lock()
+                .map_err(|_| std::io::Error::other("terminal writer poisoned"))?;
+            writer.write_all(input.as_bytes())?;
+            writer.flush()
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r.map_err(|e| e.to_string()))
+        .map_err(|e| {
+            McpError::new(
+                rmcp::model::ErrorCode::INTERNAL_ERROR,
+                format!("Failed to write to terminal: {}", e),
+                None,
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Sent input to {}",
+            params.session_id
+        ))]))
+    }
+
+    #[tool(
+        description = "Read the output of a terminal session produced since the last read",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = false,
+            open_world_hint = false
+        )
+    )]
+    async fn terminal_read(
+        &self,
+        Parameters(params): Parameters<TerminalReadParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let session = self
+            .terminals
+            .get(&params.session_id)
+            .ok_or_else(|| session_not_found(&params.session_id))?;
+        session.touch();
+
+        // Wait for output to arrive
+        let deadline =
+            Instant::now() + Duration::from_millis(params.wait_ms.unwrap_or(0).min(MAX_WAIT_MS));
+        loop {
+            let ready = session
+                .output
+                .lock()
+                .map(|o| !o.data.is_empty() || o.closed)
+                .unwrap_or(true);
+            if ready || Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let (data, dropped, closed) = {
+            let mut output = session.output.lock().map_err(|_| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INTERNAL_ERROR,
+                    "Terminal output poisoned".to_string(),
+                    None,
+                )
+            })?;
+            let take = output.data.len().min(READ_LIMIT);
+            let data: Vec<u8> = output.data.drain(..take).collect();
+            let dropped = std::mem::take(&mut output.dropped);
+            (data, dropped, output.closed && output.data.is_empty())
+        };
+
+        let mut text = strip_ansi(&String::from_utf8_lossy(&data));
+        if dropped > 0 {
+            text = format!("[{} bytes of earlier output dropped]\n{}", dropped, text);
+        }
+
+        let result = serde_json::json!({
+            "sessionId": params.session_id,
+            "output": text,
+            "running": session.exit_code().is_none(),
+            "exitCode": session.exit_code(),
+            "closed": closed,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&result).unwrap_or_else(|_| result.to_string()),
+        )]))
+    }
+
+    #[tool(
+        description = "List the terminal sessions of this connection",
+        annotations(
+            read_only_hint = true,
+            destructive_hint = false,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn terminal_list(&self) -> Result<CallToolResult, McpError> {
+        let sessions: Vec<(String, Arc<TerminalSession>)> = self
+            .terminals
+            .sessions
+            .lock()
+            .map(|s| {
+                s.active
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if sessions.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::text(
+                "No terminal sessions".to_string(),
+            )]));
+        }
+
+        let items: Vec<serde_json::Value> = sessions
+            .iter()
+            .map(|(id, session)| {
+                serde_json::json!({
+                    "sessionId": id,
+                    "command": session.command,
+                    "cwd": session.cwd,
+                    "running": session.exit_code().is_none(),
+                    "exitCode": session.exit_code(),
+                    "idleSecs": session.idle_for().as_secs(),
+                })
+            })
+            .collect();
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&items).unwrap_or_default(),
+        )]))
+    }
+
+    #[tool(
+        description = "Kill a terminal session and its processes",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true,
+            open_world_hint = false
+        )
+    )]
+    async fn terminal_kill(
+        &self,
+        Parameters(params): Parameters<TerminalKillParams>,
+    ) -> Result<CallToolResult, McpError> {
+        // Dropping the session kills the child process
+        self.terminals
+            .remove(&params.session_id)
+            .ok_or_else(|| session_not_found(&params.session_id))?;
+
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Killed terminal session {}",
+            params.session_id
+        ))]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\u{1b}[1;32mok\u{1b}[0m\r\n"), "ok\n");
+        assert_eq!(strip_ansi("\u{1b}]0;title\u{7}$ ls"), "$ ls");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_reserve_slots() {
+        let manager = TerminalManager::default();
+        let slots: Vec<_> = (0..MAX_SESSIONS)
+            .filter_map(|_| manager.reserve())
+            .collect();
+        assert_eq!(slots.len(), MAX_SESSIONS);
+        assert!(manager.reserve().is_none());
+        drop(slots);
+        assert!(manager.reserve().is_some());
+    }
+}
@@ -22,6 +22,69 @@ pub const PLUGIN_CONFIG_FILE: &str = "plugin_config.json";
 
 pub const DEF_MCP_SERVER_NAME: &str = "__SYSTEM_DIVE_SERVER__";
 
+/// Env vars of the builtin MCP server holding the HTTP proxy and CA settings,
+/// each with the desktop env vars it is taken from, in order of preference
+const DEF_MCP_HTTP_ENV: &[(&str, &[&str])] = &[
+    ("DIVE_MCP_PROXY", &["DIVE_MCP_PROXY", "HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+    ("DIVE_MCP_HTTP_PROXY", &["DIVE_MCP_HTTP_PROXY", "HTTP_PROXY", "http_proxy"]),
+    ("DIVE_MCP_NO_PROXY", &["DIVE_MCP_NO_PROXY", "NO_PROXY", "no_proxy"]),
+    ("DIVE_MCP_CA_CERTS", &["DIVE_MCP_CA_CERTS"]),
+    ("DIVE_MCP_INSECURE_HOSTS", &["DIVE_MCP_INSECURE_HOSTS"]),
+];
+
+/// Env vars of the builtin MCP server holding a proxy URL
+const DEF_MCP_PROXY_ENV: &[&str] = &["DIVE_MCP_PROXY", "DIVE_MCP_HTTP_PROXY"];
+
+/// Proxy and CA settings of the desktop process, for the builtin MCP server
+///
+/// The MCP host does not pass its own environment to stdio servers, so the
+/// settings go into the server entry of the MCP config. Proxy credentials are
+/// left out of the config file, the server reads them from its environment.
+fn def_mcp_http_env() -> serde_json::Map<String, serde_json::Value> {
+    let mut env = serde_json::Map::new();
+    for (name, sources) in DEF_MCP_HTTP_ENV {
+        let value = sources
+            .iter()
+            .find_map(|source| std::env::var(source).ok().filter(|value| !value.is_empty()));
+        if let Some(mut value) = value {
+            if DEF_MCP_PROXY_ENV.contains(name) {
+                value = strip_userinfo(&value);
+            }
+            env.insert(name.to_string(), json!(value));
+        }
+    }
+    env
+}
+
+/// The proxy URL without its user name and password
+fn strip_userinfo(proxy: &str) -> String {
+    match url::Url::parse(proxy) {
+        Ok(mut url) if url.has_host() => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        // Not a URL the userinfo could be told apart in, drop everything before the host
+        _ => match proxy.rsplit_once('@') {
+            Some((_, host)) => host.to_string(),
+            None => proxy.to_string(),
+        },
+    }
+}
+
+fn def_mcp_server_config(command: &str) -> serde_json::Value {
+    let mut config = json!({
+        "transport": "stdio",
+        "enabled": true,
+        "command": command
+    });
+    let env = def_mcp_http_env();
+    if !env.is_empty() {
+        config["env"] = serde_json::Value::Object(env);
+    }
+    config
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct McpHost {
     hostname: Arc<Mutex<Option<String>>>,
@@ -229,11 +292,7 @@ impl HostProcess {
         let mcp_config = if self.def_mcp_bin_path.exists() {
             json!({
                 "mcpServers": {
-                    DEF_MCP_SERVER_NAME: {
-                        "transport": "stdio",
-                        "enabled": true,
-                        "command": def_mcp_bin_path
-                    }
+                    DEF_MCP_SERVER_NAME: def_mcp_server_config(&def_mcp_bin_path)
                 }
             })
         } else {
@@ -307,14 +366,33 @@ impl HostProcess {
                     if !servers.contains_key(DEF_MCP_SERVER_NAME) {
                         servers.insert(
                             DEF_MCP_SERVER_NAME.to_string(),
-                            json!({
-                                "transport": "stdio",
-                                "enabled": true,
-                                "command": def_mcp_bin_path
-                            })
+                            def_mcp_server_config(&def_mcp_bin_path)
                         );
                         tokio::fs::write(&mcp_config_path, config.to_string()).await?;
                         log::info!("added {} to mcp_config.json", DEF_MCP_SERVER_NAME);
+                    } else if let Some(server) = servers
+                        .get_mut(DEF_MCP_SERVER_NAME)
+                        .and_then(|s| s.as_object_mut())
+                    {
+                        // Keep the proxy and CA settings in step with the desktop env
+                        let before = server.get("env").cloned();
+                        let mut env = before
+                            .as_ref()
+                            .and_then(|env| env.as_object().cloned())
+                            .unwrap_or_default();
+                        for (name, _) in DEF_MCP_HTTP_ENV {
+                            env.remove(*name);
+                        }
+                        env.extend(def_mcp_http_env());
+                        if env.is_empty() {
+                            server.remove("env");
+                        } else {
+                            server.insert("env".to_string(), serde_json::Value::Object(env));
+                        }
+                        if server.get("env") != before.as_ref() {
+                            tokio::fs::write(&mcp_config_path, config.to_string()).await?;
+                            log::info!("updated the http env of {} in mcp_config.json", DEF_MCP_SERVER_NAME);
+                        }
                     }
                 }
             }